@@ -6,12 +6,17 @@ use serde::{Serialize, Deserialize};
 
 use std::borrow::Cow;
 
+use crate::board::pinout::InterfaceType;
+
 #[derive(PartialEq, Clone, Serialize, Deserialize)]
 pub struct ColorScheme {
     // pub name: &'static str,
     pub name: Cow<'static, str>,
     is_dark: bool,
     colors: [Color32; 4],
+    /// The color of connections of each interface type on the system editor canvas
+    #[serde(default = "default_interface_colors")]
+    interface_colors: Cow<'static, [(InterfaceType, Color32)]>,
 }
 
 impl Default for ColorScheme {
@@ -20,6 +25,27 @@ impl Default for ColorScheme {
     }
 }
 
+impl ColorScheme {
+    /// The color of connections of the given interface type. Types without a color of their
+    /// own are drawn in the body text color.
+    pub fn interface_color(&self, iface_type: InterfaceType) -> Color32 {
+        self.interface_colors.iter()
+            .find(|(t, _)| *t == iface_type)
+            .map_or(self.colors[3], |(_, color)| *color)
+    }
+}
+
+/// Settings saved before color schemes had interface colors get the default ones.
+fn default_interface_colors() -> Cow<'static, [(InterfaceType, Color32)]> {
+    ColorScheme::default().interface_colors
+}
+
+/// The ColorScheme is kept in the egui context's memory, so that the display code of other
+/// modules can use it.
+pub fn get_colorscheme(ctx: &egui::Context) -> ColorScheme {
+    ctx.data(|data| data.get_temp(egui::Id::new("colorscheme"))).unwrap_or_default()
+}
+
 pub fn set_colorscheme(ctx: &egui::Context, cs: ColorScheme) {
     // get current style
     let mut style = (*ctx.style()).clone();
@@ -44,6 +70,7 @@ pub fn set_colorscheme(ctx: &egui::Context, cs: ColorScheme) {
     style.visuals.error_fg_color = cs.colors[3];
 
     ctx.set_style(style);
+    ctx.data_mut(|data| data.insert_temp(egui::Id::new("colorscheme"), cs));
 }
 
 // TODO -- make these serializable in a toml file for addition of new ones
@@ -57,6 +84,7 @@ pub const SOLARIZED_DARK: ColorScheme = ColorScheme {
         Color32::from_rgb( 88, 110, 117),   // Base 01 (secondary text)
         Color32::from_rgb(131, 148, 150),   // Base 0 (body text)
     ],
+    interface_colors: Cow::Borrowed(SOLARIZED_INTERFACE_COLORS),
 };
 
 pub const SOLARIZED_LIGHT: ColorScheme = ColorScheme {
@@ -68,6 +96,7 @@ pub const SOLARIZED_LIGHT: ColorScheme = ColorScheme {
         Color32::from_rgb(147, 161, 161),   // Base 1 (secondary text)
         Color32::from_rgb(101, 123, 131),   // Base 00 (body text)
     ],
+    interface_colors: Cow::Borrowed(SOLARIZED_INTERFACE_COLORS),
 };
 
 pub const INDUSTRIAL_DARK: ColorScheme = ColorScheme {
@@ -78,9 +107,39 @@ pub const INDUSTRIAL_DARK: ColorScheme = ColorScheme {
         Color32::from_rgb(42,   42,  42),   // Base 2 (background highlights)
         Color32::from_rgb(204, 204, 204),   // Base 1 (secondary text)
         Color32::from_rgb(248,  81,  73),   // Base 00 (body text)
-    ]
+    ],
+    interface_colors: Cow::Borrowed(&[
+        (InterfaceType::I2C, Color32::RED),
+        (InterfaceType::UART, Color32::BLUE),
+        (InterfaceType::SPI, Color32::YELLOW),
+        (InterfaceType::NONE, Color32::GREEN),
+        (InterfaceType::I2S, Color32::from_rgb(255, 0, 255)),
+        (InterfaceType::CAN, Color32::from_rgb(0, 200, 200)),
+        (InterfaceType::USB, Color32::from_rgb(130, 130, 255)),
+        (InterfaceType::ONEWIRE, Color32::BROWN),
+        (InterfaceType::SWD, Color32::GOLD),
+        (InterfaceType::ADC, Color32::LIGHT_GREEN),
+        (InterfaceType::ANALOG, Color32::LIGHT_GREEN),
+        (InterfaceType::POWER, Color32::from_rgb(255, 140, 0)),
+        (InterfaceType::GND, Color32::DARK_GRAY),
+        (InterfaceType::GPIO, Color32::WHITE),
+        (InterfaceType::PWM, Color32::WHITE),
+    ]),
 };
 
+/// The Solarized accent colors, shared by the light and dark variants.
+const SOLARIZED_INTERFACE_COLORS: &[(InterfaceType, Color32)] = &[
+    (InterfaceType::I2C, Color32::from_rgb(220,  50,  47)),     // red
+    (InterfaceType::UART, Color32::from_rgb( 38, 139, 210)),    // blue
+    (InterfaceType::SPI, Color32::from_rgb(181, 137,   0)),     // yellow
+    (InterfaceType::NONE, Color32::from_rgb(133, 153,   0)),    // green
+    (InterfaceType::I2S, Color32::from_rgb(211,  54, 130)),     // magenta
+    (InterfaceType::CAN, Color32::from_rgb( 42, 161, 152)),     // cyan
+    (InterfaceType::USB, Color32::from_rgb(108, 113, 196)),     // violet
+    (InterfaceType::POWER, Color32::from_rgb(203,  75,  22)),   // orange
+    (InterfaceType::GND, Color32::from_rgb( 88, 110, 117)),     // base 01
+];
+
 pub const SYSTEM_COLORSCHEMES: [ColorScheme; 3] = [
     SOLARIZED_DARK,
     SOLARIZED_LIGHT,
@@ -18,6 +18,7 @@ use toml::macros::insert_toml;
 
 // Separate modules
 use crate::board;
+use crate::board::validation::{BoardReport, Severity};
 use crate::project::Project;
 
 pub mod icons;
@@ -39,12 +40,32 @@ pub struct IronCoderOptions {
     /// The log level, one of INFO, WARN, DEBUG, TRACE. Default if INFO.
     #[arg(short, long)]
     pub verbosity: Option<String>,
-    /// An alternative path to look for the Boards directory.
+    /// An additional directory to look for boards in. Can be given more than once, and
+    /// boards in later directories override boards with the same name in earlier ones.
     #[arg(short, long)]
-    pub boards_directory: Option<PathBuf>,
+    pub boards_directory: Vec<PathBuf>,
     /// Turn app persistence on or off. Default is true.
     #[arg(short, long)]
     pub persistence: Option<bool>,
+    /// Validate the boards library, print a report of any problems, and exit.
+    #[arg(long)]
+    #[serde(default)]
+    pub validate_boards: bool,
+}
+
+impl IronCoderOptions {
+    /// The board libraries implied by these options, in order: the bundled library,
+    /// the per-user library, then any extra directories given on the command line.
+    pub fn board_roots(&self) -> Vec<board::BoardRoot> {
+        let mut roots = vec![board::BoardRoot::new(board::BoardRootKind::Bundled, board::BUNDLED_BOARDS_DIR)];
+        if let Some(storage_dir) = eframe::storage_dir("Iron Coder") {
+            roots.push(board::BoardRoot::new(board::BoardRootKind::User, storage_dir.join("boards")));
+        }
+        for dir in self.boards_directory.iter() {
+            roots.push(board::BoardRoot::new(board::BoardRootKind::Extra, dir.clone()));
+        }
+        roots
+    }
 }
 
 // The current warning flags
@@ -74,6 +95,9 @@ pub struct Git {
 pub struct Settings {
     pub colorscheme: ColorScheme,
     pub ui_scale: f32,
+    /// Extra board libraries, layered on top of the ones given on the command line
+    #[serde(default)]
+    pub boards_directories: Vec<PathBuf>,
 }
 
 /// The current GUI mode
@@ -92,11 +116,22 @@ pub struct IronCoderApp {
     display_about: bool,
     display_settings: bool,
     display_boards_window: bool,
+    display_board_library: bool,
     // #[serde(skip)]
     // modal: Option<Modal>,
     mode: Mode,
     #[serde(skip)]
     boards: Vec<board::Board>,
+    /// The board libraries that `boards` was loaded from
+    #[serde(skip)]
+    board_roots: Vec<board::BoardRoot>,
+    /// Decodes the images and BSPs of `boards` in the background, while they're loading
+    #[serde(skip)]
+    board_loader: Option<board::loader::BoardLoader>,
+    #[serde(skip)]
+    board_reports: Option<Vec<(board::BoardRoot, Vec<BoardReport>)>>,
+    // options always come from the command line, so don't persist them
+    #[serde(skip)]
     options: IronCoderOptions,
 
     warning_flags: Warnings,
@@ -106,17 +141,21 @@ pub struct IronCoderApp {
 
 impl Default for IronCoderApp {
     fn default() -> Self {
-        // Populate the boards
-        let boards_dir = Path::new("./iron-coder-boards"); // consider making this a global macro
-        let boards: Vec<board::Board> = board::get_boards(boards_dir);
+        // Populate the boards from their manifests; they're decoded once the app starts
+        let board_roots = IronCoderOptions::default().board_roots();
+        let boards: Vec<board::Board> = board::get_manifests_from_roots(&board_roots);
         Self {
             project: Project::default(),
             display_about: false,
             display_settings: false,
             display_boards_window: false,
+            display_board_library: false,
             // modal: None,
             mode: Mode::EditProject,
             boards: boards,
+            board_roots,
+            board_loader: None,
+            board_reports: None,
             options: IronCoderOptions::default(),
             // Warning Flags
             warning_flags: Warnings {
@@ -138,6 +177,7 @@ impl Default for IronCoderApp {
             settings: Settings {
                 colorscheme: colorscheme::INDUSTRIAL_DARK,
                 ui_scale: 1.0,
+                boards_directories: Vec::new(),
             },
         }
     }
@@ -163,7 +203,12 @@ impl IronCoderApp {
         app.options = options;
         info!("Reloading last project and assets...");
         app.set_colorscheme(&cc.egui_ctx);
-        app.project.known_boards = app.boards.clone();
+        if app.board_roots() != app.board_roots {
+            app.reload_boards();
+        } else {
+            app.decode_boards();
+        }
+        app.project.set_library_boards(app.boards.clone(), board::loader::default_cache_dir());
         match app.project.reload() {
             Ok(_) => (),
             Err(e) => warn!("error reloading project from disk! {:?}", e),
@@ -172,6 +217,73 @@ impl IronCoderApp {
         return app;
     }
 
+    /// The board libraries to load boards from, in order: the ones from the command line
+    /// options, followed by the extra ones from the app settings. The project-local library
+    /// is layered on top of these by the Project itself.
+    fn board_roots(&self) -> Vec<board::BoardRoot> {
+        let mut roots = self.options.board_roots();
+        for dir in self.settings.boards_directories.iter() {
+            roots.push(board::BoardRoot::new(board::BoardRootKind::Extra, dir.clone()));
+        }
+        roots
+    }
+
+    /// Reload the boards from all of the board libraries, and pass them along to the project.
+    fn reload_boards(&mut self) {
+        self.board_roots = self.board_roots();
+        info!("loading boards from {} board libraries", self.board_roots.len());
+        self.boards = board::get_manifests_from_roots(&self.board_roots);
+        self.board_reports = None;
+        self.project.set_library_boards(self.boards.clone(), board::loader::default_cache_dir());
+        self.decode_boards();
+    }
+
+    /// Start decoding the images and BSPs of the boards in the background. The boards are
+    /// updated as they're decoded, see `poll_board_loader`.
+    fn decode_boards(&mut self) {
+        self.board_loader = Some(board::loader::BoardLoader::start(&self.boards, board::loader::default_cache_dir()));
+    }
+
+    /// Merge the boards that have been decoded since the last frame, and pass them along to
+    /// the project. The project-local boards are decoded by the project itself.
+    fn poll_board_loader(&mut self, ctx: &egui::Context) {
+        let mut loading = self.project.poll_board_loader();
+        if let Some(loader) = self.board_loader.as_mut() {
+            let changed = loader.poll(&mut self.boards);
+            if !changed.is_empty() {
+                self.project.update_library_boards(&self.boards, &changed);
+            }
+            if loader.is_finished() {
+                info!("finished decoding boards");
+                self.board_loader = None;
+            } else {
+                loading = true;
+            }
+        }
+        if loading {
+            // the workers don't wake up the UI, so check on them again soon
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+    }
+
+    /// Show the progress of the boards being decoded in the bottom right corner.
+    pub fn display_board_loading_progress(&mut self, ctx: &egui::Context) {
+        let Some(loader) = self.board_loader.as_ref() else {
+            return;
+        };
+        let (done, total) = loader.progress();
+        egui::Area::new(egui::Id::new("board_loading_progress"))
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.add(egui::ProgressBar::new(done as f32 / total.max(1) as f32)
+                        .desired_width(200.0)
+                        .text(format!("loading boards {}/{}", done, total)));
+                });
+            });
+    }
+
     /// Set the colorscheme for the app
     fn set_colorscheme(&self, ctx: &egui::Context) {
         colorscheme::set_colorscheme(ctx, self.settings.colorscheme.clone());
@@ -182,6 +294,7 @@ impl IronCoderApp {
         let Self {
             display_about,
             display_settings,
+            display_board_library,
             mode,
             project,
             ..
@@ -235,7 +348,7 @@ impl IronCoderApp {
                                     ui.close_menu();
                                 },
                                 Err(e) => {
-                                    error!("error opening project: {:?}", e);
+                                    error!("error opening project: {}", e);
                                 },
                             }
                         }
@@ -252,7 +365,7 @@ impl IronCoderApp {
                                     // wants to leave the current project, and probably save
                                     // the project in it's current state.
                                     *project = Project::default();
-                                    project.known_boards = self.boards.clone();
+                                    project.set_library_boards(self.boards.clone(), board::loader::default_cache_dir());
                                     *mode = Mode::EditProject;
                                 },
                             }
@@ -266,6 +379,14 @@ impl IronCoderApp {
                             *display_settings = !*display_settings;
                         }
 
+                        let ib = egui::widgets::Button::image_and_text(
+                            icons.get("boards_icon").unwrap().clone(),
+                            "board library"
+                        );
+                        if ui.add(ib).clicked() {
+                            *display_board_library = !*display_board_library;
+                        }
+
                         let ib = egui::widgets::Button::image_and_text(
                             icons.get("about_icon").unwrap().clone(),
                             "about Iron Coder"
@@ -337,20 +458,6 @@ impl IronCoderApp {
         });
         // now render the central system editor panel
         egui::CentralPanel::default().show(ctx, |ui| {
-            // Adjust zoom level
-            let scale_id = egui::Id::new("system_editor_scale_factor");
-            let mut scale = ctx.data_mut(|data| {
-                data.get_temp_mut_or(scale_id, 5.0).clone()
-            });
-            const ZOOM_INCREMENT: f32 = 0.2;
-            scale += match ctx.input(|io| io.zoom_delta()) {
-                z if z<1.0 => { -ZOOM_INCREMENT },
-                z if z>1.0 => {  ZOOM_INCREMENT },
-                _          => {  0.0 },
-            };
-            ctx.data_mut(|data| {
-                data.insert_temp(scale_id, scale);
-            });
             // Display the board editor
             self.project.display_system_editor_boards(ctx, ui);
             // Display help text for in-progress connections
@@ -358,7 +465,7 @@ impl IronCoderApp {
                 data.get_temp::<bool>(egui::Id::new("connection_in_progress"))
             }) {
                 ui.with_layout(egui::Layout::bottom_up(egui::Align::Min), |ui| {
-                    ui.label("Click the pins to form your connection... or use ESC to cancel.");
+                    ui.label("Click the pins to form your connection, or pick them by name with ctrl+k... or use ESC to cancel.");
                 });
             }
         // Display a context menu on right-click.
@@ -399,10 +506,13 @@ impl IronCoderApp {
             settings: Settings{ 
                 colorscheme, 
                 ui_scale,
+                boards_directories,
             },
+            board_roots,
             ..
         } = self;
 
+        let mut should_reload_boards = false;
         if *display_settings {
             let window_response = egui::Window::new("App Settings")
             .open(display_settings)
@@ -490,6 +600,38 @@ impl IronCoderApp {
                     }
                 }
 
+                // Show the board libraries, and allow adding/removing extra ones
+                ui.separator();
+                ui.heading("Board Libraries:");
+                ui.label("Boards in later libraries override boards with the same name in earlier ones.");
+                for root in board_roots.iter() {
+                    let text = RichText::new(root.to_string());
+                    if root.path.is_dir() {
+                        ui.label(text);
+                    } else {
+                        ui.label(text.strikethrough()).on_hover_text("this directory doesn't exist");
+                    }
+                }
+                let mut dir_to_remove: Option<usize> = None;
+                for (i, dir) in boards_directories.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        ui.label(dir.display().to_string());
+                        if ui.button("remove").clicked() {
+                            dir_to_remove = Some(i);
+                        }
+                    });
+                }
+                if let Some(i) = dir_to_remove {
+                    boards_directories.remove(i);
+                    should_reload_boards = true;
+                }
+                if ui.button("add board library...").clicked() {
+                    if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                        boards_directories.push(dir);
+                        should_reload_boards = true;
+                    }
+                }
+
                 ui.separator();
                 ui.heading("Account Settings:");
                 ui.label("Add github account here.");
@@ -500,6 +642,80 @@ impl IronCoderApp {
             window_response.unwrap().response.layer_id.order = egui::Order::Foreground;
         }
 
+        if should_reload_boards {
+            self.reload_boards();
+        }
+    }
+
+    /// Show or hide the "Board Library" window, which lists any problems found
+    /// while validating the board manifests.
+    pub fn display_board_library_window(&mut self, ctx: &egui::Context) {
+        if !self.display_board_library { return; }
+        // validate the libraries lazily, the first time the window is opened
+        let mut roots = self.board_roots.clone();
+        roots.extend(self.project.local_board_root());
+        let Self {
+            display_board_library,
+            board_reports,
+            ..
+        } = self;
+        let reports = board_reports.get_or_insert_with(|| {
+            roots.into_iter().filter(|root| root.path.is_dir()).map(|root| {
+                let reports = board::validation::validate_boards(&root.path);
+                (root, reports)
+            }).collect()
+        });
+        let mut revalidate = false;
+        egui::Window::new("Board Library")
+        .open(display_board_library)
+        .collapsible(false)
+        .resizable(true)
+        .movable(true)
+        .show(ctx, |ui| {
+            let num_manifests: usize = reports.iter().map(|(_, r)| r.len()).sum();
+            let num_loaded = reports.iter().flat_map(|(_, r)| r.iter()).filter(|r| r.is_loadable()).count();
+            ui.horizontal(|ui| {
+                ui.label(format!("{} of {} board manifests load successfully.", num_loaded, num_manifests));
+                if ui.button("re-validate").clicked() {
+                    revalidate = true;
+                }
+            });
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (root, root_reports) in reports.iter() {
+                    ui.label(RichText::new(format!("Library: {}", root)).underline());
+                    for report in root_reports.iter() {
+                        let mut header = RichText::new(format!(
+                            "{} ({} errors, {} warnings)",
+                            report.title(),
+                            report.num_errors(),
+                            report.num_warnings(),
+                        ));
+                        if !report.is_loadable() {
+                            header = header.color(ui.visuals().error_fg_color).strikethrough();
+                        }
+                        egui::CollapsingHeader::new(header)
+                        .id_source(report.manifest.clone())
+                        .show(ui, |ui| {
+                            ui.label(report.manifest.display().to_string());
+                            if report.problems.is_empty() {
+                                ui.label("no problems found.");
+                            }
+                            for problem in report.problems.iter() {
+                                let color = match problem.severity() {
+                                    Severity::Error => ui.visuals().error_fg_color,
+                                    Severity::Warning => ui.visuals().warn_fg_color,
+                                };
+                                ui.label(RichText::new(problem.to_string()).color(color));
+                            }
+                        });
+                    }
+                }
+            });
+        });
+        if revalidate {
+            *board_reports = None;
+        }
     }
 
     /// This method will show or hide the "about" window
@@ -764,6 +980,7 @@ impl eframe::App for IronCoderApp {
     //   self in each of these method calls separately, vs once in the beginning of this
     //   method? But I can't do it the latter way while still having these as method calls.
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.poll_board_loader(ctx);
         // render the title bar with main menu
         self.display_title_and_menu(ctx, frame);
         // depending on the Mode, render the proper main view
@@ -778,9 +995,17 @@ impl eframe::App for IronCoderApp {
         // optionally render these popup windows
         self.display_settings_window(ctx);
         self.display_about_window(ctx);
+        self.display_board_library_window(ctx);
+        self.project.display_migration_report(ctx);
         self.unselected_mainboard_warning(ctx);
         self.display_unnamed_project_warning(ctx);
         self.display_invalid_name_warning(ctx);
+        self.display_board_loading_progress(ctx);
+
+        // board images stay on the GPU while their boards are in the project or catalog
+        let system = &self.project.system;
+        let project_boards = system.main_board.iter().chain(system.peripheral_boards.iter());
+        board::textures::retain_boards(ctx, self.boards.iter().chain(self.project.known_boards.iter()).chain(project_boards));
 
         let save_shortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::S);
         let quit_shortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::Q);
@@ -804,7 +1029,7 @@ impl eframe::App for IronCoderApp {
                     self.mode = Mode::DevelopProject;
                 },
                 Err(e) => {
-                    error!("error opening project: {:?}", e);
+                    error!("error opening project: {}", e);
                 },
             }
         }
@@ -817,7 +1042,7 @@ impl eframe::App for IronCoderApp {
                     // wants to leave the current project, and probably save
                     // the project in it's current state.
                     self.project = Project::default();
-                    self.project.known_boards = self.boards.clone();
+                    self.project.set_library_boards(self.boards.clone(), board::loader::default_cache_dir());
                     self.mode = Mode::EditProject;
                 },
             }
@@ -2,6 +2,7 @@
 
 use log::{info, debug};
 use crate::board::Board;
+use crate::board::textures;
 use egui::{
     Color32,
     Ui,
@@ -14,7 +15,6 @@ use egui::text::{
     LayoutJob,
 };
 use egui::widgets::Widget;
-use egui_extras::RetainedImage;
 
 /// Construct a LayoutJob with a bold heading, followed by a colon,
 /// followed by some content, all with custom colors.
@@ -44,12 +44,25 @@ fn make_field_widget_text(heading: &str,
     return job;
 }
 
+/// Show a board whose image hasn't been decoded yet, as its name and a spinner.
+fn show_loading(ui: &mut Ui, name: &str, size: egui::Vec2) -> Response {
+    egui::Frame::none()
+    .inner_margin(egui::Margin::same(5.0))
+    .show(ui, |ui| {
+        ui.set_min_size(size);
+        ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+            ui.label(name);
+            ui.spinner();
+        });
+    }).response.interact(egui::Sense::click())
+}
+
 /// Normal view for the board widget
 impl Widget for Board {
     // How to display a board as a widget
     fn ui(self, ui: &mut Ui) -> Response {
         let response: egui::Response;
-        if let Some(svg_board_info) = self.svg_board_info {
+        if self.svg_board_info.is_some() {
             // Use a frame to display multiple widgets within our widget,
             // with an inner margin
             response = egui::Frame::none()
@@ -65,11 +78,7 @@ impl Widget for Board {
                         ui.style().visuals.window_stroke.color,
                     ));
                     // ui.label(label);
-                    let retained_image = RetainedImage::from_color_image(
-                        "pic",
-                        svg_board_info.image,
-                    );
-                    retained_image.show_max_size(ui, egui::vec2(150.0, 150.0));
+                    textures::show_board_image(ui, &self, egui::vec2(150.0, 150.0));
                 });
                 ui.horizontal(|ui| {
                     ui.label(make_field_widget_text(
@@ -164,7 +173,7 @@ impl Widget for Board {
             // let i = egui::Image::new(&th, egui::vec2(128.0, 128.0)).sense(egui::Sense::click());
             // response = ui.add(i);
         } else {
-            response = ui.allocate_response(egui::vec2(128.0, 128.0), egui::Sense::click());
+            response = show_loading(ui, &self.name, egui::vec2(128.0, 128.0));
         }
         return response;
     }
@@ -177,7 +186,7 @@ impl Widget for BoardSelectorWidget {
     fn ui(self, ui: &mut Ui) -> Response {
         let this_board = self.0;
         let response: egui::Response;
-        if let Some(svg_board_info) = this_board.clone().svg_board_info {
+        if this_board.svg_board_info.is_some() {
             // Use a frame to display multiple widgets within our widget,
             // with an inner margin
             response = egui::Frame::none()
@@ -192,11 +201,7 @@ impl Widget for BoardSelectorWidget {
                         ui.style().visuals.window_stroke.color,
                     ));
                     // ui.label(label);
-                    let retained_image = RetainedImage::from_color_image(
-                        "pic",
-                        svg_board_info.image,
-                    );
-                    let _ = retained_image.show_max_size(ui, egui::vec2(150.0, 150.0));
+                    textures::show_board_image(ui, &this_board, egui::vec2(150.0, 150.0));
 
                 });
                 ui.horizontal(|ui| {
@@ -215,6 +220,12 @@ impl Widget for BoardSelectorWidget {
                         ui.label("none");
                     }
                 });
+                if let Some(root) = this_board.get_library_root() {
+                    ui.horizontal(|ui| {
+                        ui.label("Library: ");
+                        ui.label(root.kind.to_string()).on_hover_text(root.path.display().to_string());
+                    });
+                }
             }).response.interact(egui::Sense::click());
 
             
@@ -224,7 +235,7 @@ impl Widget for BoardSelectorWidget {
             }
 
         } else {
-            response = ui.allocate_response(egui::vec2(128.0, 128.0), egui::Sense::click());
+            response = show_loading(ui, &this_board.name, egui::vec2(128.0, 128.0));
         }
         return response;
     }
@@ -236,7 +247,7 @@ impl Widget for BoardMiniWidget {
     fn ui(self, ui: &mut Ui) -> Response {
         let this_board = self.0;
         let response: egui::Response;
-        if let Some(svg_board_info) = this_board.clone().svg_board_info {
+        if this_board.svg_board_info.is_some() {
             // Use a frame to display multiple widgets within our widget,
             // with an inner margin
             response = egui::Frame::none()
@@ -244,11 +255,7 @@ impl Widget for BoardMiniWidget {
             .show(ui, |ui| {
                 ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
                     ui.label(this_board.clone().name);
-                    let retained_image = RetainedImage::from_color_image(
-                        "pic",
-                        svg_board_info.image,
-                    );
-                    retained_image.show_max_size(ui, egui::vec2(96.0, 96.0));
+                    textures::show_board_image(ui, &this_board, egui::vec2(96.0, 96.0));
                 });
             }).response.interact(egui::Sense::click());
             if this_board.clone().is_main_board() {
@@ -256,8 +263,8 @@ impl Widget for BoardMiniWidget {
                 ui.painter().rect_stroke(response.rect, 0.0, (1.0, egui::Color32::WHITE));
             }
         } else {
-            debug!("could not find color_image when rendering BoardMiniWidget");
-            response = ui.allocate_response(egui::vec2(128.0, 128.0), egui::Sense::click());
+            debug!("board image of {} isn't loaded yet when rendering BoardMiniWidget", this_board.name);
+            response = show_loading(ui, &this_board.name, egui::vec2(128.0, 128.0));
         }
         return response;
     }
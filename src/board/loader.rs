@@ -0,0 +1,368 @@
+//! This module decodes the images and parses the BSPs of boards, which is the slow part of
+//! loading a board library. The board manifests are loaded first, so that the boards can be
+//! listed right away, and then each board is decoded on a worker thread.
+//!
+//! Decoded images and parsed BSPs are cached on disk, keyed by a hash of the files they came
+//! from, so a board is only decoded again when one of its files changes. Entries that weren't
+//! used while the boards were loaded are removed once they're done. Each project-local library
+//! has a cache directory of its own (see `local_cache_dir`), so loading one library never
+//! removes the entries of another.
+
+use log::{debug, info, warn};
+
+use std::fs;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+use serde::{Serialize, de::DeserializeOwned};
+use sha1::{Digest, Sha1};
+
+use crate::board::Board;
+use crate::board::parsing::{self, BspParseInfo, CachedBspParseInfo};
+use crate::board::svg_reader::SvgBoardInfo;
+
+/// Changed whenever the way boards are decoded changes, so that older cache entries aren't used
+const CACHE_VERSION: &str = "1";
+
+/// The directory that decoded boards are cached in, in the app's storage directory.
+pub fn default_cache_dir() -> Option<PathBuf> {
+    eframe::storage_dir("Iron Coder").map(|dir| dir.join("board_cache"))
+}
+
+/// The directory that the boards of a project-local library are cached in, inside of the
+/// app's cache directory.
+pub fn local_cache_dir(cache_dir: &Path, library: &Path) -> PathBuf {
+    let hash = Sha1::digest(library.to_string_lossy().as_bytes());
+    cache_dir.join("local").join(format!("{:x}", hash))
+}
+
+/// What a worker needs to decode a board. The Board itself can't be sent to a worker thread,
+/// as the BSP syntax it holds can't be.
+struct Job {
+    manifest_path: PathBuf,
+    name: String,
+    pin_ids: Vec<String>,
+    bsp: Option<String>,
+    bsp_path: Option<PathBuf>,
+    /// The job for the board that this board overrides, which is only run if this board's
+    /// image can't be decoded, and the overridden board hasn't been decoded already
+    fallback: Option<Box<Job>>,
+}
+
+impl Job {
+    fn new(board: &Board) -> Option<Self> {
+        Some(Self {
+            manifest_path: board.manifest_path.clone()?,
+            name: board.name.clone(),
+            pin_ids: board.svg_pin_ids(),
+            bsp: board.bsp.clone(),
+            bsp_path: board.bsp_path.clone(),
+            fallback: board.overridden.as_deref()
+                .filter(|overridden| overridden.svg_board_info.is_none())
+                .and_then(Job::new)
+                .map(Box::new),
+        })
+    }
+}
+
+/// A decoded board, for the board loaded from the same manifest.
+struct Decoded {
+    manifest_path: PathBuf,
+    svg_board_info: Result<SvgBoardInfo, String>,
+    bsp_parse_info: Option<CachedBspParseInfo>,
+    /// Whether the board that this board overrides is decoded next, because this one failed
+    falls_back: bool,
+}
+
+/// Decode the boards on worker threads, and wait for all of them. Boards whose image can't be
+/// decoded are replaced by the boards they override, or removed, as they can't be shown. The
+/// app decodes its boards with a `BoardLoader` instead, so this is only used by the tests.
+#[cfg(test)]
+pub fn decode_boards(boards: &mut Vec<Board>, cache_dir: Option<&Path>) {
+    let receiver = spawn_workers(jobs(boards), cache_dir.map(Path::to_path_buf));
+    for decoded in receiver.iter() {
+        merge(boards, decoded);
+    }
+}
+
+/// Decodes boards in the background, for the app to merge into its boards as they're done.
+pub struct BoardLoader {
+    receiver: mpsc::Receiver<Decoded>,
+    total: usize,
+    done: usize,
+    finished: bool,
+    cache_dir: Option<PathBuf>,
+    started: SystemTime,
+    pruned: bool,
+}
+
+impl BoardLoader {
+    /// Start decoding the boards on worker threads.
+    pub fn start(boards: &[Board], cache_dir: Option<PathBuf>) -> Self {
+        let jobs = jobs(boards);
+        let total = jobs.len();
+        info!("decoding {} boards in the background", total);
+        Self {
+            receiver: spawn_workers(jobs, cache_dir.clone()),
+            total,
+            done: 0,
+            finished: total == 0,
+            cache_dir,
+            started: SystemTime::now(),
+            pruned: false,
+        }
+    }
+
+    /// Merge the boards that have been decoded since the last poll into the boards, and return
+    /// the library ids of the boards that changed. Boards whose image can't be decoded are
+    /// replaced by the boards they override, or removed. Once all of the boards are done, the
+    /// cache entries that weren't used are removed.
+    pub fn poll(&mut self, boards: &mut Vec<Board>) -> Vec<String> {
+        let mut changed = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(decoded) => {
+                    // a board that falls back isn't done until the board it overrides is
+                    if !decoded.falls_back {
+                        self.done += 1;
+                    }
+                    changed.extend(merge(boards, decoded));
+                },
+                Err(mpsc::TryRecvError::Empty) => break,
+                // all of the workers are done, even if some of them didn't finish their boards
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.finished = true;
+                    break;
+                },
+            }
+        }
+        if self.is_finished() && !self.pruned {
+            if let Some(cache_dir) = self.cache_dir.as_deref() {
+                prune_cache(cache_dir, self.started);
+            }
+            self.pruned = true;
+        }
+        changed
+    }
+
+    /// The number of boards decoded so far, out of the number being decoded.
+    pub fn progress(&self) -> (usize, usize) {
+        (self.done, self.total)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished || self.done >= self.total
+    }
+}
+
+/// The jobs to decode the boards. The boards they override are decoded in case they can't be.
+fn jobs(boards: &[Board]) -> Vec<Job> {
+    boards.iter().filter_map(Job::new).collect()
+}
+
+/// A board, followed by the boards that it overrides.
+fn fallbacks(board: &Board) -> impl Iterator<Item = &Board> {
+    iter::successors(Some(board), |b| b.overridden.as_deref())
+}
+
+/// Start worker threads to run the jobs, which send each board to the returned receiver as it's
+/// done. The receiver disconnects once all of the boards are done.
+fn spawn_workers(jobs: Vec<Job>, cache_dir: Option<PathBuf>) -> mpsc::Receiver<Decoded> {
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(4).min(jobs.len());
+    let jobs = Arc::new(Mutex::new(jobs));
+    let (sender, receiver) = mpsc::channel();
+    for _ in 0..workers {
+        let (jobs, sender, cache_dir) = (jobs.clone(), sender.clone(), cache_dir.clone());
+        thread::spawn(move || {
+            // the lock is only held to take the next job
+            while let Some(job) = { let next = jobs.lock().unwrap().pop(); next } {
+                // a board that can't be decoded is followed by the board it overrides
+                let mut next = Some(job);
+                while let Some(mut job) = next.take() {
+                    let fallback = job.fallback.take();
+                    let mut decoded = decode(job, cache_dir.as_deref());
+                    if decoded.svg_board_info.is_err() {
+                        next = fallback.map(|fallback| *fallback);
+                    }
+                    decoded.falls_back = next.is_some();
+                    if sender.send(decoded).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+    receiver
+}
+
+/// Merge a decoded board into the board that was loaded from the same manifest, if it's still
+/// there. It may be the board in the list, or one that the board in the list overrides. Returns
+/// the library id of the board in the list if it changed.
+fn merge(boards: &mut Vec<Board>, decoded: Decoded) -> Option<String> {
+    let found = boards.iter().enumerate().find_map(|(idx, board)| {
+        let depth = fallbacks(board).position(|b| b.manifest_path.as_ref() == Some(&decoded.manifest_path))?;
+        Some((idx, depth))
+    });
+    let (idx, depth) = found?;
+    let library_id = boards[idx].library_id();
+    match decoded.svg_board_info {
+        Ok(svg_board_info) => {
+            let mut board = &mut boards[idx];
+            if depth == 0 {
+                // the board has been decoded, so the boards it overrides aren't needed any more
+                board.overridden = None;
+            }
+            for _ in 0..depth {
+                board = board.overridden.as_deref_mut().expect("the board was found at this depth");
+            }
+            info!("successfully decoded SVG for board {}. Board has physical size: {:?}", board.get_name(), svg_board_info.physical_size);
+            board.svg_board_info = Some(svg_board_info);
+            board.bsp_parse_info = decoded.bsp_parse_info.as_ref().map(BspParseInfo::from);
+            (depth == 0).then_some(library_id)
+        },
+        Err(e) => {
+            warn!("error loading board from {}: {} (run with --validate-boards for details)", decoded.manifest_path.display(), e);
+            if depth == 0 {
+                match boards[idx].overridden.take() {
+                    Some(base) => {
+                        info!("using board <{}> from {:?} instead", base.get_name(), base.library_root);
+                        boards[idx] = *base;
+                    },
+                    None => {
+                        boards.remove(idx);
+                    },
+                }
+                Some(library_id)
+            } else {
+                // take the board out of the chain of boards that the board in the list overrides
+                let mut board = &mut boards[idx];
+                for _ in 1..depth {
+                    board = board.overridden.as_deref_mut().expect("the board was found at this depth");
+                }
+                let failed = board.overridden.take().expect("the board was found at this depth");
+                board.overridden = failed.overridden;
+                None
+            }
+        },
+    }
+}
+
+/// Decode the image and parse the BSP of a board, from the cache if they're in it.
+fn decode(job: Job, cache_dir: Option<&Path>) -> Decoded {
+    Decoded {
+        svg_board_info: decode_svg(&job, cache_dir),
+        bsp_parse_info: parse_bsp(&job, cache_dir),
+        manifest_path: job.manifest_path,
+        falls_back: false,
+    }
+}
+
+fn decode_svg(job: &Job, cache_dir: Option<&Path>) -> Result<SvgBoardInfo, String> {
+    let svg_path = job.manifest_path.with_extension("svg");
+    let svg_data = fs::read(&svg_path).map_err(|e| format!("couldn't read SVG: {:?}", e))?;
+    let key = cache_key("svg", &[&svg_data, job.pin_ids.join("\n").as_bytes()]);
+    if let Some(svg_board_info) = read_cache::<SvgBoardInfo>(cache_dir, &key) {
+        return Ok(svg_board_info.with_source(svg_data, &job.pin_ids));
+    }
+    let svg_board_info = SvgBoardInfo::from_data_with_pins(svg_data, &job.pin_ids)
+        .map_err(|e| format!("unable to parse board SVG file: {:?}", e))?;
+    write_cache(cache_dir, &key, &svg_board_info);
+    Ok(svg_board_info)
+}
+
+fn parse_bsp(job: &Job, cache_dir: Option<&Path>) -> Option<CachedBspParseInfo> {
+    let bsp_path = job.bsp_path.as_deref()?;
+    let src = fs::read(bsp_path.join("src/lib.rs")).unwrap_or_default();
+    let key = cache_key("bsp", &[job.name.as_bytes(), job.bsp.as_deref().unwrap_or_default().as_bytes(), &src]);
+    if let Some(bsp_parse_info) = read_cache::<CachedBspParseInfo>(cache_dir, &key) {
+        return Some(bsp_parse_info);
+    }
+    match parsing::parse_bsp_info(&job.name, job.bsp.as_deref(), Some(bsp_path)) {
+        Ok(bsp_parse_info) => {
+            let bsp_parse_info = CachedBspParseInfo::from(&bsp_parse_info);
+            write_cache(cache_dir, &key, &bsp_parse_info);
+            Some(bsp_parse_info)
+        },
+        Err(e) => {
+            warn!("error parsing BSP for board {}: {:?}", job.name, e);
+            None
+        },
+    }
+}
+
+/// The key of a cache entry, which is a hash of what the entry was made from.
+fn cache_key(kind: &str, parts: &[&[u8]]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(CACHE_VERSION);
+    hasher.update(kind);
+    for part in parts {
+        // the length of each part keeps them from running together
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Read a cache entry, and mark it as used so that it isn't pruned.
+fn read_cache<T: DeserializeOwned>(cache_dir: Option<&Path>, key: &str) -> Option<T> {
+    let path = cache_dir?.join(key);
+    let bytes = fs::read(&path).ok()?;
+    match bincode::deserialize(&bytes) {
+        Ok(value) => {
+            let touched = fs::File::options().write(true).open(&path)
+                .and_then(|file| file.set_modified(SystemTime::now()));
+            if let Err(e) = touched {
+                debug!("couldn't mark cache entry {} as used: {:?}", key, e);
+            }
+            Some(value)
+        },
+        Err(e) => {
+            debug!("ignoring unreadable cache entry {}: {:?}", key, e);
+            None
+        },
+    }
+}
+
+/// Write a cache entry. The entry is written to a temporary file first, so that an entry is
+/// never read half-written by another worker or another instance of the app.
+fn write_cache<T: Serialize>(cache_dir: Option<&Path>, key: &str, value: &T) {
+    let Some(cache_dir) = cache_dir else {
+        return;
+    };
+    let bytes = match bincode::serialize(value) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("couldn't serialize cache entry {}: {:?}", key, e);
+            return;
+        },
+    };
+    let temp = cache_dir.join(format!("{}.{:?}.tmp", key, thread::current().id()));
+    let written = fs::create_dir_all(cache_dir)
+        .and_then(|_| fs::write(&temp, bytes))
+        .and_then(|_| fs::rename(&temp, cache_dir.join(key)));
+    if let Err(e) = written {
+        warn!("couldn't write cache entry {} to {}: {:?}", key, cache_dir.display(), e);
+        let _ = fs::remove_file(&temp);
+    }
+}
+
+/// Remove the cache entries that haven't been used since `since`, i.e. the entries for boards
+/// whose files have changed or that are gone, and entries from older versions of the cache. The
+/// directories of the project-local libraries are left alone.
+fn prune_cache(cache_dir: &Path, since: SystemTime) {
+    let Ok(entries) = fs::read_dir(cache_dir) else {
+        return;
+    };
+    let mut pruned = 0;
+    for entry in entries.flatten() {
+        let unused = entry.metadata()
+            .is_ok_and(|metadata| metadata.is_file() && metadata.modified().is_ok_and(|modified| modified < since));
+        if unused && fs::remove_file(entry.path()).is_ok() {
+            pruned += 1;
+        }
+    }
+    info!("removed {} unused entries from the board cache", pruned);
+}
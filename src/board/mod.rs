@@ -19,16 +19,69 @@ use svg_reader::SvgBoardInfo;
 pub mod display;
 
 pub mod pinout;
-use pinout::Pinout;
+use pinout::{Pin, Pinout};
 
 pub mod parsing;
+pub mod validation;
+pub mod standards;
+pub mod textures;
+pub mod loader;
 mod test;
 
 use parsing::BspParseInfo;
 
+/// The location of the board library that ships with Iron Coder
+pub const BUNDLED_BOARDS_DIR: &str = "./iron-coder-boards";
+
+/// The kinds of places that boards can be loaded from, in the order they are layered.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardRootKind {
+    /// The board library that ships with Iron Coder
+    Bundled,
+    /// The per-user board library, in the app's storage directory
+    User,
+    /// An extra library given on the command line or in the app settings
+    Extra,
+    /// The `boards/` folder inside of a project
+    Project,
+}
+
+impl fmt::Display for BoardRootKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoardRootKind::Bundled => write!(f, "bundled"),
+            BoardRootKind::User => write!(f, "user"),
+            BoardRootKind::Extra => write!(f, "extra"),
+            BoardRootKind::Project => write!(f, "project"),
+        }
+    }
+}
+
+/// A directory that boards are loaded from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BoardRoot {
+    pub kind: BoardRootKind,
+    pub path: PathBuf,
+}
+
+impl BoardRoot {
+    pub fn new(kind: BoardRootKind, path: impl Into<PathBuf>) -> Self {
+        Self {
+            kind,
+            path: path.into(),
+        }
+    }
+}
+
+impl fmt::Display for BoardRoot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.kind, self.path.display())
+    }
+}
+
 /// These are the various standard development board form factors
 #[non_exhaustive]
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BoardStandards {
     Feather,
     Arduino,
@@ -50,23 +103,43 @@ impl fmt::Display for BoardStandards {
     }
 }
 
+/// A BoardInstance identifies one board placed in a project, so that a project can contain
+/// more than one of the same board.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BoardInstance {
+    /// A number that's unique among the boards of a project, and never changes
+    pub id: u32,
+    /// A short, user-editable name for the board, e.g. "oled1"
+    pub designator: String,
+}
+
 /// The board struct defines a board type
 #[derive(Serialize, Deserialize, Clone, Default)]
 #[serde(default)]
 pub struct Board {
+    /// A stable identifier for the board in the library. If this isn't set in the manifest, it
+    /// is made from the manufacturer, name, and revision (see `Board::library_id`).
+    id: Option<String>,
     /// The name of the board
     name: String,
     /// The board manufacturer
     manufacturer: String,
+    /// The revision of the board, if the manufacturer has made more than one
+    revision: Option<String>,
     /// Whether or not the board has a processor that can run code
     is_main_board: bool,
     /// A possible form factor that the board adheres to
     standard: Option<BoardStandards>,
+    /// The header geometry of the board's form factor, if its board library describes it
+    #[serde(skip)]
+    standard_geometry: Option<standards::StandardGeometry>,
     cpu: Option<String>,
     ram: Option<isize>,
     flash: Option<isize>,
     /// A list of the interfaces available on the board
     pub pinout: Pinout,
+    /// A list of the physical pins on the board, with their various identifiers
+    pins: Vec<Pin>,
     /// A list of the Syntax Nodes of the BSP calculated by Rust Analyzer
     #[serde(skip)]
     pub ra_values: Vec<ra_ap_ide::StructureNode>,
@@ -91,6 +164,18 @@ pub struct Board {
     required_crates: Option<Vec<String>>,
     /// A list of related, optional crates
     related_crates: Option<Vec<String>>,
+    /// The board library that this board was loaded from
+    #[serde(skip)]
+    library_root: Option<BoardRoot>,
+    /// The manifest that this board was loaded from, which its image and BSP are found next to
+    #[serde(skip)]
+    manifest_path: Option<PathBuf>,
+    /// The board from an earlier library that this board overrides, which is used instead if
+    /// this board's image can't be decoded
+    #[serde(skip)]
+    overridden: Option<Box<Board>>,
+    /// The identity of the board within a project, if it has been placed in one
+    instance: Option<BoardInstance>,
 }
 
 impl fmt::Debug for Board {
@@ -109,18 +194,20 @@ impl fmt::Debug for Board {
     }
 }
 
-/// Boards are uniquely identified by their name, and thus comparable.
+/// Boards are uniquely identified by their library id and, once placed in a project, their
+/// instance id, and thus comparable.
 impl cmp::PartialEq for Board {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+        self.library_id() == other.library_id() && self.get_instance_id() == other.get_instance_id()
     }
 }
 impl cmp::Eq for Board {}
 
-/// Boards are uniquely identified by their name, and thus hashable.
+/// Boards are uniquely identified by their library id and instance id, and thus hashable.
 impl Hash for Board {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.name.hash(state);
+        self.library_id().hash(state);
+        self.get_instance_id().hash(state);
     }
 }
 
@@ -128,30 +215,21 @@ impl Hash for Board {
 /// information about them.
 impl Board {
 
-    /// Loads a board from its toml description
+    /// Loads a board from its toml description. The board's image is only found here, and is
+    /// decoded later, along with its BSP (see the `loader` module).
     fn load_from_toml(path: &Path) -> std::io::Result<Self> {
         
         let toml_str = fs::read_to_string(path)?;
         let mut b: Board = match toml::from_str(&toml_str) {
             Ok(b) => b,
-            Err(_) => {
-                return Err(std::io::Error::other("load from toml failed"));
+            Err(e) => {
+                return Err(std::io::Error::other(format!("load from toml failed: {}", e.message())));
             },
         };
 
         // See if there is an image
-        if let Ok(pic_path) = path.with_extension("svg").canonicalize() {
-            // BASED ON SVG WORK
-            match SvgBoardInfo::from_path(&pic_path) {
-                Ok(svg_board_info) => {
-                    info!("successfully decoded SVG for board {}. Board has physical size: {:?}", b.get_name(), svg_board_info.physical_size);
-                    b.svg_board_info = Some(svg_board_info);
-                },
-                Err(e) => {
-                    warn!("error with svg parsing! {:?}", e);
-                    return Err(std::io::Error::other("unable to parse board SVG file."));
-                },
-            };
+        if path.with_extension("svg").exists() {
+            b.manifest_path = Some(path.canonicalize().unwrap_or(path.to_path_buf()));
         } else {
             warn!("no svg file for board {}", b.get_name());
             return Err(std::io::Error::other("no SVG file for board."));
@@ -160,9 +238,8 @@ impl Board {
 
         // See if there are any examples
         if let Ok(examples_path) = path.parent().unwrap().join("examples").canonicalize() {
-            for (_i, e) in examples_path.read_dir().unwrap().enumerate() {
-                let example_path = e.unwrap().path();
-                b.examples.push(example_path);
+            for e in examples_path.read_dir()?.flatten() {
+                b.examples.push(e.path());
             }
         }
 
@@ -173,10 +250,80 @@ impl Board {
         self.name.as_str()
     }
 
+    /// The ids of the SVG elements that are pins of the board, according to its manifest.
+    fn svg_pin_ids(&self) -> Vec<String> {
+        self.pins.iter().chain(self.pinout_pins().iter()).map(|pin| pin.svg_id.clone()).collect()
+    }
+
     pub fn get_pinout(&self) -> Pinout {
         self.pinout.clone()
     }
 
+    /// The pins declared in the board manifest
+    pub fn get_pins(&self) -> &[Pin] {
+        &self.pins
+    }
+
+    /// Get the Pin represented by the given SVG element id. If the pin isn't declared
+    /// in the board manifest, it is only known by its SVG id.
+    pub fn get_pin_by_svg_id(&self, svg_id: &str) -> Pin {
+        self.pins.iter()
+            .find(|pin| pin.svg_id == svg_id)
+            .cloned()
+            .unwrap_or_else(|| Pin::from_svg_id(svg_id))
+    }
+
+    /// Find a Pin by any of its identifiers (see `Pin::is_called`), falling back to
+    /// the SVG element ids of the board image.
+    pub fn find_pin(&self, name: &str) -> Option<Pin> {
+        if let Some(pin) = self.pins.iter().find(|pin| pin.is_called(name)) {
+            return Some(pin.clone());
+        }
+        let svg_board_info = self.svg_board_info.as_ref()?;
+        svg_board_info.pin_rects.iter()
+            .find(|(id, _)| id == name)
+            .map(|(id, _)| Pin::from_svg_id(id))
+    }
+
+    /// The pins that the board's pinout maps to an interface, i.e. the pins that can be
+    /// connected to something. Each pin appears once, in the order of the pinout.
+    pub fn pinout_pins(&self) -> Vec<Pin> {
+        let mut pins: Vec<Pin> = Vec::new();
+        for name in self.pinout.iter().flat_map(|mapping| mapping.pins.iter()) {
+            let pin = self.find_pin(name).unwrap_or_else(|| Pin::from_svg_id(name));
+            if !pins.iter().any(|p| p.svg_id == pin.svg_id) {
+                pins.push(pin);
+            }
+        }
+        pins
+    }
+
+    pub fn get_standard(&self) -> Option<BoardStandards> {
+        self.standard
+    }
+
+    pub fn get_standard_geometry(&self) -> Option<&standards::StandardGeometry> {
+        self.standard_geometry.as_ref()
+    }
+
+    /// Where the first pin of the standard's headers is in the board image, in mm from its
+    /// top-left corner. This is found from the pins that have a header position and a place in
+    /// the image, so it's None for boards without either.
+    pub fn header_anchor(&self) -> Option<egui::Pos2> {
+        let geometry = self.standard_geometry.as_ref()?;
+        let svg_board_info = self.svg_board_info.as_ref()?;
+        let anchors: Vec<egui::Pos2> = svg_board_info.pin_rects.iter().filter_map(|(svg_id, rect)| {
+            let pin = self.get_pin_by_svg_id(svg_id);
+            let offset = geometry.header_offset(pin.header_position.as_deref()?)?;
+            Some(rect.center() - offset)
+        }).collect();
+        if anchors.is_empty() {
+            return None;
+        }
+        let sum = anchors.iter().fold(egui::Vec2::ZERO, |sum, p| sum + p.to_vec2());
+        Some((sum / anchors.len() as f32).to_pos2())
+    }
+
     pub fn required_crates(&self) -> Option<Vec<String>> {
         self.required_crates.clone()
     }
@@ -193,64 +340,228 @@ impl Board {
         return self.template_dir.clone();
     }
 
+    /// The stable identifier of the board in the library, i.e. "<manufacturer>/<name>/<revision>",
+    /// unless the manifest sets one explicitly (which keeps projects working across a rename).
+    pub fn library_id(&self) -> String {
+        match (&self.id, &self.revision) {
+            (Some(id), _) => id.clone(),
+            (None, Some(revision)) => format!("{}/{}/{}", self.manufacturer, self.name, revision),
+            (None, None) => format!("{}/{}", self.manufacturer, self.name),
+        }
+    }
+
+    /// Whether two boards are the same library board, regardless of their placement in a project.
+    pub fn is_same_library_board(&self, other: &Board) -> bool {
+        self.library_id() == other.library_id()
+    }
+
+    /// A stand-in for the placed library board with the given id, as read from a project file.
+    /// It has none of the board's resources until it's replaced by the library board itself.
+    pub fn from_reference(library_id: &str, instance: BoardInstance) -> Board {
+        Board {
+            id: Some(library_id.to_string()),
+            name: library_id.to_string(),
+            instance: Some(instance),
+            ..Default::default()
+        }
+    }
+
+    pub fn get_instance(&self) -> Option<&BoardInstance> {
+        self.instance.as_ref()
+    }
+
+    pub fn get_instance_id(&self) -> Option<u32> {
+        self.instance.as_ref().map(|instance| instance.id)
+    }
+
+    pub fn set_instance(&mut self, instance: Option<BoardInstance>) {
+        self.instance = instance;
+    }
+
+    /// The designator of a placed board, e.g. "oled1", or its name if it hasn't been placed.
+    pub fn get_designator(&self) -> &str {
+        match &self.instance {
+            Some(instance) => &instance.designator,
+            None => &self.name,
+        }
+    }
+
+    /// The start of the default designator for instances of this board, i.e. the first word of
+    /// its name, e.g. "oled" for the "OLED Featherwing (128x64)".
+    pub fn designator_prefix(&self) -> String {
+        let prefix: String = self.name.split_whitespace().next().unwrap_or_default()
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if prefix.is_empty() { "board".to_string() } else { prefix }
+    }
+
+    /// The board library that this board was loaded from, if known
+    pub fn get_library_root(&self) -> Option<&BoardRoot> {
+        self.library_root.as_ref()
+    }
+
 }
 
-/// Iteratively gather the Boards from the filesystem.
-pub fn get_boards(boards_dir: &Path) -> Vec<Board> {
-    let mut r = Vec::new();
-    if let Ok(manufacturers) = fs::read_dir(boards_dir) {
-        // first tier of organization is by manufacturer
-        for manufacturer in manufacturers {
-            let manufacturer = manufacturer.expect("error with manufacturer directory");
-            if manufacturer.file_type().expect("error parsing file type").is_file() {
-                continue;
+/// Walk the boards directory (organized as `<manufacturer>/<board>/<manifest>.toml`) and collect
+/// the paths of all the board manifests, along with any directories that couldn't be read.
+pub(crate) fn find_manifests(boards_dir: &Path) -> (Vec<PathBuf>, Vec<(PathBuf, std::io::Error)>) {
+    let mut manifests = Vec::new();
+    let mut errors = Vec::new();
+    if !boards_dir.is_dir() {
+        warn!("boards directory {} doesn't exist", boards_dir.display());
+        return (manifests, errors);
+    }
+    // Read a directory, returning the paths of its entries that are directories (or files)
+    let mut read_dir = |dir: &Path, want_dirs: bool| -> Vec<PathBuf> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                errors.push((dir.to_path_buf(), e));
+                return Vec::new();
+            },
+        };
+        let mut paths = Vec::new();
+        for entry in entries {
+            match entry.and_then(|entry| Ok((entry.path(), entry.file_type()?))) {
+                Ok((path, file_type)) if file_type.is_dir() == want_dirs => paths.push(path),
+                Ok(_) => (),
+                Err(e) => errors.push((dir.to_path_buf(), e)),
             }
-            let boards = fs::read_dir(manufacturer.path()).expect("error iterating over files in manufacturer directory");
-            for board in boards {
-                let board = board.expect("error with Board directory");
-                if board.file_type().expect("error parsing file type within board dir").is_file() {
-                    continue;
-                }
-                let files = fs::read_dir(board.path()).expect("error iterating over files in board directory");
-                for file in files {
-                    let file = file.expect("error reading file within board directory");
-                    if file.path().extension().unwrap_or_default() == "toml" {
-                        match Board::load_from_toml(&file.path()) {
-                            Ok(mut board) => {
-                                let parent = file.path().parent().unwrap().canonicalize().unwrap();
-                                // look for a template directory
-                                let template_dir = parent.join("template");
-                                if let Ok(true) = template_dir.try_exists() {
-                                    debug!("found template dir for board <{}> at {:?}", board.name.clone(), file.path().parent().unwrap().canonicalize().unwrap().join("template"));
-                                    board.template_dir = Some(template_dir);
-                                } else {
-                                    debug!("no template directory found for board <{}>", board.name.clone());
-                                }
-                                // look for a local BSP, and do things related to it if needed
-                                let bsp_dir = parent.join("bsp");
-                                if let Ok(true) = bsp_dir.try_exists() {
-                                    info!("found local bsp crate for board {}", board.name.clone());
-                                    board.bsp_path = Some(bsp_dir.clone());
-                                    // let bsp_string = fs::read_to_string(bsp_dir.join("src/lib.rs")).unwrap();
-                                    // let (analysis, fid) = ra_ap_ide::Analysis::from_single_file(bsp_string);
-                                    // board.ra_values = analysis.file_structure(fid).unwrap();
-                                    match board.load_bsp_info() {
-                                        Ok(_) => (),
-                                        Err(e) => warn!("error parsing BSP for board {}: {:?}", board.get_name(), e),
-                                    };
-                                } else {
-                                    debug!("no bsp directory found for board <{}>", board.name.clone());
-                                }
-                                r.push(board);
-                            },
-                            Err(e) => {
-                                warn!("error loading board from {}: {:?}", file.path().display().to_string(), e);
-                            },
-                        }
-                    }
+        }
+        paths.sort();
+        paths
+    };
+    // first tier of organization is by manufacturer
+    for manufacturer in read_dir(boards_dir, true) {
+        for board in read_dir(&manufacturer, true) {
+            for file in read_dir(&board, false) {
+                if file.extension().unwrap_or_default() == "toml" {
+                    manifests.push(file);
                 }
             }
         }
     }
+    (manifests, errors)
+}
+
+/// Iteratively gather the Boards from the filesystem, with their images and BSPs, which are
+/// cached in `cache_dir` if it's given. The app loads its libraries with `get_manifests_from_roots`
+/// instead, so this is only used by the tests.
+#[cfg(test)]
+pub fn get_boards(boards_dir: &Path, cache_dir: Option<&Path>) -> Vec<Board> {
+    let mut r = get_manifests(boards_dir);
+    loader::decode_boards(&mut r, cache_dir);
+    r
+}
+
+/// Gather the Boards from the filesystem, from only their manifests. This is quick, so the
+/// boards can be listed before their images and BSPs are loaded with the `loader` module.
+pub fn get_manifests(boards_dir: &Path) -> Vec<Board> {
+    let mut r = Vec::new();
+    let (manifests, errors) = find_manifests(boards_dir);
+    for (path, e) in errors {
+        warn!("error reading {}: {:?}", path.display(), e);
+    }
+    for file in manifests {
+        match Board::load_from_toml(&file) {
+            Ok(mut board) => {
+                let parent = file.parent().unwrap();
+                let parent = parent.canonicalize().unwrap_or(parent.to_path_buf());
+                // look for a template directory
+                let template_dir = parent.join("template");
+                if let Ok(true) = template_dir.try_exists() {
+                    debug!("found template dir for board <{}> at {:?}", board.name.clone(), template_dir);
+                    board.template_dir = Some(template_dir);
+                } else {
+                    debug!("no template directory found for board <{}>", board.name.clone());
+                }
+                // look for a local BSP, and do things related to it if needed
+                let bsp_dir = parent.join("bsp");
+                if let Ok(true) = bsp_dir.try_exists() {
+                    info!("found local bsp crate for board {}", board.name.clone());
+                    board.bsp_path = Some(bsp_dir.clone());
+                    // let bsp_string = fs::read_to_string(bsp_dir.join("src/lib.rs")).unwrap();
+                    // let (analysis, fid) = ra_ap_ide::Analysis::from_single_file(bsp_string);
+                    // board.ra_values = analysis.file_structure(fid).unwrap();
+                } else {
+                    debug!("no bsp directory found for board <{}>", board.name.clone());
+                }
+                r.push(board);
+            },
+            Err(e) => {
+                warn!("error loading board from {}: {:?} (run with --validate-boards for details)", file.display(), e);
+            },
+        }
+    }
+    attach_standards(&mut r, &standards::get_standards(boards_dir));
     return r;
 }
+
+/// Give each board the geometry of its standard, if it's one of the given standards.
+fn attach_standards(boards: &mut [Board], standards: &[standards::StandardGeometry]) {
+    for board in boards.iter_mut() {
+        board.standard_geometry = board.standard.and_then(|standard| {
+            standards.iter().find(|s| s.standard == standard).cloned()
+        });
+    }
+}
+
+/// Layer a set of boards on top of an existing list. A board with the same identity as one
+/// already in the list replaces it in place, and new boards are appended.
+pub fn layer_boards(boards: &mut Vec<Board>, overrides: Vec<Board>) {
+    for board in overrides {
+        if let Some(existing) = boards.iter_mut().find(|b| **b == board) {
+            info!("board <{}> from {:?} overrides the one from {:?}", board.get_name(), board.library_root, existing.library_root);
+            *existing = board;
+        } else {
+            boards.push(board);
+        }
+    }
+}
+
+/// Have each board fall back to the board with the same identity in `base`, if there is one,
+/// for when the board's image can't be decoded.
+pub fn set_overridden(boards: &mut [Board], base: &[Board]) {
+    for board in boards.iter_mut() {
+        board.overridden = base.iter().find(|b| **b == *board).cloned().map(Box::new);
+    }
+}
+
+/// Gather the Boards from an ordered list of board libraries, with their images and BSPs.
+/// Boards in later libraries override boards with the same identity in earlier ones. Libraries
+/// that don't exist are skipped. The images and BSPs are cached in `cache_dir` if it's given.
+/// Like `get_boards`, this is only used by the tests.
+#[cfg(test)]
+pub fn get_boards_from_roots(roots: &[BoardRoot], cache_dir: Option<&Path>) -> Vec<Board> {
+    let mut r = get_manifests_from_roots(roots);
+    loader::decode_boards(&mut r, cache_dir);
+    r
+}
+
+/// Gather the Boards from an ordered list of board libraries, as `get_boards_from_roots` does,
+/// but from only their manifests.
+pub fn get_manifests_from_roots(roots: &[BoardRoot]) -> Vec<Board> {
+    let mut r = Vec::new();
+    let mut standards = Vec::new();
+    for root in roots.iter() {
+        if !root.path.is_dir() {
+            debug!("skipping board library {}, which doesn't exist", root);
+            continue;
+        }
+        let mut boards = get_manifests(&root.path);
+        for board in boards.iter_mut() {
+            board.library_root = Some(root.clone());
+        }
+        set_overridden(&mut boards, &r);
+        layer_boards(&mut r, boards);
+        // a standard described by a later library replaces the one from an earlier library
+        for standard in standards::get_standards(&root.path) {
+            standards.retain(|s: &standards::StandardGeometry| s.standard != standard.standard);
+            standards.push(standard);
+        }
+    }
+    attach_standards(&mut r, &standards);
+    r
+}
@@ -5,6 +5,8 @@ use log::{info, warn};
 use std::vec::Vec;
 use std::string::String;
 use std::fs;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
 use proc_macro2::TokenStream;
 use quote::{
     format_ident,
@@ -48,6 +50,52 @@ pub struct BspParseInfo {
     pub type_substitutions: Vec<(String, Option<String>)>,
 }
 
+/// The BspParseInfo as source text. Unlike the syn and proc_macro2 types, this can be sent
+/// between threads and cached on disk, so BSPs can be parsed on worker threads.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct CachedBspParseInfo {
+    pub bsp_crate_identifier: Option<String>,
+    pub use_statement: String,
+    pub board_field_identifiers: Vec<String>,
+    pub board_field_type_identifiers: Vec<String>,
+    pub field_type_token_streams: Vec<String>,
+    pub field_constructor_token_streams: Vec<String>,
+    pub available_types: Vec<String>,
+    pub type_substitutions: Vec<(String, Option<String>)>,
+}
+
+impl From<&BspParseInfo> for CachedBspParseInfo {
+    fn from(info: &BspParseInfo) -> Self {
+        let to_strings = |items: &[TokenStream]| items.iter().map(|item| item.to_string()).collect();
+        Self {
+            bsp_crate_identifier: info.bsp_crate_identifier.as_ref().map(|ident| ident.to_string()),
+            use_statement: info.use_statement.to_string(),
+            board_field_identifiers: info.board_field_identifiers.iter().map(|ident| ident.to_string()).collect(),
+            board_field_type_identifiers: info.board_field_type_identifiers.iter().map(|ident| ident.to_string()).collect(),
+            field_type_token_streams: to_strings(&info.field_type_token_streams),
+            field_constructor_token_streams: to_strings(&info.field_constructor_token_streams),
+            available_types: info.available_types.clone(),
+            type_substitutions: info.type_substitutions.clone(),
+        }
+    }
+}
+
+impl From<&CachedBspParseInfo> for BspParseInfo {
+    fn from(cached: &CachedBspParseInfo) -> Self {
+        // the source text was written from valid tokens, so it always parses back
+        let to_tokens = |items: &[String]| items.iter().map(|item| item.parse().unwrap_or_default()).collect();
+        Self {
+            bsp_crate_identifier: cached.bsp_crate_identifier.as_ref().map(|ident| format_ident!("{}", ident)),
+            use_statement: cached.use_statement.parse().unwrap_or_default(),
+            board_field_identifiers: cached.board_field_identifiers.iter().map(|ident| format_ident!("{}", ident)).collect(),
+            board_field_type_identifiers: cached.board_field_type_identifiers.iter().map(|ident| format_ident!("{}", ident)).collect(),
+            field_type_token_streams: to_tokens(&cached.field_type_token_streams),
+            field_constructor_token_streams: to_tokens(&cached.field_constructor_token_streams),
+            available_types: cached.available_types.clone(),
+            type_substitutions: cached.type_substitutions.clone(),
+        }
+    }
+}
 
 /// Use the `syn::visit::Visit` trait to explore the syntax tree of the BSP.
 impl<'ast> Visit<'ast> for BspParseInfo {
@@ -111,82 +159,88 @@ impl<'ast> Visit<'ast> for BspParseInfo {
     }
 }
 
-/// Board impls regarding parsing of BSP syntax
-impl Board {
+/// Attempt to parse the BSP lib file.
+fn parse_bsp(board_name: &str, bsp_dir: &Path) -> Option<syn::File> {
+    let src = bsp_dir.join("src/lib.rs");
+    let src = match fs::read_to_string(src.as_path()) {
+        Ok(src) => src,
+        Err(e) => {
+            warn!("Couldn't read BSP for board {:?}: {:?}", board_name, e);
+            return None;
+        },
+    };
+    match syn::parse_file(src.as_str()) {
+        Ok(syntax) => Some(syntax),
+        Err(e) => {
+            warn!("Couldn't parse BSP for board {:?} with syn: {:?}", board_name, e);
+            None
+        },
+    }
+}
 
-    // Attempt to parse the BSP lib file.
-    fn parse_bsp(&self) -> Option<syn::File> {
-        let mut syntax = None;
-        if let Some(bsp_dir) = self.bsp_path.clone() {
-            let src = bsp_dir.join("src/lib.rs");
-            let src = fs::read_to_string(src.as_path()).unwrap();
-            syntax = match syn::parse_file(src.as_str()) {
-                Ok(syntax) => {
-                    Some(syntax)
-                },
-                Err(e) => {
-                    warn!("Couldn't parse BSP for board {:?} with syn: {:?}", self.get_name(), e);
-                    None
+/// Parse the BSP of a board, given the board's name, the name of its BSP crate, and the local
+/// BSP directory. This doesn't need the Board itself, so that it can run on a worker thread.
+pub fn parse_bsp_info(board_name: &str, bsp: Option<&str>, bsp_dir: Option<&Path>) -> core::result::Result<BspParseInfo, BspParseError> {
+
+    let mut bsp_parse_info = BspParseInfo::default();
+
+    // Get the BSP crate names to construct the `use` statements and field type identifiers.
+    let bsp = match bsp {
+        Some(bsp) => bsp.replace("-", "_").replace("(", "").replace(")", "").replace(".", ""),
+        None => return Err(BspParseError::BspMissingError),
+    };
+    let bsp_crate_ident = quote::format_ident!(
+        "{}",
+        bsp,
+    );
+    bsp_parse_info.bsp_crate_identifier = Some(bsp_crate_ident.clone());
+    bsp_parse_info.use_statement = quote! {
+        use #bsp_crate_ident;
+    };
+
+    let board_field_name = board_name
+        .replace(" ", "_")
+        .replace("-", "_")
+        .replace("(", "")
+        .replace(")", "")
+        .replace(".", "")
+        .to_ascii_lowercase();
+    let board_field_ident = quote::format_ident!("{}", board_field_name);
+    bsp_parse_info.board_field_identifiers.push(board_field_ident);
+
+    // Parse the BSP to look at what's in it, determine if we need to resolve 
+    // any generic types, etc
+    info!("sending board {:?} through the syn visitor!", board_name);
+    if let Some(syntax) = bsp_dir.and_then(|bsp_dir| parse_bsp(board_name, bsp_dir)) {
+        // iterate throught the AST, looking for a struct called "Board"
+        syntax.items.iter().for_each(|item| {
+            match item {
+                syn::Item::Struct(item_struct) => {
+                    bsp_parse_info.visit_item_struct(item_struct);
                 },
-            };
-        }
-        // self.bsp_syntax = syntax.clone();
-        return syntax;
+                syn::Item::Type(item_type) => {
+                    bsp_parse_info.visit_item_type(item_type);
+                }
+                _ => (),
+            }
+        });
+    } else {
+        warn!("couldn't parse BSP syntax for board {}!", board_name);
+        return Err(BspParseError::OtherError);
     }
 
-    /// Try to add the BSP info to self, returning Ok on success or otherwise indicating the type of error.
-    pub fn load_bsp_info(&mut self) -> Result {
+    // debug!("after parsing BSPs, the datastructure looks like: \n{:#?}", bsp_parse_info);
+    Ok(bsp_parse_info)
+}
 
-        let mut bsp_parse_info = BspParseInfo::default();
-
-        // Get the BSP crate names to construct the `use` statements and field type identifiers.
-        let bsp = match self.bsp.clone() {
-            Some(bsp) => bsp.replace("-", "_").replace("(", "").replace(")", "").replace(".", ""),
-            None => return Err(BspParseError::BspMissingError),
-        };
-        let bsp_crate_ident = quote::format_ident!(
-            "{}",
-            bsp,
-        );
-        bsp_parse_info.bsp_crate_identifier = Some(bsp_crate_ident.clone());
-        bsp_parse_info.use_statement = quote! {
-            use #bsp_crate_ident;
-        };
-
-        let board_field_name = self.get_name()
-            .replace(" ", "_")
-            .replace("-", "_")
-            .replace("(", "")
-            .replace(")", "")
-            .replace(".", "")
-            .to_ascii_lowercase();
-        let board_field_ident = quote::format_ident!("{}", board_field_name);
-        bsp_parse_info.board_field_identifiers.push(board_field_ident);
-
-        // Parse the BSP to look at what's in it, determine if we need to resolve 
-        // any generic types, etc
-        info!("sending board {:?} through the syn visitor!", self.get_name());
-        if let Some(syntax) = self.parse_bsp() {
-            // iterate throught the AST, looking for a struct called "Board"
-            syntax.items.iter().for_each(|item| {
-                match item {
-                    syn::Item::Struct(item_struct) => {
-                        bsp_parse_info.visit_item_struct(item_struct);
-                    },
-                    syn::Item::Type(item_type) => {
-                        bsp_parse_info.visit_item_type(item_type);
-                    }
-                    _ => (),
-                }
-            });
-        } else {
-            warn!("couldn't parse BSP syntax for board {}!", self.get_name());
-            return Err(BspParseError::OtherError);
-        }
+/// Board impls regarding parsing of BSP syntax
+impl Board {
 
-        // debug!("after parsing BSPs, the datastructure looks like: \n{:#?}", bsp_parse_info);
+    /// Try to add the BSP info to self, returning Ok on success or otherwise indicating the type of error.
+    pub fn load_bsp_info(&mut self) -> Result {
+        let bsp_parse_info = parse_bsp_info(self.get_name(), self.bsp.as_deref(), self.bsp_path.as_deref())?;
         self.bsp_parse_info = Some(bsp_parse_info);
         Ok(())
     }
 
-}
\ No newline at end of file
+}
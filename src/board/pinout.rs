@@ -5,10 +5,12 @@ use syn;
 
 use serde::{Serialize, Deserialize};
 use std::fmt;
+use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Sequence)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Sequence)]
 #[non_exhaustive]
 pub enum InterfaceDirection {
+    #[default]
     Unknown,
     Input,
     Output,
@@ -21,7 +23,7 @@ impl fmt::Display for InterfaceDirection {
     }
 }
 
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Sequence)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash, Sequence)]
 #[non_exhaustive]
 /// The various types of electrical interfaces we use with dev boards
 pub enum InterfaceType {
@@ -32,6 +34,74 @@ pub enum InterfaceType {
     UART,
     I2C,
     SPI,
+    I2S,
+    CAN,
+    USB,
+    ONEWIRE,
+    SWD,
+    ANALOG,
+    /// A power supply rail, such as 3V3, 5V, or VBAT
+    POWER,
+    /// The ground rail
+    GND,
+}
+
+impl InterfaceType {
+    /// The roles that the pins of this type of interface are expected to take on, e.g.
+    /// BCLK, WS, and SD for I2S. These are the signals used in pin functions, like "I2S0.BCLK".
+    pub fn pin_roles(&self) -> &'static [&'static str] {
+        match self {
+            InterfaceType::NONE => &[],
+            InterfaceType::GPIO => &["IO"],
+            InterfaceType::ADC => &["AIN"],
+            InterfaceType::PWM => &["OUT", "A", "B"],
+            InterfaceType::UART => &["TX", "RX", "CTS", "RTS"],
+            InterfaceType::I2C => &["SCL", "SDA"],
+            InterfaceType::SPI => &["SCK", "MOSI", "MISO", "CS"],
+            InterfaceType::I2S => &["BCLK", "WS", "SD", "MCLK"],
+            InterfaceType::CAN => &["TX", "RX", "CANH", "CANL"],
+            InterfaceType::USB => &["D+", "D-", "VBUS", "ID"],
+            InterfaceType::ONEWIRE => &["DQ"],
+            InterfaceType::SWD => &["SWDIO", "SWCLK", "SWO", "RESET"],
+            InterfaceType::ANALOG => &["AIN", "AOUT", "AREF"],
+            InterfaceType::POWER => &["3V3", "5V", "VIN", "VBAT", "VBUS"],
+            InterfaceType::GND => &["GND"],
+        }
+    }
+
+    /// The role of the pin that a pin of the given role is wired to, at the other end of a
+    /// connection. For most interfaces this is the same role, but a UART crosses TX over to RX.
+    pub fn paired_role(&self, role: &'static str) -> &'static str {
+        match (self, role) {
+            (InterfaceType::UART, "TX") => "RX",
+            (InterfaceType::UART, "RX") => "TX",
+            (InterfaceType::UART, "CTS") => "RTS",
+            (InterfaceType::UART, "RTS") => "CTS",
+            _ => role,
+        }
+    }
+
+    /// Whether more than two boards can share one interface of this type, e.g. an I2C bus with
+    /// several peripherals on it.
+    pub fn is_multi_drop(&self) -> bool {
+        matches!(
+            self,
+            InterfaceType::I2C | InterfaceType::SPI | InterfaceType::I2S | InterfaceType::CAN |
+            InterfaceType::ONEWIRE | InterfaceType::POWER | InterfaceType::GND
+        )
+    }
+
+    /// Whether a pin of the given role is shared by every device on a multi-drop bus. This isn't
+    /// the case for roles that select a single device, like an SPI chip select.
+    pub fn is_shared_role(&self, role: &str) -> bool {
+        !matches!((self, role), (InterfaceType::SPI, "CS"))
+    }
+
+    /// Power and ground rails are shared by everything connected to them, rather than being
+    /// a signal that belongs to a single peripheral.
+    pub fn is_rail(&self) -> bool {
+        matches!(self, InterfaceType::POWER | InterfaceType::GND)
+    }
 }
 
 impl fmt::Display for InterfaceType {
@@ -61,10 +131,261 @@ impl Default for Interface {
     }
 }
 
-/// And InterfaceMapping is a map of an Interface to a set of pins on the Board.
-/// TODO: I think a "pin" should be able to be referenced by multiple different criteria,
-/// such as the "silkscreen labal", the physical pin number (i.e. counting around the board),
-/// the logical pin number, or possibly some other criteria.
+/// A PinFunction is one of the alternate functions that a pin can be muxed to, such as
+/// "I2C1.SDA" (the SDA signal of peripheral instance I2C1), "PWM5.A", or just "GPIO".
+/// In the board manifest, it is written in that same "<INSTANCE>.<SIGNAL>" string form.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct PinFunction {
+    /// The kind of interface that the function belongs to
+    pub iface_type: InterfaceType,
+    /// The peripheral instance, e.g. "I2C1", if the MCU has more than one
+    pub instance: Option<String>,
+    /// The signal of the interface, e.g. "SDA"
+    pub signal: Option<String>,
+}
+
+impl FromStr for PinFunction {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (instance, signal) = match s.split_once('.') {
+            Some((instance, signal)) => (instance, Some(signal.to_string())),
+            None => (s, None),
+        };
+        // find the interface type that prefixes the instance, preferring the longest match
+        let mut iface_types: Vec<InterfaceType> = enum_iterator::all::<InterfaceType>()
+            .filter(|t| *t != InterfaceType::NONE)
+            .collect();
+        iface_types.sort_by_key(|t| std::cmp::Reverse(t.to_string().len()));
+        let iface_type = iface_types.into_iter()
+            .find(|t| instance.to_ascii_uppercase().starts_with(&t.to_string()))
+            .ok_or(format!("pin function `{}` doesn't start with a known interface type", s))?;
+        let instance = if instance.len() > iface_type.to_string().len() {
+            Some(instance.to_string())
+        } else {
+            None
+        };
+        Ok(Self { iface_type, instance, signal })
+    }
+}
+
+impl TryFrom<String> for PinFunction {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PinFunction> for String {
+    fn from(function: PinFunction) -> Self {
+        function.to_string()
+    }
+}
+
+impl fmt::Display for PinFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.instance {
+            Some(instance) => write!(f, "{}", instance)?,
+            None => write!(f, "{}", self.iface_type)?,
+        }
+        if let Some(signal) = &self.signal {
+            write!(f, ".{}", signal)?;
+        }
+        Ok(())
+    }
+}
+
+/// The voltage domain of a pin, i.e. the logic level (or rail voltage) that it works at.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum VoltageDomain {
+    #[serde(rename = "1V8")]
+    V1V8,
+    #[serde(rename = "3V3")]
+    V3V3,
+    #[serde(rename = "5V")]
+    V5V,
+}
+
+impl fmt::Display for VoltageDomain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VoltageDomain::V1V8 => write!(f, "1.8 V"),
+            VoltageDomain::V3V3 => write!(f, "3.3 V"),
+            VoltageDomain::V5V => write!(f, "5 V"),
+        }
+    }
+}
+
+/// The electrical attributes of a pin, used for the electrical rule check of a System.
+/// In the board manifest, this is an inline table, e.g.
+/// `electrical = { voltage = "3V3", direction = "Bidirectional", pull_up = true }`
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(default)]
+pub struct Electrical {
+    /// The voltage domain of the pin, if known
+    pub voltage: Option<VoltageDomain>,
+    /// Whether the pin can safely be driven at 5 V, even if its own domain is lower
+    pub five_volt_tolerant: bool,
+    /// Whether the pin drives, receives, or both
+    pub direction: InterfaceDirection,
+    /// Whether the pin is an open-drain output, which can share a net with other outputs
+    pub open_drain: bool,
+    /// Whether the board has a pull-up resistor on this pin
+    pub pull_up: bool,
+    /// Whether the pin supplies power, e.g. the output of a regulator
+    pub power_output: bool,
+}
+
+impl Electrical {
+    /// Whether the pin actively drives its net high and low. Open-drain outputs don't count,
+    /// since any number of them can share a net.
+    pub fn is_driver(&self) -> bool {
+        self.power_output || (self.direction == InterfaceDirection::Output && !self.open_drain)
+    }
+
+    /// Whether the pin may drive its net at its voltage domain, e.g. a bidirectional GPIO.
+    pub fn can_drive(&self) -> bool {
+        self.power_output || (
+            matches!(self.direction, InterfaceDirection::Output | InterfaceDirection::Bidirectional)
+            && !self.open_drain
+        )
+    }
+
+    /// Whether the pin can be driven by other pins on its net.
+    pub fn is_receiver(&self) -> bool {
+        !self.power_output && self.direction != InterfaceDirection::Output
+    }
+
+    /// The highest voltage domain that can safely drive this pin.
+    pub fn max_input_voltage(&self) -> Option<VoltageDomain> {
+        if self.five_volt_tolerant {
+            Some(VoltageDomain::V5V)
+        } else {
+            self.voltage
+        }
+    }
+}
+
+/// A Pin is a physical pin on a Board. It can be referenced by several different criteria:
+/// the silkscreen label, the position on a header, or the logical GPIO number of the MCU.
+/// It also records the id of the element representing it in the Board's SVG image.
+///
+/// In the board manifest, pins are described in the `[[pins]]` array. For backwards
+/// compatibility, a pin can also be given as just a string, which is taken to be its SVG id.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(from = "PinRepr")]
+pub struct Pin {
+    /// The id of the element in the Board's SVG that represents this pin
+    pub svg_id: String,
+    /// The label printed on the board's silkscreen, e.g. "SCL"
+    pub silkscreen: Option<String>,
+    /// The physical position of the pin, as "<header>-<number>", e.g. "J1-11"
+    pub header_position: Option<String>,
+    /// The logical GPIO number on the MCU, e.g. 3 for GPIO3
+    pub gpio: Option<u32>,
+    /// The full path of this pin's type in the HAL, e.g. "rp2040_hal::gpio::bank0::Gpio3"
+    pub hal_type: Option<String>,
+    /// The alternate functions that this pin can be muxed to
+    pub functions: Vec<PinFunction>,
+    /// The electrical attributes of this pin
+    pub electrical: Electrical,
+}
+
+/// The ways a Pin can be written in TOML; either a bare SVG id, or a full table.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PinRepr {
+    SvgId(String),
+    Full {
+        svg_id: String,
+        #[serde(default)]
+        silkscreen: Option<String>,
+        #[serde(default)]
+        header_position: Option<String>,
+        #[serde(default)]
+        gpio: Option<u32>,
+        #[serde(default)]
+        hal_type: Option<String>,
+        #[serde(default)]
+        functions: Vec<PinFunction>,
+        #[serde(default)]
+        electrical: Electrical,
+    },
+}
+
+impl From<PinRepr> for Pin {
+    fn from(repr: PinRepr) -> Self {
+        match repr {
+            PinRepr::SvgId(svg_id) => Pin::from_svg_id(&svg_id),
+            PinRepr::Full { svg_id, silkscreen, header_position, gpio, hal_type, functions, electrical } => Pin {
+                svg_id,
+                silkscreen,
+                header_position,
+                gpio,
+                hal_type,
+                functions,
+                electrical,
+            },
+        }
+    }
+}
+
+impl Pin {
+    /// Create a Pin that is only known by its SVG id.
+    pub fn from_svg_id(svg_id: &str) -> Self {
+        Self {
+            svg_id: svg_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// The name of the GPIO, e.g. "GPIO3", if the GPIO number is known.
+    pub fn gpio_name(&self) -> Option<String> {
+        self.gpio.map(|n| format!("GPIO{}", n))
+    }
+
+    /// Check if the provided name refers to this pin by any of its identifiers.
+    /// Silkscreen labels and GPIO names are compared case-insensitively.
+    pub fn is_called(&self, name: &str) -> bool {
+        self.svg_id == name
+            || self.header_position.as_deref() == Some(name)
+            || self.silkscreen.as_ref().is_some_and(|s| s.eq_ignore_ascii_case(name))
+            || self.gpio_name().is_some_and(|g| g.eq_ignore_ascii_case(name))
+    }
+
+    /// The role that this pin plays in the given type of interface, e.g. "SDA" for I2C. This
+    /// comes from the pin's functions if it has a matching one, and otherwise from its names.
+    pub fn role_in(&self, iface_type: InterfaceType) -> Option<&'static str> {
+        let roles = iface_type.pin_roles();
+        let role_named = |name: &str| roles.iter().find(|role| role.eq_ignore_ascii_case(name)).copied();
+        self.functions.iter()
+            .filter(|f| f.iface_type == iface_type)
+            .find_map(|f| f.signal.as_deref().and_then(role_named))
+            .or_else(|| roles.iter().find(|role| self.is_called(role) || self.svg_id.eq_ignore_ascii_case(role)).copied())
+    }
+
+    /// The primary, human-readable name of the pin.
+    pub fn name(&self) -> &str {
+        self.silkscreen.as_deref().unwrap_or(&self.svg_id)
+    }
+}
+
+/// Display all of the known identifiers, i.e. "SCL / GPIO3 / J1-11"
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+        if let Some(gpio) = self.gpio_name() {
+            write!(f, " / {}", gpio)?;
+        }
+        if let Some(header_position) = &self.header_position {
+            write!(f, " / {}", header_position)?;
+        }
+        Ok(())
+    }
+}
+
+/// And InterfaceMapping is a map of an Interface to a set of pins on the Board. The pins
+/// are names that refer to a Pin by any of its identifiers (see `Pin::is_called`).
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(default)]
 pub struct InterfaceMapping {
@@ -84,6 +405,13 @@ impl Default for InterfaceMapping {
     }
 }
 
+/// Display the interface type along with the pins, i.e. "I2C (scl, sda)"
+impl fmt::Display for InterfaceMapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.interface.iface_type, self.pins.join(", "))
+    }
+}
+
 /// A Pinout is a description of the available interfaces on a Board
 // #[derive(Serialize, Deserialize, Clone, Debug)]
 // pub struct Pinout {
@@ -0,0 +1,77 @@
+//! This module describes the geometry of the standard form factors that boards can adhere to,
+//! such as where the headers of a Feather are. Each standard is described by a TOML file in the
+//! `standards` folder of a board library, named after the standard, e.g. `standards/Feather.toml`.
+//! Pins refer to the headers by their header position, e.g. "J2-11".
+
+use log::{debug, warn};
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+use egui::Vec2;
+
+use crate::board::BoardStandards;
+
+/// A row of pins that boards of a standard have in common.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Header {
+    /// The name of the header, as used in header positions, e.g. "J1"
+    pub name: String,
+    /// The number of pins in the header
+    pub pins: u32,
+    /// Where pin 1 of the header is, in mm from pin 1 of the standard's first header
+    pub origin: [f32; 2],
+    /// The direction that the pins are numbered in, as a unit vector
+    pub direction: [f32; 2],
+}
+
+/// The header geometry of a standard form factor.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StandardGeometry {
+    pub standard: BoardStandards,
+    /// The distance between neighbouring pins of a header, in mm
+    pub pitch: f32,
+    pub headers: Vec<Header>,
+}
+
+impl StandardGeometry {
+    /// Load the geometry of a standard from its TOML description.
+    pub fn load_from_toml(path: &Path) -> std::io::Result<Self> {
+        let toml_str = fs::read_to_string(path)?;
+        toml::from_str(&toml_str).map_err(|e| {
+            std::io::Error::other(format!("load from toml failed: {}", e.message()))
+        })
+    }
+
+    /// Where the pin at a header position, e.g. "J2-11", is in mm from pin 1 of the first
+    /// header. Returns None if the position isn't on one of the standard's headers.
+    pub fn header_offset(&self, header_position: &str) -> Option<Vec2> {
+        let (name, number) = header_position.split_once('-')?;
+        let number: u32 = number.parse().ok()?;
+        let header = self.headers.iter().find(|h| h.name == name)?;
+        if number == 0 || number > header.pins {
+            return None;
+        }
+        let steps = (number - 1) as f32 * self.pitch;
+        Some(egui::vec2(header.origin[0] + header.direction[0] * steps, header.origin[1] + header.direction[1] * steps))
+    }
+}
+
+/// Load the standards described in a board library. A library doesn't have to describe any.
+pub fn get_standards(boards_dir: &Path) -> Vec<StandardGeometry> {
+    let mut standards = Vec::new();
+    let Ok(entries) = fs::read_dir(boards_dir.join("standards")) else {
+        debug!("no standards in board library {}", boards_dir.display());
+        return standards;
+    };
+    let mut paths: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths.into_iter().filter(|p| p.extension().unwrap_or_default() == "toml") {
+        match StandardGeometry::load_from_toml(&path) {
+            Ok(standard) => standards.push(standard),
+            Err(e) => warn!("error loading standard from {}: {:?}", path.display(), e),
+        }
+    }
+    standards
+}
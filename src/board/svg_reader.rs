@@ -1,9 +1,19 @@
 //! A module to help parse and SVG file with an embedded image, and various
 //! SVG paths that should be converted into egui elements.
 //!
+//! The board art is rasterized from the whole SVG with resvg, at the resolution it's shown at,
+//! so that it stays sharp when zoomed in. The embedded PNG, if there is one, is used if the SVG
+//! can't be rasterized.
+//!
+//! Pins are the shapes and groups of the SVG whose ids are among the board's pins, so a pad made
+//! of several shapes can be grouped under the pin's id. Other elements can have ids too, as every
+//! element exported from Inkscape or KiCad does. Without a list of pins, every shape with an id is
+//! a pin. Pins can be any kind of shape, e.g. a `<circle>`, `<rect>` or `<ellipse>`, and can be
+//! transformed, or inside transformed groups, as exported from Inkscape or KiCad. Each pin keeps
+//! its outline, so that the editor can hit-test the pad itself rather than its bounding box.
+//!
 //! A few things are assumed about the SVG files:
 //!   * The units should be in mm
-//!   * All transforms have been removed and the units of all elements are absolute
 //!   * All paths that should be displayed in Iron Coder have element id's that are also in the board manifest pinouts section.
 
 use usvg::{
@@ -11,16 +21,24 @@ use usvg::{
     TreeParsing,
     Tree,
     NodeKind,
+    NodeExt,
     ImageKind,
+    Transform,
+    tiny_skia_path::PathSegment,
 };
 use std::io::Cursor;
 use std::path::Path;
 use std::fs;
 use std::vec::Vec;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
 
 use std::borrow::Borrow;
 
 use image;
+use serde::{Serialize, Deserialize};
 use egui::{
     ColorImage,
     Pos2,
@@ -28,8 +46,9 @@ use egui::{
     Vec2,
 };
 
-/// A struct that holds the decoded SVG for use in egui.
-#[derive(Default, Clone)]
+/// A struct that holds the decoded SVG for use in egui. It can be cached on disk, without the
+/// SVG source, which is read again to rasterize the board art.
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct SvgBoardInfo {
     /// The SVG size (should be in mm)
     pub physical_size: Vec2,
@@ -37,22 +56,151 @@ pub struct SvgBoardInfo {
     pub image: ColorImage,
     /// A vector of egui Rects that represent the pin locations on the Board
     pub pin_rects: Vec<(String, Rect)>,
+    /// The outlines of the pins, by id, in the same units as the pin rects
+    pin_outlines: HashMap<String, PinOutline>,
+    /// The SVG source, which the board art is rasterized from
+    #[serde(skip)]
+    svg_data: Arc<Vec<u8>>,
+    /// The ids of the SVG elements that mark pins, which are left out of the board art
+    #[serde(skip)]
+    pin_ids: Arc<HashSet<String>>,
+    /// A hash of the SVG source and pin ids, which tells apart the art of boards with the same
+    /// library id, e.g. a board and the board that overrides it, or a board before and after
+    /// its SVG was changed
+    #[serde(skip)]
+    source_hash: u64,
+}
+
+/// The outline of a pin, as closed polygons in mm from the top-left of the SVG. Curves are
+/// flattened into line segments.
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PinOutline {
+    pub polygons: Vec<Vec<Pos2>>,
+}
+
+/// The number of line segments that each curve of an outline is flattened into
+const CURVE_SEGMENTS: usize = 8;
+
+impl PinOutline {
+    /// Whether a point is inside the outline, by the even-odd rule.
+    pub fn contains(&self, point: Pos2) -> bool {
+        let mut inside = false;
+        for polygon in self.polygons.iter() {
+            for (idx, a) in polygon.iter().enumerate() {
+                let b = polygon[(idx + 1) % polygon.len()];
+                if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+                    inside = !inside;
+                }
+            }
+        }
+        inside
+    }
+
+    /// The bounding box of the outline.
+    pub fn bounds(&self) -> Rect {
+        Rect::from_points(&self.polygons.concat())
+    }
+
+    /// The outline with each of its points moved, e.g. to where it's shown on screen.
+    pub fn map(&self, f: impl Fn(Pos2) -> Pos2) -> PinOutline {
+        PinOutline {
+            polygons: self.polygons.iter().map(|polygon| polygon.iter().map(|p| f(*p)).collect()).collect(),
+        }
+    }
+
+    /// Add the outline of a path, transformed into the coordinates of the whole SVG.
+    fn add_path(&mut self, path: &usvg::Path, transform: Transform, origin: Vec2) {
+        let Some(data) = path.data.as_ref().clone().transform(transform) else {
+            return;
+        };
+        let mut polygon: Vec<Pos2> = Vec::new();
+        let mut last = Pos2::ZERO;
+        let to_pos = |p: usvg::tiny_skia_path::Point| Pos2::new(p.x, p.y) - origin;
+        for segment in data.segments() {
+            match segment {
+                PathSegment::MoveTo(p) => {
+                    self.close(&mut polygon);
+                    last = to_pos(p);
+                    polygon.push(last);
+                },
+                PathSegment::LineTo(p) => {
+                    last = to_pos(p);
+                    polygon.push(last);
+                },
+                PathSegment::QuadTo(p1, p) => {
+                    let (p0, p1, p) = (last, to_pos(p1), to_pos(p));
+                    polygon.extend((1..=CURVE_SEGMENTS).map(|i| {
+                        let t = i as f32 / CURVE_SEGMENTS as f32;
+                        let u = 1.0 - t;
+                        (p0.to_vec2() * u * u + p1.to_vec2() * 2.0 * u * t + p.to_vec2() * t * t).to_pos2()
+                    }));
+                    last = p;
+                },
+                PathSegment::CubicTo(p1, p2, p) => {
+                    let (p0, p1, p2, p) = (last, to_pos(p1), to_pos(p2), to_pos(p));
+                    polygon.extend((1..=CURVE_SEGMENTS).map(|i| {
+                        let t = i as f32 / CURVE_SEGMENTS as f32;
+                        let u = 1.0 - t;
+                        (p0.to_vec2() * u * u * u + p1.to_vec2() * 3.0 * u * u * t + p2.to_vec2() * 3.0 * u * t * t + p.to_vec2() * t * t * t).to_pos2()
+                    }));
+                    last = p;
+                },
+                PathSegment::Close => self.close(&mut polygon),
+            }
+        }
+        self.close(&mut polygon);
+    }
+
+    /// Finish a polygon of the outline, if it has an area.
+    fn close(&mut self, polygon: &mut Vec<Pos2>) {
+        let polygon = std::mem::take(polygon);
+        if polygon.len() >= 3 {
+            self.polygons.push(polygon);
+        }
+    }
+}
+
+/// The largest zoom bucket, at 2^6 = 64 pixels per mm
+const MAX_ZOOM_BUCKET: i32 = 6;
+/// The longest side of a rasterized image, in pixels, which keeps it under the texture size limit
+const MAX_RASTER_SIDE: f32 = 4096.0;
+/// The resolution of the image of a board that only has vector art, in pixels per mm
+const DEFAULT_PIXELS_PER_MM: f32 = 8.0;
+
+/// The zoom bucket of a resolution in pixels per mm. Rasters are made at powers of two pixels
+/// per mm, rounding up so that they're never scaled up on screen.
+pub fn zoom_bucket(pixels_per_mm: f32) -> i32 {
+    (pixels_per_mm.max(1.0).log2().ceil() as i32).min(MAX_ZOOM_BUCKET)
 }
 
 impl SvgBoardInfo {
 
     /// Parse an Iron Coder SVG Board image from the filesystem.
     pub fn from_path(path: &Path) -> Result<SvgBoardInfo, Error> {
+        Self::from_path_with_pins(path, &[])
+    }
 
-        let mut svg_board_info = SvgBoardInfo::default();
+    /// Parse an Iron Coder SVG Board image from the filesystem, leaving the elements that mark
+    /// the given pins out of the board art. The pin markers are hit areas for the editor to
+    /// draw over, rather than part of the board.
+    pub fn from_path_with_pins(path: &Path, pin_ids: &[String]) -> Result<SvgBoardInfo, Error> {
+        match fs::read(path) {
+            Ok(svg_data) => Self::from_data_with_pins(svg_data, pin_ids),
+            Err(e) => Err(Error::FsError(e)),
+        }
+    }
 
-        let svg_string = match fs::read_to_string(path) {
-            Ok(string) => string,
-            Err(e) => return Err(Error::FsError(e)),
+    /// Parse an Iron Coder SVG Board image from its source, as `from_path_with_pins` does.
+    pub fn from_data_with_pins(svg_data: Vec<u8>, pin_ids: &[String]) -> Result<SvgBoardInfo, Error> {
+
+        let mut svg_board_info = SvgBoardInfo {
+            pin_ids: Arc::new(pin_ids.iter().cloned().collect()),
+            source_hash: source_hash(&svg_data, pin_ids),
+            ..Default::default()
         };
-        
+
         let options = Options::default();
-        let tree = match Tree::from_str(&svg_string.as_str(), &options) {
+        let tree = match Tree::from_data(&svg_data, &options) {
             Ok(t) => t,
             Err(_e) => return Err(Error::OtherError),
         };
@@ -63,6 +211,8 @@ impl SvgBoardInfo {
             x: tree.view_box.rect.width(),
             y: tree.view_box.rect.height(),
         };
+        // pins are measured from the top-left of the view box
+        let origin = Vec2::new(tree.view_box.rect.x(), tree.view_box.rect.y());
     
         // iterate through the svg looking for elements
         let mut board_image: Option<ColorImage> = None;
@@ -88,25 +238,28 @@ impl SvgBoardInfo {
                         board_image = Some(color_image);
                     }
                 },
-                NodeKind::Path(path) => {
-                    let id = path.id;
-                    let bounds = path.data.bounds(); 
-                    let min = Pos2 {
-                        x: bounds.left(),
-                        y: bounds.top(),
-                    };
-                    let max = Pos2 {
-                        x: bounds.right(),
-                        y: bounds.bottom(),
-                    };
-                    let rect = Rect::from_min_max(min, max);
-                    svg_board_info.pin_rects.push((String::from(id), rect));
+                // a group with the id of a pin is a pad made of all of the shapes in it
+                NodeKind::Group(group) if svg_board_info.pin_ids.contains(&group.id) => {
+                    let mut outline = PinOutline::default();
+                    for child in node.descendants() {
+                        if let NodeKind::Path(ref path) = *child.borrow() {
+                            outline.add_path(path, child.abs_transform(), origin);
+                        }
+                    }
+                    svg_board_info.add_pin(group.id, outline);
+                },
+                NodeKind::Path(path) if svg_board_info.is_pin(&path.id) && !in_pin_group(&node, &svg_board_info.pin_ids) => {
+                    let mut outline = PinOutline::default();
+                    outline.add_path(&path, node.abs_transform(), origin);
+                    svg_board_info.add_pin(path.id, outline);
                 },
                 _ => {},
             }
         }
     
-        if let Some(board_image) = board_image {
+        svg_board_info.svg_data = Arc::new(svg_data);
+        // boards without an embedded PNG are drawn entirely in vector form
+        if let Some(board_image) = board_image.or_else(|| svg_board_info.rasterize(DEFAULT_PIXELS_PER_MM)) {
             svg_board_info.image = board_image;
         } else {
             return Err(Error::NoImage);
@@ -115,6 +268,96 @@ impl SvgBoardInfo {
         return Ok(svg_board_info);
     }
 
+    /// Give SvgBoardInfo loaded from the cache back its SVG source and pin ids, which aren't
+    /// cached.
+    pub fn with_source(self, svg_data: Vec<u8>, pin_ids: &[String]) -> SvgBoardInfo {
+        SvgBoardInfo {
+            source_hash: source_hash(&svg_data, pin_ids),
+            svg_data: Arc::new(svg_data),
+            pin_ids: Arc::new(pin_ids.iter().cloned().collect()),
+            ..self
+        }
+    }
+
+    /// Whether an element with the given id is a pin, i.e. one of the board's pins, or any
+    /// element with an id if the board's pins aren't known.
+    fn is_pin(&self, id: &str) -> bool {
+        !id.is_empty() && (self.pin_ids.is_empty() || self.pin_ids.contains(id))
+    }
+
+    /// A hash of the SVG source and pin ids that the board art is made from.
+    pub fn source_hash(&self) -> u64 {
+        self.source_hash
+    }
+
+    /// Add a pin with its outline, if the outline isn't empty.
+    fn add_pin(&mut self, id: String, outline: PinOutline) {
+        if outline.polygons.is_empty() {
+            return;
+        }
+        self.pin_rects.push((id.clone(), outline.bounds()));
+        self.pin_outlines.insert(id, outline);
+    }
+
+    /// The outline of a pin, by its id.
+    pub fn pin_outline(&self, svg_id: &str) -> Option<&PinOutline> {
+        self.pin_outlines.get(svg_id)
+    }
+
+    /// The pin whose outline contains a point, in mm from the top-left of the SVG, if any.
+    pub fn pin_at(&self, point: Pos2) -> Option<&str> {
+        self.pin_rects.iter()
+            .find(|(id, rect)| rect.contains(point) && self.pin_outlines[id].contains(point))
+            .map(|(id, _)| id.as_str())
+    }
+
+    /// The board art for showing at a resolution, in pixels per mm. The SVG is rasterized at the
+    /// resolution of the zoom bucket on every call, so the result should be kept, as the board
+    /// textures are. If the SVG can't be rasterized, the embedded PNG is used instead.
+    pub fn image_at(&self, pixels_per_mm: f32) -> ColorImage {
+        self.rasterize(2f32.powi(zoom_bucket(pixels_per_mm))).unwrap_or_else(|| self.image.clone())
+    }
+
+    /// Rasterize the SVG at a resolution, in pixels per mm, limited so that the image isn't
+    /// larger than a texture can be.
+    fn rasterize(&self, pixels_per_mm: f32) -> Option<ColorImage> {
+        use resvg::tiny_skia;
+        let mut tree = Tree::from_data(&self.svg_data, &Options::default()).ok()?;
+        let pins: Vec<usvg::Node> = tree.root.descendants()
+            .filter(|node| self.pin_ids.contains(node.borrow().id()))
+            .collect();
+        for pin in pins {
+            pin.detach();
+        }
+        let view_box = tree.view_box.rect;
+        let longest = view_box.width().max(view_box.height());
+        let pixels_per_mm = pixels_per_mm.min(MAX_RASTER_SIDE / longest);
+        let size = usvg::Size::from_wh(view_box.width() * pixels_per_mm, view_box.height() * pixels_per_mm)?.to_int_size();
+        let mut pixmap = tiny_skia::Pixmap::new(size.width(), size.height())?;
+        tree.size = size.to_size();
+        resvg::Tree::from_usvg(&tree).render(tiny_skia::Transform::default(), &mut pixmap.as_mut());
+        Some(ColorImage::from_rgba_premultiplied(
+            [size.width() as usize, size.height() as usize],
+            pixmap.data(),
+        ))
+    }
+
+}
+
+/// Hash the SVG source along with the pin ids, which are left out of the board art.
+fn source_hash(svg_data: &[u8], pin_ids: &[String]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    svg_data.hash(&mut hasher);
+    pin_ids.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Whether a node is inside a group with the id of a pin, which makes up the pin's outline.
+fn in_pin_group(node: &usvg::Node, pin_ids: &HashSet<String>) -> bool {
+    node.ancestors().skip(1).any(|ancestor| match *ancestor.borrow() {
+        NodeKind::Group(ref group) => pin_ids.contains(&group.id),
+        _ => false,
+    })
 }
 
 #[derive(Debug)]
@@ -17,7 +17,6 @@ fn cli_cmd(str: &str) {
 mod board_tests {
     use std::collections::HashSet;
     use std::path::Path;
-    use eframe::glow::FALSE;
     use egui::TextBuffer;
     use crate::board;
     use crate::board::get_boards;
@@ -26,7 +25,7 @@ mod board_tests {
     #[test]
     pub fn test_get_boards() {
         let mut board_names: HashSet<&str> = HashSet::from(["Feather nRF52832", "Feather RP2040", "OLED Featherwing (128x64)", "PropMaker Featherwing", "PiTFT 3.2 with Capacitive Touch Screen", "MicroMod ESP32 Processor"]);
-        let boards = board::get_boards(Path::new("./iron-coder-boards"));
+        let boards = board::get_boards(Path::new("./iron-coder-boards"), None);
         for board in boards {
             assert!(board_names.contains(board.get_name()));
             board_names.remove(board.get_name());
@@ -36,15 +35,15 @@ mod board_tests {
     #[test]
     pub fn test_board_info() {
         // Ensure boards have crates associated with them.
-        let mut boards = board::get_boards(Path::new("./iron-coder-boards"));
+        let mut boards = board::get_boards(Path::new("./iron-coder-boards"), None);
         for board in boards {
-            assert!(board.related_crates().unwrap().is_empty() == FALSE);
+            assert!(!board.related_crates().unwrap().is_empty());
         }
     }
     #[test]
     pub fn test_board_crates() {
         // Ensure crates don't have any errors.
-        let mut boards = board::get_boards(Path::new("./iron-coder-boards"));
+        let mut boards = board::get_boards(Path::new("./iron-coder-boards"), None);
         let mut cmd = "cargo check --features".to_owned();
         for board in boards {
             for crates in board.related_crates().unwrap() {
@@ -53,4 +52,363 @@ mod board_tests {
             }
         }
     }
+    #[test]
+    pub fn test_validate_boards() {
+        // Every board that get_boards loads should have a loadable report, and vice-versa.
+        let reports = board::validation::validate_boards(Path::new("./iron-coder-boards"));
+        let boards = board::get_boards(Path::new("./iron-coder-boards"), None);
+        let loadable: HashSet<String> = reports.iter()
+            .filter(|r| r.is_loadable())
+            .filter_map(|r| r.board_name.clone())
+            .collect();
+        let loaded: HashSet<String> = boards.iter().map(|b| b.get_name().to_string()).collect();
+        assert_eq!(loadable, loaded);
+    }
+    #[test]
+    pub fn test_validate_bad_manifest() {
+        use crate::board::validation::{validate_board, ProblemKind};
+        let dir = std::env::temp_dir().join("iron_coder_test_validate_bad_manifest");
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = dir.join("bad_board.toml");
+        std::fs::write(&manifest, "name = \"Bad Board\"\n\n[[pinout]]\npins = [\"a\"]\ninterface = { iface_type = \"CAN_NOT\", direction = \"Input\" }\n").unwrap();
+        let report = validate_board(&manifest);
+        assert_eq!(report.board_name.as_deref(), Some("Bad Board"));
+        assert!(!report.is_loadable());
+        let unknown = report.problems.iter().find(|p| p.kind == ProblemKind::UnknownInterfaceType).unwrap();
+        assert_eq!(unknown.line, Some(5));
+        assert!(report.problems.iter().any(|p| p.kind == ProblemKind::MissingSvg));
+        // now break the toml syntax
+        std::fs::write(&manifest, "name = \"Bad Board\"\nis_main_board = \n").unwrap();
+        let report = validate_board(&manifest);
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].kind, ProblemKind::BadToml);
+        assert_eq!(report.problems[0].line, Some(2));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    pub fn test_layered_board_roots() {
+        use crate::board::{BoardRoot, BoardRootKind, get_boards_from_roots};
+        // make an extra library that overrides the Feather RP2040 with a different amount of RAM
+        let root = std::env::temp_dir().join("iron_coder_test_layered_board_roots");
+        let board_dir = root.join("Acme").join("Feather_RP2040");
+        std::fs::create_dir_all(&board_dir).unwrap();
+        let src_dir = Path::new("./iron-coder-boards/Adafruit/Feather_RP2040");
+        let manifest = std::fs::read_to_string(src_dir.join("feather_rp2040.toml")).unwrap();
+        std::fs::write(board_dir.join("feather_rp2040.toml"), manifest.replace("ram = 264", "ram = 512")).unwrap();
+        let svg = std::fs::read_to_string(src_dir.join("feather_rp2040.svg")).unwrap();
+        std::fs::write(board_dir.join("feather_rp2040.svg"), svg + "<!-- acme -->").unwrap();
+
+        let bundled = BoardRoot::new(BoardRootKind::Bundled, "./iron-coder-boards");
+        let extra = BoardRoot::new(BoardRootKind::Extra, root.clone());
+        let missing = BoardRoot::new(BoardRootKind::User, root.join("does_not_exist"));
+        let bundled_boards = get_boards_from_roots(&[bundled.clone()], None);
+        let boards = get_boards_from_roots(&[bundled.clone(), missing, extra.clone()], None);
+        assert_eq!(boards.len(), bundled_boards.len());
+        let feathers: Vec<&board::Board> = boards.iter().filter(|b| b.get_name() == "Feather RP2040").collect();
+        assert_eq!(feathers.len(), 1);
+        assert_eq!(feathers[0].ram, Some(512));
+        assert_eq!(feathers[0].get_library_root(), Some(&extra));
+
+        // the bundled Feather isn't decoded, since the one overriding it decodes fine. Its image
+        // is swapped for the override's, and its BSP, which the override doesn't have, is left out.
+        let cache_dir = root.join("cache");
+        let bundled_cache_dir = root.join("bundled_cache");
+        get_boards_from_roots(&[bundled.clone()], Some(&bundled_cache_dir));
+        get_boards_from_roots(&[bundled, extra], Some(&cache_dir));
+        let count = |dir: &Path| std::fs::read_dir(dir).unwrap().count();
+        assert_eq!(count(&cache_dir), count(&bundled_cache_dir) - 1);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    #[test]
+    pub fn test_broken_board_override() {
+        use crate::board::{BoardRoot, BoardRootKind, get_boards_from_roots, get_manifests_from_roots};
+        use crate::board::loader::BoardLoader;
+        // make an extra library that overrides the Feather RP2040 with an image that can't be decoded
+        let root = std::env::temp_dir().join("iron_coder_test_broken_board_override");
+        let board_dir = root.join("Acme").join("Feather_RP2040");
+        std::fs::create_dir_all(&board_dir).unwrap();
+        let src_dir = Path::new("./iron-coder-boards/Adafruit/Feather_RP2040");
+        std::fs::copy(src_dir.join("feather_rp2040.toml"), board_dir.join("feather_rp2040.toml")).unwrap();
+        std::fs::write(board_dir.join("feather_rp2040.svg"), "not an svg").unwrap();
+
+        // the bundled board is used instead of the broken one
+        let bundled = BoardRoot::new(BoardRootKind::Bundled, "./iron-coder-boards");
+        let roots = [bundled.clone(), BoardRoot::new(BoardRootKind::Extra, root.clone())];
+        let boards = get_boards_from_roots(&roots, None);
+        let feather = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        assert_eq!(feather.get_library_root(), Some(&bundled));
+        assert!(feather.svg_board_info.is_some());
+
+        // and the same when the boards are decoded in the background
+        let mut boards = get_manifests_from_roots(&roots);
+        let mut loader = BoardLoader::start(&boards, None);
+        while !loader.is_finished() {
+            loader.poll(&mut boards);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let feather = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        assert_eq!(feather.get_library_root(), Some(&bundled));
+        assert!(feather.svg_board_info.is_some());
+        // the bundled board is decoded in place of the broken one, rather than as a board of its own
+        assert_eq!(loader.progress(), (boards.len(), boards.len()));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+    #[test]
+    pub fn test_pin_identifiers() {
+        use crate::board::pinout::Pin;
+        #[derive(serde::Deserialize)]
+        struct Pins { pins: Vec<Pin> }
+        let Pins { pins } = toml::from_str(
+            "pins = [\"d5\", { svg_id = \"scl\", silkscreen = \"SCL\", header_position = \"J1-11\", gpio = 3 }]"
+        ).unwrap();
+        assert_eq!(pins[0], Pin::from_svg_id("d5"));
+        assert_eq!(pins[0].to_string(), "d5");
+        assert_eq!(pins[1].to_string(), "SCL / GPIO3 / J1-11");
+        for name in ["scl", "SCL", "J1-11", "gpio3", "GPIO3"] {
+            assert!(pins[1].is_called(name), "pin should be called {}", name);
+        }
+        assert!(!pins[1].is_called("J1-1"));
+        // the Feather RP2040 manifest describes its pins with all of their identifiers
+        let boards = board::get_boards(Path::new("./iron-coder-boards"), None);
+        let feather = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        let scl = feather.find_pin("GPIO3").unwrap();
+        assert_eq!(scl.svg_id, "scl");
+        assert_eq!(feather.get_pin_by_svg_id("scl"), scl);
+        assert_eq!(feather.find_pin("J2-12").unwrap().svg_id, "sda");
+        assert!(feather.find_pin("no-such-pin").is_none());
+    }
+
+    #[test]
+    pub fn test_pin_functions() {
+        use crate::board::pinout::{InterfaceType, PinFunction};
+        let sda: PinFunction = "I2C1.SDA".parse().unwrap();
+        assert_eq!(sda.iface_type, InterfaceType::I2C);
+        assert_eq!(sda.instance.as_deref(), Some("I2C1"));
+        assert_eq!(sda.signal.as_deref(), Some("SDA"));
+        assert_eq!(sda.to_string(), "I2C1.SDA");
+        let gpio: PinFunction = "GPIO".parse().unwrap();
+        assert_eq!((gpio.iface_type, gpio.instance, gpio.signal), (InterfaceType::GPIO, None, None));
+        assert!("FOO3.BAR".parse::<PinFunction>().is_err());
+        // the Feather RP2040 manifest lists the RP2040 mux options of each pin
+        let boards = board::get_boards(Path::new("./iron-coder-boards"), None);
+        let feather = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        let functions = feather.find_pin("SDA").unwrap().functions;
+        assert!(functions.contains(&sda));
+        assert!(functions.contains(&"PWM1.A".parse().unwrap()));
+    }
+
+    #[test]
+    pub fn test_interface_types() {
+        use crate::board::pinout::{InterfaceMapping, InterfaceType, PinFunction};
+        assert_eq!(InterfaceType::I2S.pin_roles(), &["BCLK", "WS", "SD", "MCLK"]);
+        assert!(InterfaceType::GND.is_rail() && InterfaceType::POWER.is_rail());
+        assert!(!InterfaceType::CAN.is_rail());
+        // every interface type can be named in a manifest
+        for iface_type in enum_iterator::all::<InterfaceType>() {
+            let mapping: InterfaceMapping = toml::from_str(&format!(
+                "pins = []\ninterface = {{ iface_type = \"{}\", direction = \"Unknown\" }}", iface_type
+            )).unwrap();
+            assert_eq!(mapping.interface.iface_type, iface_type);
+        }
+        let power: PinFunction = "POWER.3V3".parse().unwrap();
+        assert_eq!(power.iface_type, InterfaceType::POWER);
+        let onewire: PinFunction = "ONEWIRE.DQ".parse().unwrap();
+        assert_eq!(onewire.iface_type, InterfaceType::ONEWIRE);
+        let i2s: PinFunction = "I2S0.BCLK".parse().unwrap();
+        assert_eq!(i2s.iface_type, InterfaceType::I2S);
+    }
+
+    #[test]
+    pub fn test_svg_rasterization() {
+        use crate::board::svg_reader::{SvgBoardInfo, zoom_bucket};
+        // a board drawn entirely in vector form, 10mm x 5mm, with a pin on its right half
+        let dir = std::env::temp_dir().join("iron_coder_test_svg_rasterization");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let svg_path = dir.join("vector_board.svg");
+        std::fs::write(&svg_path, concat!(
+            "<svg width=\"10mm\" height=\"5mm\" viewBox=\"0 0 10 5\" xmlns=\"http://www.w3.org/2000/svg\">",
+            "<rect id=\"pcb\" x=\"0\" y=\"0\" width=\"5\" height=\"5\" fill=\"#008000\"/>",
+            "<circle id=\"sda\" cx=\"7.5\" cy=\"2.5\" r=\"1\" fill=\"#ff00ff\"/>",
+            "</svg>",
+        )).unwrap();
+        let svg_board_info = SvgBoardInfo::from_path(&svg_path).unwrap();
+        assert_eq!(svg_board_info.physical_size, egui::vec2(10.0, 5.0));
+        assert_eq!(svg_board_info.image.size, [80, 40]);
+        assert_eq!(svg_board_info.image[(60, 20)], egui::Color32::from_rgb(0xff, 0x00, 0xff));
+        let svg_board_info = SvgBoardInfo::from_path_with_pins(&svg_path, &["sda".to_string()]).unwrap();
+
+        // rasters are made at the next power of two pixels per mm, so nearby zooms share them
+        assert_eq!((zoom_bucket(0.5), zoom_bucket(10.0), zoom_bucket(16.0), zoom_bucket(1000.0)), (0, 4, 4, 6));
+        let image = svg_board_info.image_at(10.0);
+        assert_eq!(image.size, [160, 80]);
+        assert_eq!(image.pixels, svg_board_info.image_at(12.0).pixels);
+        // the board art is drawn, but the pins aren't
+        assert_eq!(image[(40, 40)], egui::Color32::from_rgb(0x00, 0x80, 0x00));
+        assert_eq!(image[(120, 40)], egui::Color32::TRANSPARENT);
+        assert_eq!(svg_board_info.image_at(1000.0).size, [640, 320]);
+
+        // boards with an embedded PNG are sharper when rasterized from the SVG at a high zoom,
+        // and the PNG is drawn along with the rest of the art
+        let boards = board::get_boards(Path::new("./iron-coder-boards"), None);
+        let opaque_share = |image: &egui::ColorImage| {
+            image.pixels.iter().filter(|p| p.a() > 0).count() as f32 / image.pixels.len() as f32
+        };
+        for name in ["Feather RP2040", "OLED Featherwing (128x64)"] {
+            let board = boards.iter().find(|b| b.get_name() == name).unwrap();
+            let svg_board_info = board.svg_board_info.as_ref().unwrap();
+            let image = svg_board_info.image_at(64.0);
+            assert!(image.size[0] > svg_board_info.image.size[0]);
+            assert!(opaque_share(&image) > 0.4, "{} is only {} opaque", name, opaque_share(&image));
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    pub fn test_svg_pin_shapes() {
+        use crate::board::svg_reader::SvgBoardInfo;
+        // pins of every shape, inside transformed groups, with a view box that doesn't start at 0
+        let dir = std::env::temp_dir().join("iron_coder_test_svg_pin_shapes");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let svg_path = dir.join("shapes_board.svg");
+        std::fs::write(&svg_path, concat!(
+            "<svg width=\"20mm\" height=\"10mm\" viewBox=\"100 50 20 10\" xmlns=\"http://www.w3.org/2000/svg\">",
+            "<g id=\"layer1\" transform=\"translate(100 50)\">",
+            "<rect id=\"pcb\" x=\"0\" y=\"0\" width=\"20\" height=\"10\" fill=\"green\"/>",
+            "<circle id=\"sda\" cx=\"2\" cy=\"2\" r=\"1\"/>",
+            "<g transform=\"translate(10 5)\"><rect id=\"scl\" x=\"-1\" y=\"-1\" width=\"2\" height=\"2\" transform=\"rotate(45)\"/></g>",
+            "<ellipse id=\"gnd\" cx=\"16\" cy=\"2\" rx=\"2\" ry=\"1\"/>",
+            "<g id=\"vbus\"><rect x=\"1\" y=\"7\" width=\"2\" height=\"2\"/><rect id=\"vbus_pad2\" x=\"5\" y=\"7\" width=\"2\" height=\"2\"/></g>",
+            "</g>",
+            "</svg>",
+        )).unwrap();
+        let pins = ["sda", "scl", "gnd", "vbus"].map(String::from);
+        let svg_board_info = SvgBoardInfo::from_path_with_pins(&svg_path, &pins).unwrap();
+        let ids: Vec<&str> = svg_board_info.pin_rects.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["sda", "scl", "gnd", "vbus"]);
+        // other elements with ids, like the board outline, aren't pins
+        assert!(svg_board_info.pin_outline("pcb").is_none());
+
+        // the transforms are applied, and pins are measured from the top-left of the view box
+        let rect = |id: &str| svg_board_info.pin_rects.iter().find(|(pin, _)| pin == id).unwrap().1;
+        assert!((rect("sda").center() - egui::pos2(2.0, 2.0)).length() < 0.01);
+        assert!((rect("scl").center() - egui::pos2(10.0, 5.0)).length() < 0.01);
+        assert!((rect("scl").width() - 2.0 * 2f32.sqrt()).abs() < 0.01);
+        assert!((rect("vbus").center() - egui::pos2(4.0, 8.0)).length() < 0.01);
+
+        // hit-testing follows the shape of each pad, rather than its bounding box
+        assert_eq!(svg_board_info.pin_at(egui::pos2(2.0, 2.0)), Some("sda"));
+        assert_eq!(svg_board_info.pin_at(egui::pos2(1.1, 1.1)), None);
+        assert_eq!(svg_board_info.pin_at(egui::pos2(10.0, 4.0)), Some("scl"));
+        assert_eq!(svg_board_info.pin_at(egui::pos2(9.2, 4.2)), None);
+        assert_eq!(svg_board_info.pin_at(egui::pos2(17.5, 2.0)), Some("gnd"));
+        assert_eq!(svg_board_info.pin_at(egui::pos2(17.5, 2.8)), None);
+        // a grouped pin is made of all of its shapes, but not the gap between them
+        assert_eq!(svg_board_info.pin_at(egui::pos2(6.0, 8.0)), Some("vbus"));
+        assert_eq!(svg_board_info.pin_at(egui::pos2(4.0, 8.0)), None);
+        assert_eq!(svg_board_info.pin_outline("vbus").unwrap().polygons.len(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    pub fn test_board_textures() {
+        use crate::board::textures::{board_texture, retain_boards};
+        let ctx = egui::Context::default();
+        let allocated = || ctx.tex_manager().read().num_allocated();
+        let before = allocated();
+        let boards = board::get_boards(Path::new("./iron-coder-boards"), None);
+        let feather = boards.iter().find(|b| b.get_name() == "Feather RP2040").unwrap();
+        let oled = boards.iter().find(|b| b.get_name() == "OLED Featherwing (128x64)").unwrap();
+
+        // each board is uploaded once per zoom bucket, and only when it's first drawn
+        let texture = board_texture(&ctx, feather, 10.0).unwrap();
+        assert_eq!(allocated(), before + 1);
+        assert_eq!(board_texture(&ctx, feather, 12.0).unwrap().id(), texture.id());
+        assert_ne!(board_texture(&ctx, feather, 20.0).unwrap().id(), texture.id());
+        let _ = board_texture(&ctx, oled, 10.0).unwrap();
+        assert_eq!(allocated(), before + 3);
+        drop(texture);
+
+        // a board with the same library id but other art, like an override or a board whose SVG
+        // has changed, doesn't share its textures
+        let mut changed = feather.clone();
+        let svg = "<svg width=\"10mm\" height=\"5mm\" viewBox=\"0 0 10 5\" xmlns=\"http://www.w3.org/2000/svg\"><rect width=\"10\" height=\"5\" fill=\"red\"/></svg>";
+        changed.svg_board_info = Some(crate::board::svg_reader::SvgBoardInfo::from_data_with_pins(svg.as_bytes().to_vec(), &[]).unwrap());
+        assert!(changed.is_same_library_board(feather));
+        let _ = board_texture(&ctx, &changed, 10.0).unwrap();
+        assert_eq!(allocated(), before + 4);
+
+        // textures are freed once their boards are gone
+        retain_boards(&ctx, [oled, &changed]);
+        assert_eq!(allocated(), before + 2);
+        retain_boards(&ctx, []);
+        assert_eq!(allocated(), before);
+    }
+
+    #[test]
+    pub fn test_board_loader() {
+        use crate::board::loader::{self, decode_boards, BoardLoader};
+        use crate::board::parsing::CachedBspParseInfo;
+        let cache_dir = std::env::temp_dir().join("iron_coder_test_board_loader");
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        // the manifests are loaded without decoding anything
+        let manifests = board::get_manifests(Path::new("./iron-coder-boards"));
+        assert!(!manifests.is_empty());
+        assert!(manifests.iter().all(|b| b.svg_board_info.is_none() && b.bsp_parse_info.is_none()));
+
+        // decoding fills in the boards, and caches them
+        let mut decoded = manifests.clone();
+        decode_boards(&mut decoded, Some(&cache_dir));
+        assert!(!decoded.is_empty());
+        assert!(decoded.iter().all(|b| b.svg_board_info.is_some()));
+        assert!(std::fs::read_dir(&cache_dir).unwrap().count() >= decoded.len());
+
+        // the second time, the boards come from the cache, the same as before
+        let mut cached = manifests.clone();
+        decode_boards(&mut cached, Some(&cache_dir));
+        assert_eq!(cached.len(), decoded.len());
+        for (a, b) in decoded.iter().zip(cached.iter()) {
+            let (a_info, b_info) = (a.svg_board_info.as_ref().unwrap(), b.svg_board_info.as_ref().unwrap());
+            assert_eq!(a_info.pin_rects, b_info.pin_rects);
+            assert_eq!(a_info.physical_size, b_info.physical_size);
+            assert_eq!(a_info.image.size, b_info.image.size);
+            let bsp = |board: &board::Board| board.bsp_parse_info.as_ref().map(CachedBspParseInfo::from);
+            assert_eq!(bsp(a), bsp(b));
+        }
+
+        // an entry that isn't used while the boards are loaded is pruned once they're done
+        let stale = cache_dir.join("stale");
+        std::fs::write(&stale, "stale").unwrap();
+        let file = std::fs::File::options().write(true).open(&stale).unwrap();
+        file.set_modified(std::time::SystemTime::UNIX_EPOCH).unwrap();
+        drop(file);
+        let entries = std::fs::read_dir(&cache_dir).unwrap().count() - 1;
+        // ...but the entries of project-local libraries are left to their own loaders
+        let local_cache_dir = loader::local_cache_dir(&cache_dir, Path::new("project/boards"));
+        std::fs::create_dir_all(&local_cache_dir).unwrap();
+        let local = local_cache_dir.join("local");
+        std::fs::write(&local, "local").unwrap();
+        let file = std::fs::File::options().write(true).open(&local).unwrap();
+        file.set_modified(std::time::SystemTime::UNIX_EPOCH).unwrap();
+        drop(file);
+        let entries = entries + 1;
+
+        // the background loader merges the boards as they're done
+        let mut boards = manifests.clone();
+        let mut loader = BoardLoader::start(&boards, Some(cache_dir.clone()));
+        while !loader.is_finished() {
+            loader.poll(&mut boards);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let (done, total) = loader.progress();
+        assert_eq!(done, total);
+        assert_eq!(boards.len(), decoded.len());
+        assert!(boards.iter().all(|b| b.svg_board_info.is_some()));
+        assert!(!stale.exists());
+        assert!(local.exists());
+        assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), entries);
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
 }
@@ -0,0 +1,66 @@
+//! This module keeps the GPU textures of board images, so that the art of each board is uploaded
+//! once per zoom level, rather than on every frame. Like the ColorScheme, the cache is kept in the
+//! egui context's memory, so that any display code can draw a board with only the context.
+//!
+//! Textures are keyed by the source of the board's art (see `SvgBoardInfo::source_hash`) and the
+//! zoom bucket (see `svg_reader::zoom_bucket`), so all the instances of a board in a project share
+//! them, while a board that overrides another, or whose SVG has changed, gets textures of its own.
+//! A texture is uploaded the first time it's drawn, and dropped once its board leaves both the
+//! project and the board catalog.
+
+use std::collections::{HashMap, HashSet};
+
+use egui::{TextureHandle, TextureOptions, Vec2};
+
+use crate::board::Board;
+use crate::board::svg_reader::zoom_bucket;
+
+/// The textures of the board images that have been drawn, by art source hash and zoom bucket.
+#[derive(Clone, Default)]
+struct TextureCache {
+    textures: HashMap<(u64, i32), TextureHandle>,
+}
+
+fn cache_id() -> egui::Id {
+    egui::Id::new("board_texture_cache")
+}
+
+/// The texture of a board's image, for showing at a resolution in pixels per mm. The texture is
+/// uploaded the first time it's needed. Returns None if the board doesn't have an image.
+pub fn board_texture(ctx: &egui::Context, board: &Board, pixels_per_mm: f32) -> Option<TextureHandle> {
+    let svg_board_info = board.svg_board_info.as_ref()?;
+    let key = (svg_board_info.source_hash(), zoom_bucket(pixels_per_mm));
+    let cached = ctx.data_mut(|data| {
+        data.get_temp_mut_or_default::<TextureCache>(cache_id()).textures.get(&key).cloned()
+    });
+    if let Some(texture) = cached {
+        return Some(texture);
+    }
+    // the context can't be used from inside of its memory, so the texture is uploaded first
+    let image = svg_board_info.image_at(pixels_per_mm);
+    let texture = ctx.load_texture(format!("board {} at zoom {}", board.library_id(), key.1), image, TextureOptions::LINEAR);
+    ctx.data_mut(|data| {
+        data.get_temp_mut_or_default::<TextureCache>(cache_id()).textures.insert(key, texture.clone());
+    });
+    Some(texture)
+}
+
+/// Show a board's image as large as it fits in max_size, keeping its aspect ratio.
+pub fn show_board_image(ui: &mut egui::Ui, board: &Board, max_size: Vec2) -> Option<egui::Response> {
+    let physical_size = board.svg_board_info.as_ref()?.physical_size;
+    let size = physical_size * (max_size.x / physical_size.x).min(max_size.y / physical_size.y);
+    let texture = board_texture(ui.ctx(), board, size.x / physical_size.x * ui.ctx().pixels_per_point())?;
+    Some(ui.image((texture.id(), size)))
+}
+
+/// Drop the textures of any boards other than these, e.g. the boards of the project and the
+/// catalog. The GPU memory is freed once the textures aren't being drawn any more.
+pub fn retain_boards<'a>(ctx: &egui::Context, boards: impl IntoIterator<Item = &'a Board>) {
+    let sources: HashSet<u64> = boards.into_iter()
+        .filter_map(|board| board.svg_board_info.as_ref().map(|info| info.source_hash()))
+        .collect();
+    ctx.data_mut(|data| {
+        let cache = data.get_temp_mut_or_default::<TextureCache>(cache_id());
+        cache.textures.retain(|(source, _), _| sources.contains(source));
+    });
+}
@@ -0,0 +1,346 @@
+//! This module contains a validation pass over the boards library. Rather than
+//! silently dropping a board whose manifest has a problem, it collects every problem
+//! it can find into a per-board report, with the offending file and line when known.
+
+use log::debug;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::board::Board;
+use crate::board::pinout::{InterfaceType, Pin};
+use crate::board::svg_reader::SvgBoardInfo;
+
+/// How serious a problem is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// The various problems that can be found in a board's manifest or SVG.
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// A file or directory couldn't be read.
+    Filesystem,
+    /// The manifest isn't valid TOML, or doesn't match the Board schema.
+    BadToml,
+    /// A `[[pinout]]` entry names an `iface_type` that Iron Coder doesn't know about.
+    UnknownInterfaceType,
+    /// There is no SVG file next to the manifest.
+    MissingSvg,
+    /// The SVG file exists but couldn't be decoded.
+    BadSvg,
+    /// A `[[pins]]` or `[[pinout]]` pin has no matching path id in the SVG.
+    PinNotInSvg,
+    /// An SVG path id isn't referred to by any `[[pins]]` or `[[pinout]]` entry.
+    UnusedSvgPin,
+    /// A pin function's signal isn't one of the expected pin roles of its interface type.
+    UnknownPinRole,
+}
+
+impl ProblemKind {
+    /// Problems of these kinds keep the board out of the catalog entirely.
+    pub fn prevents_loading(&self) -> bool {
+        match self {
+            ProblemKind::Filesystem |
+            ProblemKind::BadToml |
+            ProblemKind::UnknownInterfaceType |
+            ProblemKind::MissingSvg |
+            ProblemKind::BadSvg => true,
+            ProblemKind::PinNotInSvg |
+            ProblemKind::UnusedSvgPin |
+            ProblemKind::UnknownPinRole => false,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            ProblemKind::UnusedSvgPin |
+            ProblemKind::UnknownPinRole => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+/// A single problem, located in a file and (if known) a 1-based line number.
+#[derive(Clone, Debug)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(kind: ProblemKind, file: &Path, line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            file: file.to_path_buf(),
+            line,
+            message: message.into(),
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        write!(f, ": {}: {}", self.severity(), self.message)
+    }
+}
+
+/// The result of validating a single board manifest.
+#[derive(Clone, Debug)]
+pub struct BoardReport {
+    /// The path to the board's toml manifest
+    pub manifest: PathBuf,
+    /// The board name, if the manifest could be parsed far enough to find it
+    pub board_name: Option<String>,
+    pub problems: Vec<Problem>,
+}
+
+impl BoardReport {
+    /// A human-readable name for the report, falling back to the manifest path.
+    pub fn title(&self) -> String {
+        match &self.board_name {
+            Some(name) => name.clone(),
+            None => self.manifest.display().to_string(),
+        }
+    }
+
+    /// Whether the board will show up in the catalog.
+    pub fn is_loadable(&self) -> bool {
+        !self.problems.iter().any(|p| p.kind.prevents_loading())
+    }
+
+    pub fn num_errors(&self) -> usize {
+        self.problems.iter().filter(|p| p.severity() == Severity::Error).count()
+    }
+
+    pub fn num_warnings(&self) -> usize {
+        self.problems.iter().filter(|p| p.severity() == Severity::Warning).count()
+    }
+}
+
+impl fmt::Display for BoardReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ({} errors, {} warnings{})",
+            self.title(),
+            self.num_errors(),
+            self.num_warnings(),
+            if self.is_loadable() { "" } else { ", not loaded" },
+        )?;
+        for problem in self.problems.iter() {
+            writeln!(f, "  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Just enough of the manifest schema to locate pin names and interface types.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawManifest {
+    name: Option<String>,
+    pins: Vec<Spanned<Pin>>,
+    pinout: Vec<RawInterfaceMapping>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawInterfaceMapping {
+    interface: Option<RawInterface>,
+    pins: Vec<Spanned<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct RawInterface {
+    iface_type: Option<Spanned<String>>,
+}
+
+/// Convert a byte offset into `src` into a 1-based line number.
+fn line_of(src: &str, offset: usize) -> usize {
+    src[..offset.min(src.len())].matches('\n').count() + 1
+}
+
+/// Find the line in an SVG file where the element with the given id is declared.
+fn svg_line_of_id(svg_src: &str, id: &str) -> Option<usize> {
+    svg_src.find(&format!("id=\"{}\"", id)).map(|offset| line_of(svg_src, offset))
+}
+
+/// Validate a single board manifest, along with its SVG image.
+pub fn validate_board(manifest: &Path) -> BoardReport {
+    let mut report = BoardReport {
+        manifest: manifest.to_path_buf(),
+        board_name: None,
+        problems: Vec::new(),
+    };
+    let problems = &mut report.problems;
+
+    let toml_str = match fs::read_to_string(manifest) {
+        Ok(s) => s,
+        Err(e) => {
+            problems.push(Problem::new(ProblemKind::Filesystem, manifest, None, e.to_string()));
+            return report;
+        },
+    };
+
+    // First pass: is this TOML at all, and where are the pins and interface types?
+    let raw: RawManifest = match toml::from_str(&toml_str) {
+        Ok(raw) => raw,
+        Err(e) => {
+            let line = e.span().map(|span| line_of(&toml_str, span.start));
+            problems.push(Problem::new(ProblemKind::BadToml, manifest, line, e.message()));
+            return report;
+        },
+    };
+    report.board_name = raw.name.clone();
+
+    let known_iface_types: Vec<String> = enum_iterator::all::<InterfaceType>()
+        .map(|t| t.to_string())
+        .collect();
+    for mapping in raw.pinout.iter() {
+        if let Some(iface_type) = mapping.interface.as_ref().and_then(|i| i.iface_type.as_ref()) {
+            if !known_iface_types.contains(iface_type.get_ref()) {
+                problems.push(Problem::new(
+                    ProblemKind::UnknownInterfaceType,
+                    manifest,
+                    Some(line_of(&toml_str, iface_type.span().start)),
+                    format!("unknown interface type `{}`, expected one of {}", iface_type.get_ref(), known_iface_types.join(", ")),
+                ));
+            }
+        }
+    }
+
+    for pin in raw.pins.iter() {
+        for function in pin.get_ref().functions.iter() {
+            let roles = function.iface_type.pin_roles();
+            if let Some(signal) = &function.signal {
+                if !roles.contains(&signal.to_ascii_uppercase().as_str()) {
+                    problems.push(Problem::new(
+                        ProblemKind::UnknownPinRole,
+                        manifest,
+                        Some(line_of(&toml_str, pin.span().start)),
+                        format!("pin `{}` has function `{}`, but {} pins are expected to be one of {}", pin.get_ref(), function, function.iface_type, roles.join(", ")),
+                    ));
+                }
+            }
+        }
+    }
+
+    // Second pass: does the manifest match the full Board schema? Skip this if there was
+    // an unknown interface type, since that was already reported more precisely.
+    if !problems.iter().any(|p| p.kind.prevents_loading()) {
+        if let Err(e) = toml::from_str::<Board>(&toml_str) {
+            let line = e.span().map(|span| line_of(&toml_str, span.start));
+            problems.push(Problem::new(ProblemKind::BadToml, manifest, line, e.message()));
+        }
+    }
+
+    // Check the SVG image, and cross-reference its pin ids against the pinout
+    let svg_path = manifest.with_extension("svg");
+    if !svg_path.exists() {
+        problems.push(Problem::new(
+            ProblemKind::MissingSvg,
+            manifest,
+            None,
+            format!("no SVG file found, expected {}", svg_path.display()),
+        ));
+        return report;
+    }
+    // pins can be groups of shapes in the SVG, which are found by the ids the manifest refers to
+    let pin_ids: Vec<String> = raw.pins.iter().map(|pin| pin.get_ref().svg_id.clone())
+        .chain(raw.pinout.iter().flat_map(|mapping| mapping.pins.iter()).map(|name| name.get_ref().clone()))
+        .collect();
+    let svg_board_info = match SvgBoardInfo::from_path_with_pins(&svg_path, &pin_ids) {
+        Ok(info) => info,
+        Err(e) => {
+            problems.push(Problem::new(ProblemKind::BadSvg, &svg_path, None, format!("couldn't decode SVG: {:?}", e)));
+            return report;
+        },
+    };
+    let svg_src = fs::read_to_string(&svg_path).unwrap_or_default();
+
+    let svg_ids: HashSet<&str> = svg_board_info.pin_rects.iter().map(|(id, _)| id.as_str()).collect();
+    let mut referenced_ids: HashSet<&str> = HashSet::new();
+    for pin in raw.pins.iter() {
+        referenced_ids.insert(pin.get_ref().svg_id.as_str());
+        if !svg_ids.contains(pin.get_ref().svg_id.as_str()) {
+            problems.push(Problem::new(
+                ProblemKind::PinNotInSvg,
+                manifest,
+                Some(line_of(&toml_str, pin.span().start)),
+                format!("pin `{}` has svg_id `{}`, which has no matching path id in {}", pin.get_ref(), pin.get_ref().svg_id, svg_path.display()),
+            ));
+        }
+    }
+    for name in raw.pinout.iter().flat_map(|mapping| mapping.pins.iter()) {
+        // a pinout name can refer to a declared pin by any identifier, or directly to an SVG id
+        let svg_id = match raw.pins.iter().find(|pin| pin.get_ref().is_called(name.get_ref())) {
+            Some(pin) => pin.get_ref().svg_id.as_str(),
+            None => name.get_ref().as_str(),
+        };
+        referenced_ids.insert(svg_id);
+        if !svg_ids.contains(svg_id) {
+            problems.push(Problem::new(
+                ProblemKind::PinNotInSvg,
+                manifest,
+                Some(line_of(&toml_str, name.span().start)),
+                format!("pin `{}` isn't declared in [[pins]], and has no matching path id in {}", name.get_ref(), svg_path.display()),
+            ));
+        }
+    }
+    for (id, _) in svg_board_info.pin_rects.iter() {
+        if !referenced_ids.contains(id.as_str()) {
+            problems.push(Problem::new(
+                ProblemKind::UnusedSvgPin,
+                &svg_path,
+                svg_line_of_id(&svg_src, id),
+                format!("path id `{}` isn't referred to by any [[pins]] or [[pinout]] entry", id),
+            ));
+        }
+    }
+
+    debug!("validated board manifest {}: {} problems", manifest.display(), problems.len());
+    report
+}
+
+/// Validate every board manifest in the provided boards directory.
+pub fn validate_boards(boards_dir: &Path) -> Vec<BoardReport> {
+    let (manifests, fs_errors) = super::find_manifests(boards_dir);
+    let mut reports: Vec<BoardReport> = fs_errors.into_iter().map(|(path, e)| {
+        BoardReport {
+            manifest: path.clone(),
+            board_name: None,
+            problems: vec![Problem::new(ProblemKind::Filesystem, &path, None, e.to_string())],
+        }
+    }).collect();
+    reports.extend(manifests.iter().map(|m| validate_board(m)));
+    reports
+}
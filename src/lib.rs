@@ -1,4 +1,6 @@
 mod app;
 mod board;
 mod project;
-pub use app::{IronCoderOptions, IronCoderApp};
\ No newline at end of file
+pub use app::{IronCoderOptions, IronCoderApp};
+pub use board::BoardRoot;
+pub use board::validation::{validate_boards, BoardReport};
@@ -24,6 +24,23 @@ fn main() -> eframe::Result<()> {
 
     info!("Running Iron Coder with options:\n{:?}", app_options);
 
+    // Validate the board libraries and exit without starting the GUI.
+    if app_options.validate_boards {
+        let mut num_manifests = 0;
+        let mut num_errors = 0;
+        for root in app_options.board_roots().iter().filter(|root| root.path.is_dir()) {
+            println!("Library: {}", root);
+            let reports = iron_coder::validate_boards(&root.path);
+            for report in reports.iter() {
+                print!("{}", report);
+            }
+            num_manifests += reports.len();
+            num_errors += reports.iter().map(|r| r.num_errors()).sum::<usize>();
+        }
+        println!("validated {} board manifests, found {} errors.", num_manifests, num_errors);
+        std::process::exit(if num_errors > 0 { 1 } else { 0 });
+    }
+
     let native_options = eframe::NativeOptions::default();
     eframe::run_native(
         "Iron Coder",
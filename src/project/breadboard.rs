@@ -0,0 +1,286 @@
+//! Title: Iron Coder Project Module - Breadboard
+//! Description: This module lays out a system on a virtual breadboard, as a second view of the
+//! same System as the system editor diagram. Boards are plugged in one after another along the
+//! breadboard, and each connection becomes jumper wires between the rows of its pins.
+//!
+//! The breadboard is drawn on its side, with its rows numbered from the left, and its columns
+//! "a" to "e" above the center channel and "f" to "j" below it. The five holes of a column half
+//! in the same row are connected, so a jumper plugs into a free hole next to the pin it joins.
+
+use egui::Color32;
+
+use crate::board::Board;
+use crate::board::pinout::{InterfaceType, Pin};
+use super::system::{Connection, System};
+
+/// The distance between neighbouring holes, in mm
+pub const PITCH: f32 = 2.54;
+/// The number of columns, "a" to "j"
+pub const COLUMNS: u8 = 10;
+/// The fewest rows that a breadboard has
+const MIN_ROWS: u32 = 30;
+/// The number of empty rows between boards, and at the ends of the breadboard
+const GAP_ROWS: u32 = 2;
+/// The colors of jumper wires that aren't power or ground, which nets take in turn
+const JUMPER_COLORS: [Color32; 8] = [
+    Color32::from_rgb(0xff, 0x8c, 0x00),
+    Color32::from_rgb(0xf0, 0xd0, 0x20),
+    Color32::from_rgb(0x2e, 0xa0, 0x43),
+    Color32::from_rgb(0x1e, 0x6f, 0xd9),
+    Color32::from_rgb(0x8e, 0x44, 0xad),
+    Color32::from_rgb(0xee, 0xee, 0xee),
+    Color32::from_rgb(0x8b, 0x5a, 0x2b),
+    Color32::from_rgb(0x20, 0xc0, 0xc0),
+];
+
+/// A hole of the breadboard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Hole {
+    /// The row, counting from 1
+    pub row: u32,
+    /// The column, where 0 is "a" and 9 is "j"
+    pub column: u8,
+}
+
+impl Hole {
+    /// The name printed on the breadboard, e.g. "c12"
+    pub fn name(&self) -> String {
+        format!("{}{}", (b'a' + self.column) as char, self.row)
+    }
+
+    /// Whether the hole is above the center channel.
+    pub fn is_top(&self) -> bool {
+        self.column < COLUMNS / 2
+    }
+
+    /// Where the hole is, in mm from the first hole of column "a". The center channel is as
+    /// wide as two rows of holes.
+    pub fn position(&self) -> egui::Pos2 {
+        let y = if self.is_top() { self.column } else { self.column + 2 };
+        egui::pos2((self.row - 1) as f32 * PITCH, y as f32 * PITCH)
+    }
+}
+
+/// Where a board is plugged into the breadboard.
+#[derive(Clone, Debug)]
+pub struct Footprint {
+    pub board: Board,
+    /// The boards stacked on this one, which share its pins
+    pub stacked: Vec<Board>,
+    /// The holes that the board covers, from the top-left to the bottom-right
+    pub min: Hole,
+    pub max: Hole,
+    /// The pins of the board and the holes they're plugged into
+    pub pins: Vec<(Pin, Hole)>,
+}
+
+/// One jumper wire of a connection, between the rows of two of its pins.
+#[derive(Clone, Debug)]
+pub struct Jumper {
+    /// The index of the connection in the system
+    pub connection: usize,
+    pub from: JumperEnd,
+    pub to: JumperEnd,
+    pub color: Color32,
+}
+
+/// An end of a jumper, which plugs in next to a pin.
+#[derive(Clone, Debug)]
+pub struct JumperEnd {
+    pub board: Board,
+    pub pin: Pin,
+    /// The hole the jumper plugs into
+    pub hole: Hole,
+}
+
+impl JumperEnd {
+    /// Describe where the jumper lands, e.g. "feather1 SCL (J2-11) at a14"
+    pub fn describe(&self) -> String {
+        match &self.pin.header_position {
+            Some(header_position) => format!("{} {} ({}) at {}", self.board.get_designator(), self.pin.name(), header_position, self.hole.name()),
+            None => format!("{} {} at {}", self.board.get_designator(), self.pin.name(), self.hole.name()),
+        }
+    }
+
+    /// The short label drawn at the end of the jumper; the header position of the pin if it's
+    /// known, and otherwise the pin's name.
+    pub fn label(&self) -> &str {
+        self.pin.header_position.as_deref().unwrap_or(self.pin.name())
+    }
+}
+
+/// A system laid out on a breadboard.
+#[derive(Clone, Debug, Default)]
+pub struct Layout {
+    /// The number of rows of the breadboard
+    pub rows: u32,
+    pub footprints: Vec<Footprint>,
+    pub jumpers: Vec<Jumper>,
+}
+
+impl Layout {
+    /// The hole that a pin of a board is plugged into, if the board is on the breadboard. Pins
+    /// of stacked boards are plugged in through the board they're stacked on.
+    pub fn hole_of(&self, board: &Board, svg_id: &str) -> Option<Hole> {
+        self.footprints.iter().find_map(|f| {
+            if f.board == *board {
+                f.pins.iter().find(|(pin, _)| pin.svg_id == svg_id).map(|(_, hole)| *hole)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Lay out the system on a breadboard. Boards are plugged in in the order of the system, and
+/// boards stacked on another board are plugged in along with it. The pins of each connection
+/// are joined by a chain of jumpers, in the order of its endpoints.
+pub fn layout(system: &System) -> Layout {
+    let mut layout = Layout::default();
+    let mut next_row = 1 + GAP_ROWS;
+    let boards = system.get_all_boards();
+    for board in boards.iter().filter(|b| system.stack_base(b).is_none()) {
+        let used: Vec<Pin> = system.connections.iter()
+            .flat_map(|c| c.endpoints.iter())
+            .filter(|e| e.board == *board)
+            .map(|e| e.pin.clone())
+            .collect();
+        let footprint = footprint(board, &used, next_row);
+        next_row = footprint.max.row + 1 + GAP_ROWS;
+        layout.footprints.push(Footprint {
+            stacked: boards.iter().filter(|b| system.stack_base(b).as_ref() == Some(board)).cloned().collect(),
+            ..footprint
+        });
+    }
+    layout.rows = (next_row - 1).max(MIN_ROWS);
+
+    // each jumper plugs into the free hole of its pin's row that's furthest from the board
+    let mut taken: Vec<Hole> = layout.footprints.iter().flat_map(|f| f.pins.iter().map(|(_, hole)| *hole)).collect();
+    for (idx, connection) in system.connections.iter().enumerate() {
+        let color = jumper_color(connection, idx);
+        // pins in the same row and half of the breadboard are already connected, e.g. the pins
+        // of a stacked board, so a net needs only one jumper end in each of them
+        let mut ends: Vec<(JumperEnd, Hole)> = Vec::new();
+        for endpoint in connection.endpoints.iter() {
+            let Some((pin_hole, base)) = pin_hole(system, &layout, &endpoint.board, &endpoint.pin) else {
+                continue;
+            };
+            if ends.iter().any(|(_, other)| same_strip(*other, pin_hole)) {
+                continue;
+            }
+            let footprint = layout.footprints.iter().find(|f| f.board == base);
+            let hole = footprint.and_then(|f| free_hole(pin_hole, f, &taken)).unwrap_or(pin_hole);
+            taken.push(hole);
+            ends.push((JumperEnd { board: endpoint.board.clone(), pin: endpoint.pin.clone(), hole }, pin_hole));
+        }
+        for pair in ends.windows(2) {
+            layout.jumpers.push(Jumper { connection: idx, from: pair[0].0.clone(), to: pair[1].0.clone(), color });
+        }
+    }
+    layout
+}
+
+/// Where a board is plugged in, starting at the given row. A board whose pins have header
+/// positions in its standard straddles the center channel, with its headers lined up with the
+/// rows. Other boards are plugged in like a breakout with a single row of header pins, made of
+/// the pins of its pinout and any other pins that are connected.
+fn footprint(board: &Board, used: &[Pin], first_row: u32) -> Footprint {
+    if let Some(footprint) = header_footprint(board, first_row) {
+        return footprint;
+    }
+    let mut pins = board.pinout_pins();
+    for pin in used {
+        if !pins.iter().any(|p| p.svg_id == pin.svg_id) {
+            pins.push(pin.clone());
+        }
+    }
+    let column = COLUMNS / 2 - 1;
+    let pins: Vec<(Pin, Hole)> = pins.into_iter().enumerate()
+        .map(|(idx, pin)| (pin, Hole { row: first_row + idx as u32, column }))
+        .collect();
+    let last_row = first_row + (pins.len() as u32).max(1) - 1;
+    Footprint {
+        board: board.clone(),
+        stacked: Vec::new(),
+        min: Hole { row: first_row, column },
+        max: Hole { row: last_row, column },
+        pins,
+    }
+}
+
+/// Where a board is plugged in by its standard's headers, if it has pins at header positions
+/// and its headers fit across the center channel. The board is placed so that it leaves as
+/// many free holes on either side as it can.
+fn header_footprint(board: &Board, first_row: u32) -> Option<Footprint> {
+    let geometry = board.get_standard_geometry()?;
+    // the header pins, as (pin, distance along the rows, distance across the columns) in holes
+    let header_pins: Vec<(Pin, i32, i32)> = board.get_pins().iter().filter_map(|pin| {
+        let offset = geometry.header_offset(pin.header_position.as_deref()?)?;
+        Some((pin.clone(), (offset.y / PITCH).round() as i32, (offset.x / PITCH).round() as i32))
+    }).collect();
+    let min_along = header_pins.iter().map(|(_, along, _)| *along).min()?;
+    let min_across = header_pins.iter().map(|(_, _, across)| *across).min()?;
+    let max_across = header_pins.iter().map(|(_, _, across)| *across).max()?;
+    // holes are two further apart across the center channel
+    let span = max_across - min_across - 2;
+    let half = (COLUMNS / 2) as i32;
+    if span < 1 || span > COLUMNS as i32 - 1 {
+        return None;
+    }
+    // the first header's column above the channel, leaving free holes on both sides
+    let top = (0..half).filter(|top| (half..COLUMNS as i32).contains(&(top + span)))
+        .max_by_key(|top| (*top).min(COLUMNS as i32 - 1 - (top + span)))?;
+    let pins: Vec<(Pin, Hole)> = header_pins.into_iter().map(|(pin, along, across)| {
+        let column = if across == min_across { top } else { top + span };
+        (pin, Hole { row: first_row + (along - min_along) as u32, column: column as u8 })
+    }).collect();
+    let last_row = pins.iter().map(|(_, hole)| hole.row).max()?;
+    Some(Footprint {
+        board: board.clone(),
+        stacked: Vec::new(),
+        min: Hole { row: first_row, column: top as u8 },
+        max: Hole { row: last_row, column: (top + span) as u8 },
+        pins,
+    })
+}
+
+/// The hole that a pin is plugged into, along with the board on the breadboard that it's on. A
+/// pin of a stacked board is plugged in through the pin of the base at the same header position.
+fn pin_hole(system: &System, layout: &Layout, board: &Board, pin: &Pin) -> Option<(Hole, Board)> {
+    let base = system.stack_root(board);
+    let base_pin = match base == *board {
+        true => pin.clone(),
+        false => base.get_pins().iter().find(|p| p.header_position.is_some() && p.header_position == pin.header_position)?.clone(),
+    };
+    Some((layout.hole_of(&base, &base_pin.svg_id)?, base))
+}
+
+/// Whether two holes are in the same row and half of the breadboard, so they're connected.
+pub fn same_strip(a: Hole, b: Hole) -> bool {
+    a.row == b.row && a.is_top() == b.is_top()
+}
+
+/// A free hole in the same row and half as a pin, outside of the board, furthest from it first.
+fn free_hole(pin_hole: Hole, footprint: &Footprint, taken: &[Hole]) -> Option<Hole> {
+    let columns: Vec<u8> = match pin_hole.is_top() {
+        true => (0..pin_hole.column).filter(|c| *c < footprint.min.column).collect(),
+        false => (pin_hole.column + 1..COLUMNS).rev().filter(|c| *c > footprint.max.column).collect(),
+    };
+    columns.into_iter()
+        .map(|column| Hole { row: pin_hole.row, column })
+        .find(|hole| !taken.contains(hole))
+}
+
+/// The color of a connection's jumpers. Power is red and ground is black, like on a real
+/// breadboard, and other nets take turns with the rest of the colors, unless the connection
+/// has a color of its own.
+pub fn jumper_color(connection: &Connection, idx: usize) -> Color32 {
+    if let Some(color) = connection.color {
+        return color;
+    }
+    match connection.interface_mapping.interface.iface_type {
+        InterfaceType::POWER => Color32::from_rgb(0xd0, 0x20, 0x20),
+        InterfaceType::GND => Color32::from_rgb(0x20, 0x20, 0x20),
+        _ => JUMPER_COLORS[idx % JUMPER_COLORS.len()],
+    }
+}
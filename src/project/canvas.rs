@@ -0,0 +1,179 @@
+//! Title: Iron Coder Project Module - Canvas
+//! Description: This module contains the coordinate transform of the system editor canvas,
+//! which is measured in millimeters (the same units as a board's physical size), along with
+//! the grid, snapping, "fit all" and minimap that are drawn on it.
+
+use egui::{Color32, Pos2, Rect, Stroke, Vec2};
+
+use crate::board::Board;
+use super::system::{System, Viewport};
+
+/// The limits of the zoom factor, in screen points per millimeter.
+pub const MIN_ZOOM: f32 = 0.5;
+pub const MAX_ZOOM: f32 = 40.0;
+
+/// Boards snap to a grid with this spacing, in millimeters.
+pub const SNAP_SPACING: f32 = 1.0;
+
+/// The size of the minimap, in screen points.
+const MINIMAP_SIZE: Vec2 = Vec2::new(200.0, 140.0);
+
+/// The transform between canvas coordinates (in millimeters) and screen coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct Canvas {
+    /// The part of the screen that the canvas is drawn in
+    pub rect: Rect,
+    /// The screen position of the canvas origin
+    origin: Pos2,
+    /// The number of screen points per millimeter
+    zoom: f32,
+}
+
+impl Canvas {
+    pub fn new(rect: Rect, viewport: &Viewport) -> Self {
+        Self {
+            rect,
+            origin: rect.min + egui::vec2(viewport.pan_x, viewport.pan_y),
+            zoom: viewport.zoom,
+        }
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Convert a point on the canvas to a point on the screen.
+    pub fn to_screen(self, p: Pos2) -> Pos2 {
+        self.origin + p.to_vec2() * self.zoom
+    }
+
+    /// Convert a point on the screen to a point on the canvas.
+    pub fn to_canvas(self, p: Pos2) -> Pos2 {
+        ((p - self.origin) / self.zoom).to_pos2()
+    }
+
+    /// The part of the canvas that's visible on the screen.
+    pub fn visible_area(&self) -> Rect {
+        Rect::from_min_max(self.to_canvas(self.rect.min), self.to_canvas(self.rect.max))
+    }
+}
+
+/// Move the view of the canvas by the given amount, in screen points.
+pub fn pan(viewport: &mut Viewport, delta: Vec2) {
+    viewport.pan_x += delta.x;
+    viewport.pan_y += delta.y;
+}
+
+/// Zoom the view of the canvas by the given factor, keeping the canvas point that's under the
+/// cursor in place.
+pub fn zoom_around(viewport: &mut Viewport, rect: Rect, cursor: Pos2, factor: f32) {
+    let anchor = Canvas::new(rect, viewport).to_canvas(cursor);
+    viewport.zoom = (viewport.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+    let pan = cursor - rect.min - anchor.to_vec2() * viewport.zoom;
+    viewport.pan_x = pan.x;
+    viewport.pan_y = pan.y;
+}
+
+/// Center the view on the given point of the canvas, without changing the zoom.
+pub fn center_on(viewport: &mut Viewport, rect: Rect, p: Pos2) {
+    let pan = rect.size() / 2.0 - p.to_vec2() * viewport.zoom;
+    viewport.pan_x = pan.x;
+    viewport.pan_y = pan.y;
+}
+
+/// Zoom and pan the view so that the given area of the canvas fills the screen, with a margin.
+pub fn fit(viewport: &mut Viewport, rect: Rect, area: Rect) {
+    if area.width() <= 0.0 || area.height() <= 0.0 {
+        return;
+    }
+    let zoom = (rect.width() / area.width()).min(rect.height() / area.height()) * 0.9;
+    viewport.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    center_on(viewport, rect, area.center());
+}
+
+/// Round a canvas coordinate to the nearest grid line.
+pub fn snap(v: f32) -> f32 {
+    (v / SNAP_SPACING).round() * SNAP_SPACING
+}
+
+/// Where the center of a pin of a board ends up on the canvas, if the board has an image.
+pub fn pin_location(system: &System, board: &Board, svg_id: &str) -> Option<Pos2> {
+    let svg_board_info = board.svg_board_info.as_ref()?;
+    let (_, pin_rect) = svg_board_info.pin_rects.iter().find(|(id, _)| id == svg_id)?;
+    let placement = system.get_placement(board);
+    let p = placement.place_point(svg_board_info.physical_size, pin_rect.center());
+    Some(egui::pos2(placement.x, placement.y) + p.to_vec2())
+}
+
+/// The area of the canvas covered by each board of the system that has an image.
+pub fn board_bounds(system: &System) -> Vec<(Board, Rect)> {
+    system.get_all_boards().into_iter().filter_map(|board| {
+        let size = board.svg_board_info.as_ref()?.physical_size;
+        let placement = system.get_placement(&board);
+        let min = egui::pos2(placement.x, placement.y);
+        let rect = Rect::from_min_size(min, placement.placed_size(size));
+        Some((board, rect))
+    }).collect()
+}
+
+/// The smallest area of the canvas that covers all of the boards, if there are any.
+pub fn bounds_of(bounds: &[(Board, Rect)]) -> Option<Rect> {
+    bounds.iter().map(|(_, rect)| *rect).reduce(|a, b| a.union(b))
+}
+
+/// Draw a millimeter grid over the visible part of the canvas. Lines that would be too close
+/// together at the current zoom are left out, and every tenth line is drawn more strongly.
+pub fn draw_grid(painter: &egui::Painter, canvas: &Canvas, color: Color32) {
+    let Some(spacing) = [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0].into_iter().find(|s| s * canvas.zoom >= 8.0) else {
+        return;
+    };
+    let minor = Stroke::new(1.0, color.gamma_multiply(0.3));
+    let major = Stroke::new(1.0, color.gamma_multiply(0.7));
+    let area = canvas.visible_area();
+    let stroke_of = |v: f32| if (v / (spacing * 10.0)).round() * spacing * 10.0 == v { major } else { minor };
+    let mut x = (area.min.x / spacing).floor() * spacing;
+    while x <= area.max.x {
+        let top = canvas.to_screen(egui::pos2(x, area.min.y));
+        let bottom = canvas.to_screen(egui::pos2(x, area.max.y));
+        painter.line_segment([top, bottom], stroke_of(x));
+        x += spacing;
+    }
+    let mut y = (area.min.y / spacing).floor() * spacing;
+    while y <= area.max.y {
+        let left = canvas.to_screen(egui::pos2(area.min.x, y));
+        let right = canvas.to_screen(egui::pos2(area.max.x, y));
+        painter.line_segment([left, right], stroke_of(y));
+        y += spacing;
+    }
+}
+
+/// Show a minimap of the boards and the visible area in the bottom-right corner of the canvas.
+/// Returns the canvas point that was clicked or dragged to, if any.
+pub fn show_minimap(ctx: &egui::Context, canvas: &Canvas, bounds: &[(Board, Rect)]) -> Option<Pos2> {
+    let visible = canvas.visible_area();
+    let world = bounds_of(bounds)?.union(visible);
+    let mut target = None;
+    egui::Area::new(egui::Id::new("system_editor_minimap"))
+        .pivot(egui::Align2::RIGHT_BOTTOM)
+        .fixed_pos(canvas.rect.right_bottom() - egui::vec2(8.0, 8.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).inner_margin(0.0).show(ui, |ui| {
+                let (rect, response) = ui.allocate_exact_size(MINIMAP_SIZE, egui::Sense::click_and_drag());
+                let scale = (rect.width() / world.width()).min(rect.height() / world.height());
+                let offset = rect.center() - world.center().to_vec2() * scale;
+                let to_map = |r: Rect| Rect::from_min_max(offset + r.min.to_vec2() * scale, offset + r.max.to_vec2() * scale);
+                let painter = ui.painter_at(rect);
+                for (_, board_rect) in bounds.iter() {
+                    painter.rect_filled(to_map(*board_rect), 1.0, ui.visuals().widgets.inactive.bg_fill);
+                }
+                painter.rect_stroke(to_map(visible), 0.0, Stroke::new(1.0, ui.visuals().selection.stroke.color));
+                if response.clicked() || response.dragged() {
+                    if let Some(p) = response.interact_pointer_pos() {
+                        target = Some(((p - offset) / scale).to_pos2());
+                    }
+                }
+            });
+        });
+    target
+}
@@ -4,8 +4,8 @@
 //! helper functions for drawing connections between pins on
 //! the system editor.
 
-use egui::{Key, Response};
-use egui_extras::RetainedImage;
+use egui::{Key, KeyboardShortcut, Modifiers, Response};
+use egui_extras::{TableBuilder, Column as TableColumn};
 use log::{info, warn};
 use std::collections::HashMap;
 use std::path::Path;
@@ -17,15 +17,24 @@ use egui::widgets::Button;
 use git2::{Repository, StatusOptions};
 
 use crate::board;
+use crate::board::validation::Severity;
 use crate::project::Project;
 use crate::app::icons::IconSet;
 use crate::app::{Mode, Warnings, Git};
+use crate::app::colorscheme::{self, ColorScheme};
 
 use enum_iterator;
 
 use serde::{Serialize, Deserialize};
 
 use super::system;
+use super::canvas::{self, Canvas};
+use super::erc;
+use super::migration;
+use super::routing;
+use super::table;
+use super::picker;
+use super::breadboard;
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub enum ProjectViewType {
@@ -381,57 +390,260 @@ impl Project {
 
     }
 
+    /// If the project file was upgraded when it was loaded, explain what changed in a window.
+    pub fn display_migration_report(&mut self, ctx: &egui::Context) {
+        let Some(report) = &self.migration_report else { return };
+        let mut open = true;
+        let mut dismissed = false;
+        egui::Window::new("Project Upgraded")
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .movable(true)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "This project was saved by an older version of Iron Coder (project file version {}), \
+                and has been upgraded to version {}.",
+                report.from_version, migration::SCHEMA_VERSION,
+            ));
+            match &report.backup {
+                Some(backup) => ui.label(format!("The original project file was backed up to {}.", backup.display())),
+                None => ui.label(RichText::new("The original project file couldn't be backed up.").color(ui.visuals().warn_fg_color)),
+            };
+            ui.separator();
+            for change in report.changes.iter() {
+                ui.label(format!("\u{2022} {}", change));
+            }
+            ui.separator();
+            ui.label("The upgraded project file will be written the next time the project is saved.");
+            if ui.button("OK").clicked() {
+                dismissed = true;
+            }
+        });
+        if !open || dismissed {
+            self.migration_report = None;
+        }
+    }
+
     /// Show the boards in egui "Area"s so we can move them around!
     pub fn display_system_editor_boards(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
 
+        // the canvas takes the keyboard when it's clicked, and keeps tab and the arrow keys for
+        // itself. Keyboard commands work while it has the keyboard, or nothing else does.
+        let canvas_id = ui.id().with("system_editor_canvas");
+        if ctx.memory(|m| m.has_focus(canvas_id)) {
+            let filter = egui::EventFilter { tab: true, horizontal_arrows: true, vertical_arrows: true, escape: false };
+            ctx.memory_mut(|m| m.set_focus_lock_filter(canvas_id, filter));
+        }
+        let canvas_keyboard = !ctx.wants_keyboard_input() || ctx.memory(|m| m.has_focus(canvas_id));
+
+        // undo or redo an edit of the system
+        let redo_shortcut = KeyboardShortcut::new(Modifiers::CTRL | Modifiers::SHIFT, Key::Z);
+        let undo_shortcut = KeyboardShortcut::new(Modifiers::CTRL, Key::Z);
+        if canvas_keyboard {
+            if ctx.input_mut(|i| i.consume_shortcut(&redo_shortcut)) {
+                self.redo();
+            } else if ctx.input_mut(|i| i.consume_shortcut(&undo_shortcut)) {
+                self.undo();
+            }
+        }
+
+        // any changes made to the system below are recorded as a single command in the undo
+        // history, described by `edit` along with whether it continues an ongoing change
+        let mut edit: Option<(String, bool)> = None;
+
+        // cut, copy, paste, duplicate and delete the selection, cycle through the boards with tab,
+        // nudge the selected boards with the arrow keys, and pick pins to connect with ctrl+k
+        self.selection.retain(&self.system);
+        if canvas_keyboard {
+            for event in ctx.input(|i| i.events.clone()) {
+                match event {
+                    egui::Event::Copy | egui::Event::Cut => {
+                        if let Some(text) = self.copy_selection() {
+                            ctx.output_mut(|o| o.copied_text = text);
+                            if event == egui::Event::Cut {
+                                self.delete_selection();
+                                edit = Some(("cut".to_string(), false));
+                            }
+                        }
+                    },
+                    egui::Event::Paste(text) if self.paste(&text) => {
+                        edit = Some(("paste".to_string(), false));
+                    },
+                    _ => (),
+                }
+            }
+            if ctx.input_mut(|i| i.consume_shortcut(&KeyboardShortcut::new(Modifiers::CTRL, Key::D))) {
+                self.duplicate_selection();
+                edit = Some(("duplicate".to_string(), false));
+            }
+            if ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Delete) || i.consume_key(Modifiers::NONE, Key::Backspace)) {
+                self.delete_selection();
+                edit = Some(("delete".to_string(), false));
+            }
+            if ctx.input_mut(|i| i.consume_shortcut(&KeyboardShortcut::new(Modifiers::CTRL, Key::K))) {
+                open_pin_picker(ctx);
+            }
+            let backwards = ctx.input_mut(|i| i.consume_key(Modifiers::SHIFT, Key::Tab));
+            if backwards || ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Tab)) {
+                self.selection.select_next_board(&self.system, backwards);
+                ctx.memory_mut(|m| m.request_focus(canvas_id));
+            }
+            if let Some((nudge, held)) = nudge_from_keys(ctx).filter(|_| self.selection.num_boards() > 0) {
+                // each press of an arrow key can be undone on its own, while holding it down
+                // nudges the boards as a single edit
+                if !held {
+                    self.history.close();
+                }
+                for board in self.selection.stack_roots(&self.system) {
+                    let mut placement = self.system.get_placement(&board);
+                    placement.x += nudge.x;
+                    placement.y += nudge.y;
+                    self.system.set_placement(&board, placement);
+                }
+                edit = Some((format!("nudge {} boards", self.selection.num_boards()), true));
+            }
+        }
+
+        // list the results of the electrical rule check, if there are any
+        let violations = erc::check(&self.system);
+        if !violations.is_empty() {
+            egui::SidePanel::right("erc_panel").show_inside(ui, |ui| {
+                self.display_erc_panel(ui, &violations);
+            });
+        }
+
+        // the breadboard view of the system sits beside the diagram
+        if self.system.viewport.show_breadboard {
+            egui::SidePanel::right("breadboard_panel").resizable(true).default_width(480.0).show_inside(ui, |ui| {
+                if let Some(breadboard_edit) = self.display_breadboard(ctx, ui) {
+                    edit = Some(breadboard_edit);
+                }
+            });
+        }
+
+        // the table of connections is edited alongside the diagram
+        if self.system.viewport.show_connection_table {
+            egui::TopBottomPanel::bottom("connection_table_panel").resizable(true).show_inside(ui, |ui| {
+                if let Some(table_edit) = self.display_connection_table(ctx, ui) {
+                    edit = Some(table_edit);
+                }
+            });
+        }
+
+        // pin locations are keyed by board and the pin's SVG id
         let mut pin_locations: HashMap<(board::Board, String), egui::Pos2> = HashMap::new();
 
+        // pan and zoom the canvas, then draw its grid. Board placements are in mm on the canvas.
+        let canvas_rect = ui.available_rect_before_wrap();
+        let panning = self.update_canvas_view(ctx, canvas_rect);
+        let viewport = self.system.viewport;
+        let canvas = Canvas::new(canvas_rect, &viewport);
+        let scale = canvas.zoom();
+        canvas::draw_grid(&ui.painter_at(canvas_rect), &canvas, ui.visuals().weak_text_color());
+        let colorscheme = colorscheme::get_colorscheme(ctx);
+
+        // dragging across the background draws a rubber band, which selects the boards and
+        // connections inside it once the pin locations are known below. The start of the band
+        // is kept in canvas coordinates, so it stays put if the view zooms.
+        let background = ui.interact(canvas_rect, canvas_id, egui::Sense::click_and_drag());
+        if background.clicked() || background.drag_started() {
+            ctx.memory_mut(|m| m.request_focus(canvas_id));
+        }
+        let band_id = egui::Id::new("selection_band_start");
+        if background.drag_started_by(egui::PointerButton::Primary) && !panning {
+            if let Some(p) = background.interact_pointer_pos() {
+                ctx.data_mut(|data| data.insert_temp(band_id, canvas.to_canvas(p)));
+            }
+        }
+        let band = ctx.data(|data| data.get_temp::<egui::Pos2>(band_id))
+            .zip(ctx.pointer_latest_pos())
+            .map(|(start, end)| egui::Rect::from_two_pos(canvas.to_screen(start), end));
+        if let Some(band) = band {
+            let selection_stroke = ui.visuals().selection.stroke;
+            ui.painter_at(canvas_rect).rect(band, 0.0, selection_stroke.color.gamma_multiply(0.1), selection_stroke);
+        }
+        let shift = ctx.input(|i| i.modifiers.shift);
+        let mut clicked_item = false;
+
         // iterate through the system boards and draw them on the screen
         for board in self.system.get_all_boards().iter_mut() {
 
-            let scale_id = egui::Id::new("system_editor_scale_factor");
-            // set the editor scale factor in memory:
-            let scale = ctx.data_mut(|data| {
-                data.get_temp_mut_or(scale_id, 5.0).clone()
-            });
+            let mut placement = self.system.get_placement(board);
+            let board_pos = canvas.to_screen(egui::pos2(placement.x, placement.y));
+            let selected = self.selection.contains_board(board);
 
             // Get the response of the board/pin Ui
-            let board_id = egui::Id::new(board.get_name());
-            let response = egui::Area::new(board_id).show(ctx, |ui| {
+            let board_id = egui::Id::new(("system_editor_board", &*board));
+            let area = egui::Area::new(board_id).current_pos(board_pos).sense(egui::Sense::click_and_drag());
+            let response = area.show(ctx, |ui| {
 
-                let mut pin_clicked: Option<String> = None;
+                let mut pin_clicked: Option<board::pinout::Pin> = None;
 
-                if let Some(svg_board_info) = board.clone().svg_board_info {
-                    let retained_image = RetainedImage::from_color_image(
-                        "pic",
-                        svg_board_info.image,
-                    );
+                // the board art is rasterized at the resolution it's shown at, so it stays sharp when zoomed in
+                let texture = board::textures::board_texture(ctx, board, scale * ctx.pixels_per_point());
+                if let (Some(svg_board_info), Some(texture)) = (board.svg_board_info.as_ref(), texture) {
 
                     let display_size = svg_board_info.physical_size * scale;
 
-                    let image_rect = retained_image.show_max_size(ui, display_size).rect;
+                    // draw the image as a textured rectangle, so it can be rotated and flipped
+                    let (image_rect, _) = ui.allocate_exact_size(placement.placed_size(display_size), egui::Sense::hover());
+                    let mut mesh = egui::Mesh::with_texture(texture.id());
+                    for uv in [egui::pos2(0.0, 0.0), egui::pos2(1.0, 0.0), egui::pos2(1.0, 1.0), egui::pos2(0.0, 1.0)] {
+                        let corner = egui::pos2(uv.x * display_size.x, uv.y * display_size.y);
+                        mesh.vertices.push(egui::epaint::Vertex {
+                            pos: image_rect.left_top() + placement.place_point(display_size, corner).to_vec2(),
+                            uv,
+                            color: egui::Color32::WHITE,
+                        });
+                    }
+                    mesh.add_triangle(0, 1, 2);
+                    mesh.add_triangle(0, 2, 3);
+                    ui.painter().add(mesh);
+                    ui.painter().text(
+                        image_rect.left_top(),
+                        egui::Align2::LEFT_TOP,
+                        board.get_designator(),
+                        egui::FontId::proportional(14.0),
+                        ui.visuals().strong_text_color(),
+                    );
+                    if selected {
+                        ui.painter().rect_stroke(image_rect.expand(2.0), 2.0, egui::Stroke::new(2.0, ui.visuals().selection.stroke.color));
+                    }
 
-                    // iterate through the pin_nodes of the board, and check if their rects (properly scaled and translated)
+                    // iterate through the pin_nodes of the board, and check if their outlines (properly scaled and translated)
                     // contain the pointer. If so, actually draw the stuff there.
-                    for (pin_name, mut pin_rect) in board.clone().svg_board_info.unwrap().pin_rects {
-                        // scale the rects the same amount that the board image was scaled
-                        pin_rect.min.x *= scale;
-                        pin_rect.min.y *= scale;
-                        pin_rect.max.x *= scale;
-                        pin_rect.max.y *= scale;
-                        // translate the rects so they are in absolute coordinates
-                        pin_rect = pin_rect.translate(image_rect.left_top().to_vec2());
-                        pin_locations.insert((board.clone(), pin_name.clone()), pin_rect.center());
-
-                        // render the pin overlay, and check for clicks/hovers
+                    for (svg_id, mut pin_rect) in svg_board_info.pin_rects.iter().cloned() {
+                        let pin = board.get_pin_by_svg_id(&svg_id);
+                        // scale, rotate and flip the pin along with the board image, and translate it
+                        // so it's in absolute coordinates
+                        let to_screen = |p: egui::Pos2| image_rect.left_top() + placement.place_point(display_size, (p.to_vec2() * scale).to_pos2()).to_vec2();
+                        pin_rect = egui::Rect::from_two_pos(to_screen(pin_rect.min), to_screen(pin_rect.max));
+                        let outline = svg_board_info.pin_outline(&svg_id).map(|outline| outline.map(to_screen));
+                        pin_locations.insert((board.clone(), svg_id.clone()), pin_rect.center());
+
+                        // render the pin overlay, and check for clicks/hovers. The pin's rect is only
+                        // its bounding box, so the pointer has to be over the pad itself.
                         let r = ui.allocate_rect(pin_rect, egui::Sense::click());
-                        if r.clicked() {
-                            pin_clicked = Some(pin_name.clone());
+                        let over_pin = match (&outline, r.hover_pos()) {
+                            (Some(outline), Some(pos)) => outline.contains(pos),
+                            (None, _) => r.hovered(),
+                            _ => false,
+                        };
+                        let clicked = r.clicked() && over_pin;
+                        if clicked {
+                            pin_clicked = Some(pin.clone());
                         }
-                        if r.hovered() {
-                            ui.painter().circle_filled(r.rect.center(), r.rect.height()/2.0, egui::Color32::GREEN);
+                        if over_pin {
+                            match &outline {
+                                // outlines can be concave, which a filled polygon can't be drawn as
+                                Some(outline) => for polygon in outline.polygons.iter() {
+                                    ui.painter().add(egui::Shape::closed_line(polygon.clone(), egui::Stroke::new(2.0, egui::Color32::GREEN)));
+                                },
+                                None => { ui.painter().circle_filled(r.rect.center(), r.rect.height()/2.0, egui::Color32::GREEN); },
+                            }
+                            r.clone().on_hover_text(format!("{}: {}", board.get_designator(), pin));
                         }
-                        r.clone().on_hover_text(String::from(board.get_name()) + ":" + &pin_name);
                         r.clone().context_menu(|ui| {
                             ui.label("a pin-level menu option");
                         });
@@ -449,7 +661,7 @@ impl Project {
                             });
                         }
                         
-                        if connection_in_progress && r.clicked() {
+                        if connection_in_progress && clicked {
                             // check conditions for starting/ending a connection
                             match self.system.in_progress_connection_start {
                                 None => {
@@ -457,19 +669,16 @@ impl Project {
                                     ctx.data_mut(|data| {
                                         data.insert_temp(egui::Id::new("connection_start_pos"), r.rect.center());
                                     });
-                                    self.system.in_progress_connection_start = Some((board.clone(), pin_name.clone()));
+                                    self.system.in_progress_connection_start = Some((board.clone(), pin.clone()));
                                 },
                                 Some((ref start_board, ref start_pin)) => {
-                                    // add the connection to the system struct
-                                    let c = super::system::Connection {
-                                        name: format!("connection_{}", self.system.connections.len()),
-                                        start_board: start_board.clone(),
-                                        start_pin: start_pin.clone(),
-                                        end_board: board.clone(),
-                                        end_pin: pin_name.clone(),
-                                        interface_mapping: board::pinout::InterfaceMapping::default(),
-                                    };
-                                    self.system.connections.push(c);
+                                    // add the connection to the system struct, joining any existing
+                                    // connection that either pin is already a part of
+                                    let (start_board, start_pin) = (start_board.clone(), start_pin.clone());
+                                    match self.system.connect_pins(&start_board, &start_pin, board, &pin) {
+                                        Ok(_) => edit = Some((format!("connect {} to {}", start_pin.name(), pin.name()), false)),
+                                        Err(e) => warn!("couldn't connect {} to {}: {:?}", start_pin.name(), pin.name(), e),
+                                    }
                                     // clear the in_progress_connection fields
                                     self.system.in_progress_connection_start = None;
                                     self.system.in_progress_connection_end = None;
@@ -492,8 +701,87 @@ impl Project {
             let board_response = response.response;
             let pin_response = response.inner;
 
+            // a stacked board is drawn on top of the board it's stacked on
+            if self.system.stack_base(board).is_some() {
+                ctx.move_to_top(board_response.layer_id);
+            }
+
+            // clicking a board selects it, and dragging a board that isn't selected yet selects it
+            if board_response.clicked() || (board_response.drag_started() && !selected && !panning) {
+                self.selection.click_board(board, shift);
+                clicked_item = true;
+            }
+            if board_response.clicked() || board_response.drag_started() {
+                ctx.memory_mut(|m| m.request_focus(canvas_id));
+            }
+
+            // dragging the board moves it on the canvas, along with the rest of the selection. The
+            // unsnapped position is kept while dragging, so that small movements add up to a move
+            // to the next grid line.
+            let drag_id = board_id.with("drag_position");
+            if board_response.dragged() && !panning {
+                let unsnapped = ctx.data_mut(|data| {
+                    let p = data.get_temp_mut_or(drag_id, egui::pos2(placement.x, placement.y));
+                    *p += board_response.drag_delta() / scale;
+                    *p
+                });
+                let (old_x, old_y) = (placement.x, placement.y);
+                placement.x = if viewport.snap_to_grid { canvas::snap(unsnapped.x) } else { unsnapped.x };
+                placement.y = if viewport.snap_to_grid { canvas::snap(unsnapped.y) } else { unsnapped.y };
+                self.system.set_placement(board, placement);
+                edit = Some((format!("move {}", board.get_designator()), true));
+                if self.selection.contains_board(board) && self.selection.num_boards() > 1 {
+                    let root = self.system.stack_root(board);
+                    for other in self.selection.stack_roots(&self.system).iter().filter(|other| **other != root) {
+                        let mut other_placement = self.system.get_placement(other);
+                        other_placement.x += placement.x - old_x;
+                        other_placement.y += placement.y - old_y;
+                        self.system.set_placement(other, other_placement);
+                    }
+                    edit = Some((format!("move {} boards", self.selection.num_boards()), true));
+                }
+            } else {
+                ctx.data_mut(|data| data.remove::<egui::Pos2>(drag_id));
+            }
+            if board_response.drag_stopped() {
+                self.history.close();
+            }
+
             // Actions for board-level stuff
             board_response.context_menu(|ui| {
+                ui.label(format!("{} designator:", board.get_name()));
+                // the designator is only changed once it's been typed out and Enter is pressed, so
+                // the text being typed is kept in memory until then, and dropped on Esc
+                let designator_id = egui::Id::new(("designator_edit", &*board));
+                let mut designator = ctx.data(|data| data.get_temp::<String>(designator_id))
+                    .unwrap_or_else(|| board.get_designator().to_string());
+                let designator_response = ui.add(egui::TextEdit::singleline(&mut designator).id(designator_id));
+                if designator_response.lost_focus() {
+                    ctx.data_mut(|data| data.remove::<String>(designator_id));
+                    if ui.input(|i| i.key_pressed(egui::Key::Enter)) && designator != board.get_designator() {
+                        match self.system.set_designator(board, &designator) {
+                            Ok(_) => edit = Some(("change designator".to_string(), false)),
+                            Err(e) => warn!("couldn't change the designator of {} to {}: {:?}", board.get_designator(), designator, e),
+                        }
+                    }
+                } else if designator_response.has_focus() {
+                    ctx.data_mut(|data| data.insert_temp(designator_id, designator));
+                } else {
+                    ctx.data_mut(|data| data.remove::<String>(designator_id));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("rotate").clicked() {
+                        placement.rotate();
+                        self.system.set_placement(board, placement);
+                        edit = Some((format!("rotate {}", board.get_designator()), false));
+                    }
+                    if ui.button("flip").clicked() {
+                        placement.flipped = !placement.flipped;
+                        self.system.set_placement(board, placement);
+                        edit = Some((format!("flip {}", board.get_designator()), false));
+                    }
+                });
+                ui.separator();
                 ui.menu_button("pinout info", |ui| {
                     for po in board.get_pinout().iter() {
                         let label = format!("{:?}", po);
@@ -502,6 +790,62 @@ impl Project {
                         }
                     }
                 });
+                // connect a whole interface (e.g. I2C) to a compatible one on another board
+                match self.system.in_progress_bus_start.clone() {
+                    Some((start_board, start_mapping)) if start_board != *board => {
+                        ui.menu_button(format!("finish {} connection", start_mapping.interface.iface_type), |ui| {
+                            let compatible = board.get_pinout().into_iter().filter(|mapping| {
+                                mapping.interface.iface_type == start_mapping.interface.iface_type
+                            });
+                            for mapping in compatible {
+                                if ui.button(mapping.to_string()).clicked() {
+                                    self.system.add_bus_connection(&start_board, &start_mapping, board, &mapping).unwrap_or_else(|e| {
+                                        warn!("couldn't connect {} to {}: {:?}", start_mapping, mapping, e);
+                                    });
+                                    edit = Some((format!("connect {} bus", mapping.interface.iface_type), false));
+                                    self.system.in_progress_bus_start = None;
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                        if ui.button("cancel interface connection").clicked() {
+                            self.system.in_progress_bus_start = None;
+                        }
+                    },
+                    _ => {
+                        ui.menu_button("connect interface", |ui| {
+                            for mapping in board.get_pinout().into_iter() {
+                                if ui.button(mapping.to_string()).clicked() {
+                                    self.system.in_progress_bus_start = Some((board.clone(), mapping));
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                        // add the board to a bus that's already shared by other boards
+                        let joinable: Vec<(String, board::pinout::InterfaceMapping)> = self.system.get_buses().into_iter().filter_map(|bus| {
+                            let wires = self.system.get_bus_wires(&bus);
+                            let iface_type = wires.first()?.interface_mapping.interface.iface_type;
+                            if !iface_type.is_multi_drop() || wires.iter().any(|wire| wire.get_boards().contains(&&*board)) {
+                                return None;
+                            }
+                            let mapping = board.get_pinout().into_iter().find(|m| m.interface.iface_type == iface_type)?;
+                            Some((bus, mapping))
+                        }).collect();
+                        if !joinable.is_empty() {
+                            ui.menu_button("join bus", |ui| {
+                                for (bus, mapping) in joinable {
+                                    if ui.button(format!("{}: {}", bus, mapping)).clicked() {
+                                        self.system.join_bus(&bus, board, &mapping).unwrap_or_else(|e| {
+                                            warn!("couldn't join {} to bus {}: {:?}", mapping, bus, e);
+                                        });
+                                        edit = Some((format!("join bus {}", bus), false));
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        }
+                    },
+                }
                 ui.menu_button("rust-analyser stuff", |ui| {
                     for s in board.ra_values.iter() {
                         if ui.label(format!("{:?}", s.label)).clicked() {
@@ -509,16 +853,35 @@ impl Project {
                         }
                     }
                 });
+                // stack the board on a main board of the same form factor, or take it off again
+                if self.system.stack_base(board).is_some() {
+                    if ui.button("unstack").clicked() {
+                        self.system.unstack(board).unwrap_or_else(|e| {
+                            warn!("couldn't unstack {}: {:?}", board.get_designator(), e);
+                        });
+                        edit = Some((format!("unstack {}", board.get_designator()), false));
+                        ui.close_menu();
+                    }
+                } else if let Some(base) = self.system.stackable_on(board) {
+                    if ui.button(format!("stack on {}", base.get_designator())).clicked() {
+                        self.system.stack(board).unwrap_or_else(|e| {
+                            warn!("couldn't stack {} on {}: {:?}", board.get_designator(), base.get_designator(), e);
+                        });
+                        edit = Some((format!("stack {}", board.get_designator()), false));
+                        ui.close_menu();
+                    }
+                }
                 if ui.button("remove board from system").clicked() {
                     self.system.remove_board(board.clone()).unwrap_or_else(|_| {
                         warn!("error removing board from system.");
                     });
+                    edit = Some((format!("remove {}", board.get_designator()), false));
                 }
             });
 
             // Actions for pin-level stuff
             if let Some(pin) = pin_response {
-                info!("pin {} clicked!", pin);
+                info!("pin {:?} clicked!", pin);
             }
 
         } // for each Board
@@ -537,6 +900,16 @@ impl Project {
                 data.insert_temp(id, false);
                 data.remove::<egui::Pos2>(egui::Id::new("connection_start_pos"));
                 self.system.in_progress_connection_start = None;
+                self.system.in_progress_bus_start = None;
+            });
+        }
+
+        if let Some((start_board, start_mapping)) = &self.system.in_progress_bus_start {
+            ui.with_layout(egui::Layout::bottom_up(egui::Align::Min), |ui| {
+                ui.label(format!(
+                    "Right-click another board to connect it to {} on {}... or use ESC to cancel.",
+                    start_mapping, start_board.get_designator(),
+                ));
             });
         }
 
@@ -556,50 +929,951 @@ impl Project {
             }
         }
 
+        // with orthogonal routing, two-pin connections are routed around the boards
+        let routes = match viewport.orthogonal_routing {
+            true => self.route_connections(ctx),
+            false => HashMap::new(),
+        };
+
         // go through the system connections and see if this pin is a part of any of them
         let mut connection_to_remove: Option<system::Connection> = None;
+        let mut endpoint_to_remove: Option<(String, usize)> = None;
+        // renames are validated by the system, so they're made after the connections are drawn
+        let mut rename: Option<(String, String)> = None;
+        let label_editor_id = egui::Id::new("wire_label_editor");
+        let editing_label = ctx.data(|data| data.get_temp::<String>(label_editor_id));
         for connection in self.system.connections.iter_mut() {
-            // get the start and end pin locations. If they're not in the map (which they should be...), just skip
-            let start_loc: egui::Pos2 = match pin_locations.get(&(connection.start_board.clone(), connection.start_pin.clone())) {
-                Some(sl) => *sl,
-                None => continue,
-            };
-            let end_loc: egui::Pos2 = match pin_locations.get(&(connection.end_board.clone(), connection.end_pin.clone())) {
-                Some(el) => *el,
-                None => continue,
+            // the wires of a bus are drawn together below
+            if connection.bus.is_some() {
+                continue;
+            }
+            // get the pin locations. If they're not in the map (which they should be...), just skip
+            let locs: Vec<egui::Pos2> = connection.endpoints.iter().filter_map(|endpoint| {
+                pin_locations.get(&(endpoint.board.clone(), endpoint.pin.svg_id.clone())).copied()
+            }).collect();
+            if locs.len() < connection.endpoints.len() || locs.len() < 2 {
+                continue;
+            }
+            // draw the connection and perform interactions. A connection joining more than two
+            // pins is drawn as a trunk with a branch to each pin.
+            let wire_color = connection.color.unwrap_or_else(|| {
+                colorscheme.interface_color(connection.interface_mapping.interface.iface_type)
+            });
+            let c = match self.selection.contains_connection(&connection.name) {
+                true => ui.visuals().selection.stroke.color,
+                false => wire_color,
             };
-            // draw the connection and perform interactions.
-            let c = match connection.interface_mapping.interface.iface_type {
-                board::pinout::InterfaceType::I2C => egui::Color32::RED,
-                board::pinout::InterfaceType::UART => egui::Color32::BLUE,
-                board::pinout::InterfaceType::SPI => egui::Color32::YELLOW,
-                board::pinout::InterfaceType::NONE => egui::Color32::GREEN,
-                _ => egui::Color32::WHITE,
+            let route = routes.get(&connection.name);
+            let (resp, label_pos) = match (route, locs.len()) {
+                (Some(route), _) => {
+                    let positions: Vec<egui::Pos2> = route.iter().map(|p| canvas.to_screen(*p)).collect();
+                    (draw_route(ctx, ui, &positions, egui::Stroke { width: 2.0, color: c }), route_label_pos(&positions))
+                },
+                (None, 2) => (draw_connection(ctx, ui, locs[0], locs[1], c), wire_label_pos(&locs)),
+                _ => (draw_net(ctx, ui, &locs, egui::Stroke { width: 2.0, color: c }), wire_label_pos(&locs)),
             };
-            let resp = draw_connection(ctx, ui, start_loc, end_loc, c);
+            // label the wire with the connection's name, which can be edited by double-clicking it.
+            // The name being typed is kept in memory until the editor loses focus, and only
+            // renames the connection if that was by pressing Enter.
+            if editing_label.as_deref() == Some(connection.name.as_str()) {
+                let text_edit_id = label_editor_id.with("text");
+                egui::Area::new(label_editor_id)
+                    .pivot(egui::Align2::CENTER_CENTER)
+                    .fixed_pos(label_pos)
+                    .order(egui::Order::Foreground)
+                    .show(ctx, |ui| {
+                        let mut name = ctx.data(|data| data.get_temp::<String>(text_edit_id))
+                            .unwrap_or_else(|| connection.name.clone());
+                        let r = ui.add(egui::TextEdit::singleline(&mut name).id(text_edit_id).desired_width(120.0));
+                        if r.lost_focus() {
+                            ctx.data_mut(|data| {
+                                data.remove::<String>(label_editor_id);
+                                data.remove::<String>(text_edit_id);
+                            });
+                            if ui.input(|i| i.key_pressed(egui::Key::Enter)) && name != connection.name {
+                                rename = Some((connection.name.clone(), name));
+                            }
+                        } else {
+                            ctx.data_mut(|data| data.insert_temp(text_edit_id, name));
+                        }
+                    });
+            } else if viewport.show_wire_labels
+                && draw_wire_label(ui, label_pos, &connection.name, c).on_hover_text("double-click to rename").double_clicked()
+            {
+                ctx.data_mut(|data| data.insert_temp(label_editor_id, connection.name.clone()));
+                ctx.memory_mut(|memory| memory.request_focus(label_editor_id.with("text")));
+                clicked_item = true;
+            }
+            let mut hover_text = connection.name.clone();
+            for endpoint in connection.endpoints.iter() {
+                hover_text += &format!("\n{}: {}", endpoint.board.get_designator(), endpoint.pin);
+            }
+            // flag the connection if the electrical rule check found problems with it
+            let names = [connection.name.clone()];
+            flag_violations(ui, &violations, &names, &locs, &mut hover_text);
+            let resp = resp.on_hover_text(hover_text);
+            if resp.clicked_by(egui::PointerButton::Primary) {
+                self.selection.click_connections(&names, shift);
+                clicked_item = true;
+            }
+            // the waypoints of a selected route can be dragged around, or removed with a double
+            // click. A route without any shows its corners instead, and dragging one of them
+            // turns all of the corners into waypoints.
+            if let Some(route) = route.filter(|_| self.selection.contains_connection(&connection.name)) {
+                let handles: Vec<egui::Pos2> = match connection.waypoints.is_empty() {
+                    true => route.get(2..route.len().saturating_sub(2)).unwrap_or_default().to_vec(),
+                    false => connection.waypoints.iter().map(|w| egui::pos2(w.x, w.y)).collect(),
+                };
+                let mut painter = ui.painter().clone();
+                painter.set_layer_id(egui::LayerId::new(egui::Order::Middle, painter.layer_id().id));
+                for (idx, handle) in handles.iter().enumerate() {
+                    let handle_pos = canvas.to_screen(*handle);
+                    let handle_id = egui::Id::new(("route_handle", &connection.name, idx));
+                    let r = ui.interact(egui::Rect::from_center_size(handle_pos, egui::vec2(10.0, 10.0)), handle_id, egui::Sense::click_and_drag());
+                    painter.circle(handle_pos, 4.0, ui.visuals().extreme_bg_color, ui.visuals().selection.stroke);
+                    if r.dragged() && !panning {
+                        if connection.waypoints.is_empty() {
+                            connection.waypoints = handles.iter().map(|p| system::Waypoint { x: p.x, y: p.y }).collect();
+                        }
+                        if let Some(p) = r.interact_pointer_pos().map(|p| canvas.to_canvas(p)) {
+                            let snap = |v: f32| if viewport.snap_to_grid { canvas::snap(v) } else { v };
+                            connection.waypoints[idx] = system::Waypoint { x: snap(p.x), y: snap(p.y) };
+                            edit = Some(("move waypoint".to_string(), true));
+                        }
+                    }
+                    if r.drag_stopped() {
+                        self.history.close();
+                    }
+                    if r.double_clicked() && idx < connection.waypoints.len() {
+                        connection.waypoints.remove(idx);
+                        edit = Some(("remove waypoint".to_string(), false));
+                    }
+                }
+            }
             // Connection-level right click menu
             resp.context_menu(|ui| {
                 ui.label("connection name:");
-                ui.text_edit_singleline(&mut connection.name);
+                let name_id = egui::Id::new(("connection_name_edit", &connection.name));
+                let mut name = ctx.data(|data| data.get_temp::<String>(name_id))
+                    .unwrap_or_else(|| connection.name.clone());
+                let name_response = ui.add(egui::TextEdit::singleline(&mut name).id(name_id));
+                if name_response.lost_focus() {
+                    ctx.data_mut(|data| data.remove::<String>(name_id));
+                    if ui.input(|i| i.key_pressed(egui::Key::Enter)) && name != connection.name {
+                        rename = Some((connection.name.clone(), name));
+                    }
+                } else if name_response.has_focus() {
+                    ctx.data_mut(|data| data.insert_temp(name_id, name));
+                } else {
+                    ctx.data_mut(|data| data.remove::<String>(name_id));
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label("color:");
+                    let mut color = wire_color;
+                    if egui::color_picker::color_edit_button_srgba(ui, &mut color, egui::color_picker::Alpha::Opaque).changed() {
+                        connection.color = Some(color);
+                        edit = Some((format!("change the color of {}", connection.name), true));
+                    }
+                    if connection.color.is_some() && ui.button("use interface color").clicked() {
+                        connection.color = None;
+                        edit = Some((format!("change the color of {}", connection.name), false));
+                    }
+                });
                 ui.separator();
                 ui.label("connection type:");
-                for iface_type in enum_iterator::all::<board::pinout::InterfaceType>() {
-                    ui.selectable_value(&mut connection.interface_mapping.interface.iface_type, iface_type, format!("{:?}", iface_type));
+                // a bus wire takes its type from the bus
+                ui.add_enabled_ui(connection.bus.is_none(), |ui| {
+                    for iface_type in enum_iterator::all::<board::pinout::InterfaceType>() {
+                        if ui.selectable_value(&mut connection.interface_mapping.interface.iface_type, iface_type, format!("{:?}", iface_type)).clicked() {
+                            edit = Some((format!("change the type of {}", connection.name), false));
+                        }
+                    }
+                });
+                for endpoint in connection.endpoints.iter_mut() {
+                    if endpoint.pin.functions.is_empty() {
+                        continue;
+                    }
+                    ui.separator();
+                    ui.label(format!("{} {} function:", endpoint.board.get_designator(), endpoint.pin.name()));
+                    if ui.selectable_value(&mut endpoint.function, None, "unassigned").clicked() {
+                        edit = Some((format!("unassign the function of {}", endpoint.pin.name()), false));
+                    }
+                    for f in endpoint.pin.functions.iter() {
+                        // picking a function also sets the connection type, if it hasn't been chosen yet
+                        if ui.selectable_value(&mut endpoint.function, Some(f.clone()), f.to_string()).clicked() {
+                            edit = Some((format!("assign {} to {}", f, endpoint.pin.name()), false));
+                            if connection.interface_mapping.interface.iface_type == board::pinout::InterfaceType::NONE {
+                                connection.interface_mapping.interface.iface_type = f.iface_type;
+                            }
+                        }
+                    }
                 }
                 ui.separator();
+                if !connection.waypoints.is_empty() && ui.button("reset route").clicked() {
+                    connection.waypoints.clear();
+                    edit = Some((format!("reset the route of {}", connection.name), false));
+                }
+                if connection.endpoints.len() > 2 {
+                    ui.menu_button("disconnect pin", |ui| {
+                        for (idx, endpoint) in connection.endpoints.iter().enumerate() {
+                            if ui.button(format!("{}: {}", endpoint.board.get_designator(), endpoint.pin.name())).clicked() {
+                                endpoint_to_remove = Some((connection.name.clone(), idx));
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                }
                 if ui.button("delete connection").clicked() {
                     connection_to_remove = Some(connection.clone());
                 }
             });
         }
 
+        if let Some((name, new_name)) = rename {
+            match self.system.rename_connection(&name, &new_name) {
+                Ok(_) => {
+                    self.selection.rename_connection(&name, &new_name);
+                    edit = Some((format!("rename {}", name), false));
+                },
+                Err(e) => warn!("couldn't rename connection {} to {}: {:?}", name, new_name, e),
+            }
+        }
+
         // remove the connection if it was selected for deletion
         if let Some(conn) = connection_to_remove {
             self.system.connections.retain(|elem| {
                 elem.name != conn.name
             });
+            edit = Some((format!("delete {}", conn.name), false));
+        }
+        if let Some((name, idx)) = endpoint_to_remove {
+            if let Some(connection) = self.system.connections.iter_mut().find(|c| c.name == name) {
+                let endpoint = connection.endpoints.remove(idx);
+                edit = Some((format!("disconnect {} from {}", endpoint.pin.name(), name), false));
+            }
+        }
+
+        // draw each bus as a single, grouped cable
+        for bus in self.system.get_buses() {
+            let wires = self.system.get_bus_wires(&bus);
+            // group the pin locations by board
+            let mut groups: Vec<(&board::Board, Vec<egui::Pos2>)> = Vec::new();
+            for endpoint in wires.iter().flat_map(|wire| wire.endpoints.iter()) {
+                let Some(loc) = pin_locations.get(&(endpoint.board.clone(), endpoint.pin.svg_id.clone())) else {
+                    continue;
+                };
+                match groups.iter_mut().find(|(board, _)| **board == endpoint.board) {
+                    Some((_, locs)) => locs.push(*loc),
+                    None => groups.push((&endpoint.board, vec![*loc])),
+                }
+            }
+            if groups.len() < 2 {
+                continue;
+            }
+            let iface_type = wires[0].interface_mapping.interface.iface_type;
+            let mut hover_text = format!("{} ({} bus)", bus, iface_type);
+            for wire in wires.iter() {
+                let pins: Vec<String> = wire.endpoints.iter().map(|endpoint| {
+                    format!("{}: {}", endpoint.board.get_designator(), endpoint.pin.name())
+                }).collect();
+                hover_text += &format!("\n{} \u{2192} {}", wire.name, pins.join(", "));
+            }
+            let names: Vec<String> = wires.iter().map(|wire| wire.name.clone()).collect();
+            let wires_colored = wires.iter().any(|wire| wire.color.is_some());
+            let locs: Vec<Vec<egui::Pos2>> = groups.into_iter().map(|(_, locs)| locs).collect();
+            let bus_color = wires[0].color.unwrap_or_else(|| colorscheme.interface_color(iface_type));
+            let color = match names.iter().all(|name| self.selection.contains_connection(name)) {
+                true => ui.visuals().selection.stroke.color,
+                false => bus_color,
+            };
+            let resp = draw_bus(ctx, ui, &locs, color);
+            if viewport.show_wire_labels {
+                let hubs: Vec<egui::Pos2> = locs.iter().map(|group| wire_label_pos(group)).collect();
+                draw_wire_label(ui, wire_label_pos(&hubs), &bus, color);
+            }
+            if resp.clicked_by(egui::PointerButton::Primary) {
+                self.selection.click_connections(&names, shift);
+                clicked_item = true;
+            }
+            let all_locs: Vec<egui::Pos2> = locs.concat();
+            flag_violations(ui, &violations, &names, &all_locs, &mut hover_text);
+            // Bus-level right click menu
+            resp.on_hover_text(hover_text).context_menu(|ui| {
+                ui.label("bus name:");
+                // like a designator, the bus is only renamed once its name has been typed out
+                let name_id = egui::Id::new(("bus_name_edit", &bus));
+                let mut name = ctx.data(|data| data.get_temp::<String>(name_id))
+                    .unwrap_or_else(|| bus.clone());
+                let name_response = ui.add(egui::TextEdit::singleline(&mut name).id(name_id));
+                if name_response.lost_focus() {
+                    ctx.data_mut(|data| data.remove::<String>(name_id));
+                    if ui.input(|i| i.key_pressed(egui::Key::Enter)) && name != bus {
+                        match self.system.rename_bus(&bus, &name) {
+                            Ok(_) => {
+                                let new_names = self.system.get_bus_wires(&name).into_iter().map(|wire| wire.name.clone());
+                                for (old_name, new_name) in names.iter().zip(new_names) {
+                                    self.selection.rename_connection(old_name, &new_name);
+                                }
+                                edit = Some((format!("rename bus {}", bus), false));
+                            },
+                            Err(e) => warn!("couldn't rename bus {} to {}: {:?}", bus, name, e),
+                        }
+                    }
+                } else if name_response.has_focus() {
+                    ctx.data_mut(|data| data.insert_temp(name_id, name));
+                } else {
+                    ctx.data_mut(|data| data.remove::<String>(name_id));
+                }
+                ui.horizontal(|ui| {
+                    ui.label("color:");
+                    let mut color = bus_color;
+                    if egui::color_picker::color_edit_button_srgba(ui, &mut color, egui::color_picker::Alpha::Opaque).changed() {
+                        self.system.set_bus_color(&bus, Some(color));
+                        edit = Some((format!("change the color of {}", bus), true));
+                    }
+                    if wires_colored && ui.button("use interface color").clicked() {
+                        self.system.set_bus_color(&bus, None);
+                        edit = Some((format!("change the color of {}", bus), false));
+                    }
+                });
+                ui.separator();
+                if ui.button("delete bus").clicked() {
+                    self.system.remove_bus(&bus);
+                    edit = Some((format!("delete bus {}", bus), false));
+                }
+            });
+        }
+
+        // finish the rubber band once the drag is over
+        if let Some(band) = band.filter(|_| !background.dragged()) {
+            ctx.data_mut(|data| data.remove::<egui::Pos2>(band_id));
+            if !shift {
+                self.selection.clear();
+            }
+            let area = egui::Rect::from_two_pos(canvas.to_canvas(band.min), canvas.to_canvas(band.max));
+            for (board, rect) in canvas::board_bounds(&self.system) {
+                if area.intersects(rect) {
+                    self.selection.add_board(&board);
+                }
+            }
+            for connection in self.system.connections.iter() {
+                let inside = connection.endpoints.iter().all(|endpoint| {
+                    pin_locations.get(&(endpoint.board.clone(), endpoint.pin.svg_id.clone())).is_some_and(|p| band.contains(*p))
+                });
+                if inside {
+                    self.selection.add_connection(&connection.name);
+                }
+            }
+        } else if !clicked_item && !shift && ctx.input(|i| i.pointer.primary_clicked()) {
+            // a click on empty canvas clears the selection. The connections sense clicks in the
+            // whole rectangle around them, so this can't rely on the background's response.
+            let on_background = ctx.pointer_interact_pos().is_some_and(|p| {
+                canvas_rect.contains(p) && ctx.layer_id_at(p).is_none_or(|layer| layer == ui.layer_id())
+            });
+            if on_background {
+                self.selection.clear();
+            }
+        }
+
+        // pick the pins of a connection by name
+        if let Some(picker_edit) = self.display_pin_picker(ctx, &canvas) {
+            edit = Some(picker_edit);
+            ctx.memory_mut(|m| m.request_focus(canvas_id));
+        }
+
+        // the toolbar's undo and redo aren't edits, but its other buttons are
+        if let Some(toolbar_edit) = self.display_canvas_controls(ctx, &canvas) {
+            edit = Some(toolbar_edit);
+        }
+
+        // record the changes made this frame in the undo history
+        if let Some((description, continuing)) = edit {
+            self.record_edit(&description, continuing);
+        }
+
+        if self.system.viewport.show_legend {
+            self.display_legend(ctx, &canvas, &colorscheme);
         }
+    }
+
+    /// Pan and zoom the system editor canvas with the mouse. The view pans while dragging with
+    /// the middle button, or with the primary button while holding space, and the scroll wheel
+    /// zooms around the cursor. Returns whether the view is being panned.
+    fn update_canvas_view(&mut self, ctx: &egui::Context, canvas_rect: egui::Rect) -> bool {
+        let board_layers: Vec<egui::Id> = self.system.get_all_boards().iter()
+            .map(|board| egui::Id::new(("system_editor_board", board)))
+            .collect();
+        // the pointer is over the canvas if it's over the background, or over one of the boards
+        let Some(pointer) = ctx.input(|io| io.pointer.hover_pos()) else { return false };
+        let over_canvas = canvas_rect.contains(pointer) && ctx.layer_id_at(pointer).is_none_or(|layer| {
+            layer.order == egui::Order::Background || board_layers.contains(&layer.id)
+        });
+        if !over_canvas {
+            return false;
+        }
+        let space_down = !ctx.wants_keyboard_input() && ctx.input(|io| io.key_down(Key::Space));
+        let (panning, delta, scroll, zoom) = ctx.input(|io| (
+            io.pointer.middle_down() || (space_down && io.pointer.primary_down()),
+            io.pointer.delta(),
+            io.smooth_scroll_delta.y,
+            io.zoom_delta(),
+        ));
+        if panning {
+            canvas::pan(&mut self.system.viewport, delta);
+            ctx.output_mut(|o| o.cursor_icon = egui::CursorIcon::Grabbing);
+        } else if space_down {
+            ctx.output_mut(|o| o.cursor_icon = egui::CursorIcon::Grab);
+        }
+        let factor = zoom * (scroll / 200.0).exp();
+        if factor != 1.0 {
+            canvas::zoom_around(&mut self.system.viewport, canvas_rect, pointer, factor);
+        }
+        panning
+    }
 
+    /// Route the connections that join two pins around the boards, keyed by connection name. The
+    /// routes are kept between frames, and only found again once a board or waypoint moves.
+    fn route_connections(&self, ctx: &egui::Context) -> HashMap<String, Vec<egui::Pos2>> {
+        let bounds = canvas::board_bounds(&self.system);
+        let obstacles: Vec<egui::Rect> = bounds.iter().map(|(_, rect)| *rect).collect();
+        let board_rect = |board: &board::Board| bounds.iter().find(|(b, _)| b == board).map(|(_, rect)| *rect);
+        let mut names: Vec<String> = Vec::new();
+        let mut requests: Vec<routing::RouteRequest> = Vec::new();
+        for connection in self.system.connections.iter().filter(|c| c.bus.is_none() && c.endpoints.len() == 2) {
+            let (from, to) = (&connection.endpoints[0], &connection.endpoints[1]);
+            let from_pos = canvas::pin_location(&self.system, &from.board, &from.pin.svg_id);
+            let to_pos = canvas::pin_location(&self.system, &to.board, &to.pin.svg_id);
+            let (Some(from_pos), Some(to_pos)) = (from_pos, to_pos) else { continue };
+            names.push(connection.name.clone());
+            requests.push(routing::RouteRequest {
+                from: from_pos,
+                from_board: board_rect(&from.board),
+                to: to_pos,
+                to_board: board_rect(&to.board),
+                via: connection.waypoints.iter().map(|w| egui::pos2(w.x, w.y)).collect(),
+            });
+        }
+        let key = routing::cache_key(&obstacles, &requests);
+        let id = egui::Id::new("system_editor_routes");
+        let routes = match ctx.data(|data| data.get_temp::<(u64, Vec<Vec<egui::Pos2>>)>(id)) {
+            Some((cached_key, routes)) if cached_key == key => routes,
+            _ => {
+                let routes = routing::route_all(&obstacles, &requests);
+                ctx.data_mut(|data| data.insert_temp(id, (key, routes.clone())));
+                routes
+            },
+        };
+        names.into_iter().zip(routes).collect()
+    }
+
+    /// Show the canvas toolbar in the top-right corner, with "fit all" and the snapping toggle,
+    /// and a minimap if some of the boards are out of view. Returns a description of the edit,
+    /// if one was made.
+    fn display_canvas_controls(&mut self, ctx: &egui::Context, canvas: &Canvas) -> Option<(String, bool)> {
+        let mut edit: Option<(String, bool)> = None;
+        let bounds = canvas::board_bounds(&self.system);
+        egui::Area::new(egui::Id::new("system_editor_canvas_toolbar"))
+            .pivot(egui::Align2::RIGHT_TOP)
+            .fixed_pos(canvas.rect.right_top() + egui::vec2(-8.0, 8.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let undo_text = self.history.undo_description().map(|d| format!("undo {} (ctrl+z)", d));
+                        let undo_button = ui.add_enabled(undo_text.is_some(), egui::Button::new("undo"));
+                        if undo_button.on_hover_text(undo_text.unwrap_or_default()).clicked() {
+                            self.undo();
+                        }
+                        let redo_text = self.history.redo_description().map(|d| format!("redo {} (ctrl+shift+z)", d));
+                        let redo_button = ui.add_enabled(redo_text.is_some(), egui::Button::new("redo"));
+                        if redo_button.on_hover_text(redo_text.unwrap_or_default()).clicked() {
+                            self.redo();
+                        }
+                        ui.separator();
+                        let duplicate_button = ui.add_enabled(self.selection.num_boards() > 0, egui::Button::new("duplicate"));
+                        if duplicate_button.on_hover_text("duplicate the selected boards (ctrl+d)").clicked() {
+                            self.duplicate_selection();
+                            edit = Some(("duplicate".to_string(), false));
+                        }
+                        if ui.button("connect").on_hover_text("pick the pins of a new connection by name (ctrl+k)").clicked() {
+                            open_pin_picker(ctx);
+                        }
+                        let delete_button = ui.add_enabled(!self.selection.is_empty(), egui::Button::new("delete"));
+                        if delete_button.on_hover_text("delete the selection (del)").clicked() {
+                            self.delete_selection();
+                            edit = Some(("delete".to_string(), false));
+                        }
+                        ui.separator();
+                        let fit_button = ui.add_enabled(!bounds.is_empty(), egui::Button::new("fit all"));
+                        if fit_button.clicked() {
+                            if let Some(area) = canvas::bounds_of(&bounds) {
+                                canvas::fit(&mut self.system.viewport, canvas.rect, area);
+                            }
+                        }
+                        ui.checkbox(&mut self.system.viewport.snap_to_grid, "snap to grid");
+                        ui.checkbox(&mut self.system.viewport.orthogonal_routing, "orthogonal wires");
+                        ui.checkbox(&mut self.system.viewport.show_wire_labels, "labels");
+                        ui.checkbox(&mut self.system.viewport.show_legend, "legend");
+                        ui.checkbox(&mut self.system.viewport.show_connection_table, "table");
+                        ui.checkbox(&mut self.system.viewport.show_breadboard, "breadboard");
+                        ui.label(format!("{:.0}%", self.system.viewport.zoom / system::Viewport::default().zoom * 100.0));
+                    });
+                });
+            });
+        let all_visible = canvas::bounds_of(&bounds).is_none_or(|area| canvas.visible_area().contains_rect(area));
+        if !all_visible {
+            if let Some(target) = canvas::show_minimap(ctx, canvas, &bounds) {
+                canvas::center_on(&mut self.system.viewport, canvas.rect, target);
+            }
+        }
+        edit
+    }
+
+    /// Show which color means which interface type in the bottom-left corner of the canvas, for
+    /// the interface types of the system's connections, along with any connections that have
+    /// a color of their own.
+    fn display_legend(&self, ctx: &egui::Context, canvas: &Canvas, colorscheme: &ColorScheme) {
+        let iface_types: Vec<board::pinout::InterfaceType> = enum_iterator::all::<board::pinout::InterfaceType>().filter(|t| {
+            self.system.connections.iter().any(|c| c.interface_mapping.interface.iface_type == *t)
+        }).collect();
+        let swatch = |ui: &mut egui::Ui, color: egui::Color32, text: String| {
+            ui.horizontal(|ui| {
+                let (rect, _) = ui.allocate_exact_size(egui::vec2(20.0, 4.0), egui::Sense::hover());
+                ui.painter().rect_filled(rect, 0.0, color);
+                ui.label(text);
+            });
+        };
+        egui::Area::new(egui::Id::new("system_editor_legend"))
+            .pivot(egui::Align2::LEFT_BOTTOM)
+            .fixed_pos(canvas.rect.left_bottom() + egui::vec2(8.0, -8.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.label(RichText::new("Legend").strong());
+                    if iface_types.is_empty() {
+                        ui.label("no connections yet");
+                    }
+                    for iface_type in iface_types {
+                        swatch(ui, colorscheme.interface_color(iface_type), iface_type.to_string());
+                    }
+                    let mut custom = self.system.connections.iter().filter(|c| c.color.is_some()).peekable();
+                    if custom.peek().is_some() {
+                        ui.separator();
+                    }
+                    for connection in custom {
+                        let name = connection.bus.clone().unwrap_or_else(|| connection.name.clone());
+                        swatch(ui, connection.color.unwrap_or_default(), name);
+                    }
+                });
+            });
+    }
+
+    /// List the problems found by the electrical rule check.
+    fn display_erc_panel(&self, ui: &mut egui::Ui, violations: &[erc::ErcViolation]) {
+        ui.heading("Electrical Rule Check");
+        let num_errors = violations.iter().filter(|v| v.severity == Severity::Error).count();
+        ui.label(format!("{} errors, {} warnings", num_errors, violations.len() - num_errors));
+        ui.separator();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for violation in violations.iter() {
+                let text = RichText::new(format!("\u{26A0} {}", violation.message))
+                    .color(severity_color(ui, violation.severity));
+                ui.label(text).on_hover_text(format!("connections: {}", violation.connections.join(", ")));
+            }
+        });
+    }
+
+    /// Show the pin picker at the top of the canvas, if it's open. Picking a pin starts a
+    /// connection in the same way as clicking it does, and picking a second pin finishes it.
+    /// The arrow keys move through the matching pins, enter picks one, and escape closes the
+    /// picker. Returns a description of the edit, if a connection was made.
+    fn display_pin_picker(&mut self, ctx: &egui::Context, canvas: &Canvas) -> Option<(String, bool)> {
+        let id = egui::Id::new("pin_picker");
+        let mut state = ctx.data(|data| data.get_temp::<picker::PickerState>(id))?;
+        if ctx.input(|i| i.key_pressed(Key::Escape)) {
+            ctx.data_mut(|data| data.remove::<picker::PickerState>(id));
+            return None;
+        }
+        let choices = picker::choices(&self.system);
+        let down = ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::ArrowDown));
+        let up = ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::ArrowUp));
+        let enter = ctx.input(|i| i.key_pressed(Key::Enter));
+        let prompt = match &self.system.in_progress_connection_start {
+            None => "connect from:".to_string(),
+            Some((board, pin)) => format!("connect {}:{} to:", board.get_designator(), pin.name()),
+        };
+        let mut picked: Option<picker::PinChoice> = None;
+        egui::Area::new(id)
+            .pivot(egui::Align2::CENTER_TOP)
+            .fixed_pos(canvas.rect.center_top() + egui::vec2(0.0, 8.0))
+            .order(egui::Order::Foreground)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_width(320.0);
+                    ui.label(prompt);
+                    let query = ui.add(egui::TextEdit::singleline(&mut state.query).hint_text("board:pin").desired_width(f32::INFINITY));
+                    if query.changed() {
+                        state.highlighted = 0;
+                    }
+                    let matches = picker::matches(&choices, &state.query);
+                    if down {
+                        state.highlighted += 1;
+                    }
+                    if up {
+                        state.highlighted = state.highlighted.saturating_sub(1);
+                    }
+                    state.highlighted = state.highlighted.min(matches.len().saturating_sub(1));
+                    egui::ScrollArea::vertical().max_height(240.0).show(ui, |ui| {
+                        for (idx, choice) in matches.iter().enumerate() {
+                            let highlighted = idx == state.highlighted;
+                            let r = ui.selectable_label(highlighted, &choice.label);
+                            if highlighted && (up || down) {
+                                r.scroll_to_me(None);
+                            }
+                            if r.clicked() || (highlighted && enter) {
+                                picked = Some((*choice).clone());
+                            }
+                        }
+                        if matches.is_empty() {
+                            ui.weak("no matching pins");
+                        }
+                    });
+                    query.request_focus();
+                });
+            });
+        let Some(choice) = picked else {
+            ctx.data_mut(|data| data.insert_temp(id, state));
+            return None;
+        };
+        let start_pos = canvas::pin_location(&self.system, &choice.board, &choice.pin.svg_id).map(|p| canvas.to_screen(p));
+        let connected = self.pick_connection_pin(ctx, choice.board, choice.pin, start_pos);
+        match connected {
+            Some(_) => ctx.data_mut(|data| data.remove::<picker::PickerState>(id)),
+            None => ctx.data_mut(|data| data.insert_temp(id, picker::PickerState::default())),
+        }
+        connected
+    }
+
+    /// Pick a pin of a new connection from the pin picker or the breadboard, in the same way as
+    /// clicking it in the diagram. The first pin starts the connection, drawn from `start_pos`
+    /// on the screen, and the second one finishes it. Returns a description of the edit once
+    /// the connection is made.
+    fn pick_connection_pin(&mut self, ctx: &egui::Context, board: board::Board, pin: board::pinout::Pin, start_pos: Option<egui::Pos2>) -> Option<(String, bool)> {
+        match self.system.in_progress_connection_start.clone() {
+            None => {
+                self.system.in_progress_connection_start = Some((board, pin));
+                ctx.data_mut(|data| {
+                    data.insert_temp(egui::Id::new("connection_in_progress"), true);
+                    if let Some(start_pos) = start_pos {
+                        data.insert_temp(egui::Id::new("connection_start_pos"), start_pos);
+                    }
+                });
+                None
+            },
+            Some((start_board, start_pin)) => {
+                let connected = self.system.connect_pins(&start_board, &start_pin, &board, &pin);
+                self.system.in_progress_connection_start = None;
+                self.system.in_progress_connection_end = None;
+                ctx.data_mut(|data| {
+                    data.insert_temp(egui::Id::new("connection_in_progress"), false);
+                    data.remove::<egui::Pos2>(egui::Id::new("connection_start_pos"));
+                });
+                match connected {
+                    Ok(_) => Some((format!("connect {} to {}", start_pin.name(), pin.name()), false)),
+                    Err(e) => {
+                        warn!("couldn't connect {} to {}: {:?}", start_pin.name(), pin.name(), e);
+                        None
+                    },
+                }
+            },
+        }
+    }
+
+    /// Show the system on a virtual breadboard. The breadboard is laid out from the same system
+    /// as the diagram, so it follows every edit made there, and clicking a jumper selects its
+    /// connection in both views. While a connection is being made, clicking a pin on the
+    /// breadboard picks it, as in the diagram. Returns a description of the edit, if one was made.
+    fn display_breadboard(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) -> Option<(String, bool)> {
+        use breadboard::PITCH;
+        let mut edit: Option<(String, bool)> = None;
+        let layout = breadboard::layout(&self.system);
+        let connection_in_progress = ctx.data(|data| data.get_temp::<bool>(egui::Id::new("connection_in_progress"))).unwrap_or(false);
+        ui.heading("Breadboard");
+        ui.label(RichText::new("hover over a jumper to see where it lands").weak());
+        ui.separator();
+        let shift = ctx.input(|i| i.modifiers.shift);
+        egui::ScrollArea::both().show(ui, |ui| {
+            // the breadboard is drawn 5 points to the mm, with room for the power rails and labels
+            let scale = 5.0;
+            let size = egui::vec2((layout.rows + 3) as f32 * PITCH, 19.0 * PITCH) * scale;
+            let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+            let origin = rect.min + egui::vec2(2.0 * PITCH, 4.0 * PITCH) * scale;
+            let to_screen = |p: egui::Pos2| origin + p.to_vec2() * scale;
+            let hole_pos = |hole: breadboard::Hole| to_screen(hole.position());
+            let painter = ui.painter_at(rect);
+
+            // the breadboard itself, with its rails, holes, and row and column labels
+            painter.rect_filled(rect, 4.0, egui::Color32::from_rgb(0xf2, 0xf0, 0xe6));
+            let hole_color = egui::Color32::from_gray(0x60);
+            let label_color = egui::Color32::from_gray(0x80);
+            let font = egui::FontId::monospace(9.0);
+            let rail_start = to_screen(egui::pos2(0.0, 0.0)).x;
+            let rail_end = to_screen(egui::pos2((layout.rows - 1) as f32 * PITCH, 0.0)).x;
+            for (y, color) in [(-3.5, egui::Color32::RED), (-1.5, egui::Color32::BLUE), (12.5, egui::Color32::RED), (14.5, egui::Color32::BLUE)] {
+                let y = to_screen(egui::pos2(0.0, y * PITCH)).y;
+                painter.line_segment([egui::pos2(rail_start, y), egui::pos2(rail_end, y)], egui::Stroke::new(1.0, color));
+            }
+            for row in 1..=layout.rows {
+                for column in 0..breadboard::COLUMNS {
+                    let p = hole_pos(breadboard::Hole { row, column });
+                    painter.rect_filled(egui::Rect::from_center_size(p, egui::Vec2::splat(0.35 * PITCH * scale)), 0.0, hole_color);
+                }
+                for rail in [-3.0, -2.0, 13.0, 14.0] {
+                    let p = to_screen(egui::pos2((row - 1) as f32 * PITCH, rail * PITCH));
+                    painter.rect_filled(egui::Rect::from_center_size(p, egui::Vec2::splat(0.35 * PITCH * scale)), 0.0, hole_color);
+                }
+                if row == 1 || row % 5 == 0 {
+                    let p = to_screen(egui::pos2((row - 1) as f32 * PITCH, -0.9 * PITCH));
+                    painter.text(p, egui::Align2::CENTER_CENTER, row.to_string(), font.clone(), label_color);
+                }
+            }
+            for column in 0..breadboard::COLUMNS {
+                let p = hole_pos(breadboard::Hole { row: 1, column }) - egui::vec2(1.2 * PITCH * scale, 0.0);
+                painter.text(p, egui::Align2::CENTER_CENTER, ((b'a' + column) as char).to_string(), font.clone(), label_color);
+            }
+
+            // the boards, with their pins plugged into the holes. A picked pin can change the
+            // connections that the layout was made from, so it's only connected once the
+            // breadboard has been drawn.
+            let mut picked: Option<(board::Board, board::pinout::Pin, egui::Pos2)> = None;
+            for footprint in layout.footprints.iter() {
+                let outline = egui::Rect::from_two_pos(hole_pos(footprint.min), hole_pos(footprint.max)).expand(0.6 * PITCH * scale);
+                let selected = std::iter::once(&footprint.board).chain(footprint.stacked.iter()).any(|b| self.selection.contains_board(b));
+                let stroke = match selected {
+                    true => egui::Stroke::new(2.0, ui.visuals().selection.stroke.color),
+                    false => egui::Stroke::new(1.0, egui::Color32::from_gray(0x20)),
+                };
+                painter.rect(outline, 2.0, egui::Color32::from_rgba_unmultiplied(0x20, 0x50, 0x30, 0xa0), stroke);
+                let mut name = footprint.board.get_designator().to_string();
+                for stacked in footprint.stacked.iter() {
+                    name.push_str(&format!(" + {}", stacked.get_designator()));
+                }
+                painter.text(outline.center(), egui::Align2::CENTER_CENTER, name, egui::FontId::proportional(12.0), egui::Color32::WHITE);
+                for (pin, hole) in footprint.pins.iter() {
+                    let p = hole_pos(*hole);
+                    let r = ui.interact(egui::Rect::from_center_size(p, egui::Vec2::splat(PITCH * scale)), ui.id().with(("breadboard_pin", &footprint.board, &pin.svg_id)), egui::Sense::click());
+                    let color = if r.hovered() { egui::Color32::GREEN } else { egui::Color32::GOLD };
+                    painter.circle_filled(p, 0.3 * PITCH * scale, color);
+                    let r = r.on_hover_text(format!("{}: {} at {}", footprint.board.get_designator(), pin, hole.name()));
+                    if connection_in_progress && r.clicked() {
+                        picked = Some((footprint.board.clone(), pin.clone(), p));
+                    }
+                }
+            }
+
+            // the jumpers, arching over the breadboard, with where they land at each end
+            let mut connection_to_remove: Option<usize> = None;
+            for jumper in layout.jumpers.iter() {
+                let Some(connection) = self.system.connections.get(jumper.connection) else {
+                    continue;
+                };
+                let (from, to) = (hole_pos(jumper.from.hole), hole_pos(jumper.to.hole));
+                let lift = egui::vec2(0.0, -(from.distance(to) * 0.25).clamp(2.0 * PITCH * scale, 12.0 * PITCH * scale));
+                let curve = egui::epaint::CubicBezierShape::from_points_stroke(
+                    [from, from + lift, to + lift, to],
+                    false,
+                    egui::Color32::TRANSPARENT,
+                    egui::Stroke::new(0.4 * PITCH * scale, jumper.color),
+                );
+                let points = curve.flatten(Some(0.5));
+                let segments: Vec<[egui::Pos2; 2]> = points.windows(2).map(|w| [w[0], w[1]]).collect();
+                if self.selection.contains_connection(&connection.name) {
+                    let outline = egui::Stroke::new(0.4 * PITCH * scale + 4.0, ui.visuals().selection.stroke.color);
+                    painter.add(egui::Shape::line(points.clone(), outline));
+                }
+                painter.add(curve);
+                for (end, p) in [(&jumper.from, from), (&jumper.to, to)] {
+                    painter.circle_filled(p, 0.3 * PITCH * scale, jumper.color);
+                    painter.text(p + egui::vec2(0.0, 0.6 * PITCH * scale), egui::Align2::CENTER_TOP, end.label(), font.clone(), egui::Color32::BLACK);
+                }
+                let r = interact_with_segments(ctx, ui, &segments)
+                    .on_hover_text(format!("{}: {} \u{2192} {}", connection.name, jumper.from.describe(), jumper.to.describe()));
+                if r.clicked_by(egui::PointerButton::Primary) {
+                    self.selection.click_connections(std::slice::from_ref(&connection.name), shift);
+                }
+                r.context_menu(|ui| {
+                    if ui.button("delete connection").clicked() {
+                        connection_to_remove = Some(jumper.connection);
+                        ui.close_menu();
+                    }
+                });
+            }
+            if let Some(idx) = connection_to_remove {
+                let removed = self.system.connections.remove(idx);
+                edit = Some((format!("delete {}", removed.name), false));
+            }
+            if let Some((board, pin, p)) = picked {
+                if let Some(connected) = self.pick_connection_pin(ctx, board, pin, Some(p)) {
+                    edit = Some(connected);
+                }
+            }
+        });
+        edit
+    }
+
+    /// Show the connections of the system as a table, which is sorted by clicking a column
+    /// header and filtered by any text in its rows. Every cell can be edited, and the pins
+    /// offered for each board are the ones in its pinout. Clicking a row selects the connection
+    /// on the canvas. Returns a description of the edit, if one was made.
+    fn display_connection_table(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) -> Option<(String, bool)> {
+        let state_id = egui::Id::new("connection_table_state");
+        let mut state: table::TableState = ctx.data(|data| data.get_temp(state_id)).unwrap_or_default();
+        let mut edit: Option<(String, bool)> = None;
+        ui.horizontal(|ui| {
+            ui.heading("Connections");
+            ui.label("filter:");
+            ui.add(egui::TextEdit::singleline(&mut state.filter).desired_width(160.0));
+            ui.label(format!("{} of {}", state.rows(&self.system.connections).len(), self.system.connections.len()));
+        });
+        let boards = self.system.get_all_boards();
+        let board_pins: Vec<Vec<board::pinout::Pin>> = boards.iter().map(|board| board.pinout_pins()).collect();
+        let rows = state.rows(&self.system.connections);
+        let columns: Vec<table::Column> = enum_iterator::all::<table::Column>().collect();
+        let shift = ctx.input(|io| io.modifiers.shift);
+        // renames, endpoint and interface changes are validated by the system, so they're made after the
+        // rows have been drawn
+        let mut rename: Option<(String, String)> = None;
+        let mut endpoint_change: Option<(String, usize, board::Board, board::pinout::Pin)> = None;
+        let mut interface_change: Option<(String, table::Column, board::pinout::Interface)> = None;
+        TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .sense(egui::Sense::click())
+            .columns(TableColumn::auto().at_least(80.0), columns.len() - 1)
+            .column(TableColumn::remainder())
+            .header(20.0, |mut header| {
+                for column in columns.iter() {
+                    header.col(|ui| {
+                        let sorted = state.sort == *column;
+                        let arrow = if !sorted { "" } else if state.ascending { " \u{23F6}" } else { " \u{23F7}" };
+                        if ui.selectable_label(sorted, format!("{}{}", column.title(), arrow)).clicked() {
+                            state.sort_by(*column);
+                        }
+                    });
+                }
+            })
+            .body(|mut body| {
+                for idx in rows {
+                    let connection = &self.system.connections[idx];
+                    body.row(22.0, |mut row| {
+                        row.set_selected(self.selection.contains_connection(&connection.name));
+                        for column in columns.iter() {
+                            let combo_id = ("connection_table", idx, *column as usize);
+                            row.col(|ui| {
+                                // the wires of a bus are changed along with their bus, from its menu
+                                let is_wire = connection.bus.is_none();
+                                let mut change_endpoint = |ui: &mut egui::Ui, end: usize, pick_board: bool| {
+                                    let Some(endpoint) = connection.endpoints.get(end) else {
+                                        return;
+                                    };
+                                    let picked = ui.add_enabled_ui(is_wire, |ui| match pick_board {
+                                        true => endpoint_board_cell(ui, combo_id, endpoint, &boards, &board_pins),
+                                        false => endpoint_pin_cell(ui, combo_id, endpoint, &boards, &board_pins),
+                                    }).inner;
+                                    if let Some((board, pin)) = picked {
+                                        endpoint_change = Some((connection.name.clone(), end, board, pin));
+                                    }
+                                };
+                                match column {
+                                    table::Column::Name => {
+                                        // like a designator, the name is only changed once it's been typed out
+                                        let name_id = ui.id().with(("connection_name", idx));
+                                        let mut name = ctx.data(|data| data.get_temp::<String>(name_id))
+                                            .unwrap_or_else(|| connection.name.clone());
+                                        let response = ui.add(egui::TextEdit::singleline(&mut name).id(name_id));
+                                        if response.lost_focus() {
+                                            ctx.data_mut(|data| data.remove::<String>(name_id));
+                                            if ui.input(|i| i.key_pressed(egui::Key::Enter)) && name != connection.name {
+                                                rename = Some((connection.name.clone(), name));
+                                            }
+                                        } else if response.has_focus() {
+                                            ctx.data_mut(|data| data.insert_temp(name_id, name));
+                                        } else {
+                                            ctx.data_mut(|data| data.remove::<String>(name_id));
+                                        }
+                                    },
+                                    table::Column::StartBoard => change_endpoint(ui, 0, true),
+                                    table::Column::StartPin => change_endpoint(ui, 0, false),
+                                    table::Column::EndBoard => change_endpoint(ui, 1, true),
+                                    table::Column::EndPin => {
+                                        change_endpoint(ui, 1, false);
+                                        if connection.endpoints.len() > 2 {
+                                            ui.label(format!("(+{})", connection.endpoints.len() - 2))
+                                                .on_hover_text("this net joins more pins, which are edited on the canvas");
+                                        }
+                                    },
+                                    table::Column::Type => {
+                                        let mut interface = connection.interface_mapping.interface.clone();
+                                        ui.add_enabled_ui(is_wire, |ui| {
+                                            egui::ComboBox::from_id_source(combo_id).selected_text(interface.iface_type.to_string()).show_ui(ui, |ui| {
+                                                for t in enum_iterator::all::<board::pinout::InterfaceType>() {
+                                                    ui.selectable_value(&mut interface.iface_type, t, t.to_string());
+                                                }
+                                            });
+                                        });
+                                        if interface != connection.interface_mapping.interface {
+                                            interface_change = Some((connection.name.clone(), *column, interface));
+                                        }
+                                    },
+                                    table::Column::Direction => {
+                                        let mut interface = connection.interface_mapping.interface.clone();
+                                        ui.add_enabled_ui(is_wire, |ui| {
+                                            egui::ComboBox::from_id_source(combo_id).selected_text(interface.direction.to_string()).show_ui(ui, |ui| {
+                                                for d in enum_iterator::all::<board::pinout::InterfaceDirection>() {
+                                                    ui.selectable_value(&mut interface.direction, d, d.to_string());
+                                                }
+                                            });
+                                        });
+                                        if interface != connection.interface_mapping.interface {
+                                            interface_change = Some((connection.name.clone(), *column, interface));
+                                        }
+                                    },
+                                }
+                            });
+                        }
+                        if row.response().clicked() {
+                            self.selection.click_connections(std::slice::from_ref(&connection.name), shift);
+                        }
+                    });
+                }
+            });
+        if let Some((name, new_name)) = rename {
+            match self.system.rename_connection(&name, &new_name) {
+                Ok(_) => {
+                    self.selection.rename_connection(&name, &new_name);
+                    edit = Some((format!("rename {}", name), false));
+                },
+                Err(e) => warn!("couldn't rename connection {} to {}: {:?}", name, new_name, e),
+            }
+        }
+        if let Some((name, end, board, pin)) = endpoint_change {
+            match self.system.set_endpoint(&name, end, &board, &pin) {
+                Ok(_) => {
+                    self.selection.retain(&self.system);
+                    edit = Some((format!("connect {} to {}", name, pin.name()), false));
+                },
+                Err(e) => warn!("couldn't connect {} to {} {}: {:?}", name, board.get_designator(), pin.name(), e),
+            }
+        }
+        if let Some((name, column, interface)) = interface_change {
+            match self.system.set_interface(&name, interface) {
+                Ok(_) => edit = Some((format!("edit {} of {}", column.title(), name), false)),
+                Err(e) => warn!("couldn't edit {} of {}: {:?}", column.title(), name, e),
+            }
+        }
+        ctx.data_mut(|data| data.insert_temp(state_id, state));
+        edit
     }
 
     /// Show the project HUD with information about the current system. Return a "Mode" so that
@@ -715,17 +1989,202 @@ impl Project {
 
 
 
+/// Flag a connection (or the wires of a bus) if the electrical rule check found problems
+/// with it, by circling its pins and adding the problems to its hover text.
+fn flag_violations(ui: &egui::Ui, violations: &[erc::ErcViolation], names: &[String], locs: &[egui::Pos2], hover_text: &mut String) {
+    let connection_violations: Vec<&erc::ErcViolation> = violations.iter().filter(|violation| {
+        violation.connections.iter().any(|name| names.contains(name))
+    }).collect();
+    if connection_violations.is_empty() || locs.is_empty() {
+        return;
+    }
+    let color = severity_color(ui, worst_severity(&connection_violations));
+    for loc in locs {
+        ui.painter().circle_stroke(*loc, 8.0, egui::Stroke::new(2.0, color));
+    }
+    let center = locs.iter().fold(egui::Vec2::ZERO, |acc, loc| acc + loc.to_vec2()) / locs.len() as f32;
+    ui.painter().text(
+        center.to_pos2(),
+        egui::Align2::CENTER_CENTER,
+        "\u{26A0}",
+        egui::FontId::proportional(18.0),
+        color,
+    );
+    for violation in connection_violations {
+        *hover_text += &format!("\n\u{26A0} {}: {}", violation.severity, violation.message);
+    }
+}
+
+/// The color used to flag a problem of the given severity.
+fn severity_color(ui: &egui::Ui, severity: Severity) -> egui::Color32 {
+    match severity {
+        Severity::Error => ui.visuals().error_fg_color,
+        Severity::Warning => ui.visuals().warn_fg_color,
+    }
+}
+
+fn worst_severity(violations: &[&erc::ErcViolation]) -> Severity {
+    if violations.iter().any(|v| v.severity == Severity::Error) {
+        Severity::Error
+    } else {
+        Severity::Warning
+    }
+}
+
+/// Where to put the label of a wire through the given positions, i.e. the middle of the curve
+/// between two pins, or the middle of the trunk of a net.
+fn wire_label_pos(positions: &[egui::Pos2]) -> egui::Pos2 {
+    let min_x = positions.iter().map(|pos| pos.x).fold(f32::INFINITY, f32::min);
+    let max_x = positions.iter().map(|pos| pos.x).fold(f32::NEG_INFINITY, f32::max);
+    let mean_y = positions.iter().map(|pos| pos.y).sum::<f32>() / positions.len() as f32;
+    egui::pos2((min_x + max_x) / 2.0, mean_y)
+}
+
+/// Where to put the label of an orthogonal route, i.e. the middle of its longest segment.
+fn route_label_pos(positions: &[egui::Pos2]) -> egui::Pos2 {
+    positions.windows(2)
+        .max_by(|a, b| a[0].distance(a[1]).total_cmp(&b[0].distance(b[1])))
+        .map_or(positions[0], |pair| pair[0].lerp(pair[1], 0.5))
+}
+
+/// Draw a wire's label, on a background so that it can be read over the wires and grid.
+/// Returns the label's response, so that it can be double-clicked to edit.
+fn draw_wire_label(ui: &mut egui::Ui, pos: egui::Pos2, text: &str, color: egui::Color32) -> Response {
+    let galley = ui.painter().layout_no_wrap(text.to_string(), egui::FontId::proportional(12.0), color);
+    let rect = egui::Rect::from_center_size(pos, galley.size() + egui::vec2(6.0, 2.0));
+    let response = ui.interact(rect, ui.id().with(("wire_label", text)), egui::Sense::click());
+    let mut painter = ui.painter().clone();
+    painter.set_layer_id(egui::LayerId::new(egui::Order::Middle, painter.layer_id().id));
+    painter.rect(rect, 3.0, ui.visuals().extreme_bg_color, egui::Stroke::new(1.0, color));
+    painter.galley(rect.min + egui::vec2(3.0, 1.0), galley, color);
+    response
+}
+
 /// Given a start and end position, draw a line representing the connection.
 /// Return a response that indicates if the pointer is nearby, i.e. hovering, over the line.
 /// Also handles click events.
 fn draw_connection(ctx: &egui::Context, ui: &mut egui::Ui, src_pos: egui::Pos2, dst_pos: egui::Pos2, color: egui::Color32) -> Response {
+    draw_cable(ctx, ui, src_pos, dst_pos, egui::Stroke { width: 2.0, color })
+}
+
+/// Draw the wires of a bus as a single cable. The pins on each board are gathered into a hub,
+/// with a thin branch fanning out to each pin, and a thick trunk joins the hubs.
+fn draw_bus(ctx: &egui::Context, ui: &mut egui::Ui, groups: &[Vec<egui::Pos2>], color: egui::Color32) -> Response {
+    let center = |positions: &[egui::Pos2]| {
+        (positions.iter().fold(egui::Vec2::ZERO, |acc, pos| acc + pos.to_vec2()) / positions.len() as f32).to_pos2()
+    };
+    let mut hubs: Vec<egui::Pos2> = groups.iter().map(|group| center(group)).collect();
+    let trunk_stroke = egui::Stroke { width: 5.0, color };
+    let response = if hubs.len() == 2 {
+        // pull the ends of the trunk away from the pins, so that the branches are visible
+        const BRANCH_LENGTH: f32 = 20.0;
+        let offset = egui::Vec2::X * BRANCH_LENGTH * (hubs[1].x - hubs[0].x).signum();
+        hubs = vec![hubs[0] + offset, hubs[1] - offset];
+        draw_cable(ctx, ui, hubs[0], hubs[1], trunk_stroke)
+    } else {
+        draw_net(ctx, ui, &hubs, trunk_stroke)
+    };
+
+    let mut painter = ui.painter().clone();
+    let mut layer_id = painter.layer_id();
+    layer_id.order = egui::Order::Middle;
+    painter.set_layer_id(layer_id);
+    let branch_stroke = egui::Stroke { width: 1.5, color };
+    for (group, hub) in groups.iter().zip(hubs.iter()) {
+        for pos in group {
+            painter.line_segment([*pos, *hub], branch_stroke);
+        }
+    }
+
+    response
+}
+
+/// Draw a connection that joins more than two pins as a trunk with branches. The trunk runs
+/// horizontally through the middle of the pins, and a vertical branch drops from it to each pin.
+fn draw_net(ctx: &egui::Context, ui: &mut egui::Ui, positions: &[egui::Pos2], stroke: egui::Stroke) -> Response {
+    let trunk_y = positions.iter().map(|pos| pos.y).sum::<f32>() / positions.len() as f32;
+    let min_x = positions.iter().map(|pos| pos.x).fold(f32::INFINITY, f32::min);
+    let max_x = positions.iter().map(|pos| pos.x).fold(f32::NEG_INFINITY, f32::max);
+    let mut segments = vec![[egui::Pos2::new(min_x, trunk_y), egui::Pos2::new(max_x, trunk_y)]];
+    for pos in positions {
+        segments.push([*pos, egui::Pos2::new(pos.x, trunk_y)]);
+    }
+
+    let response = interact_with_segments(ctx, ui, &segments);
+    let mut stroke = stroke;
+    if response.hovered() {
+        stroke.color = stroke.color.gamma_multiply(0.5);
+    }
+    let mut painter = ui.painter().clone();
+    let mut layer_id = painter.layer_id();
+    layer_id.order = egui::Order::Middle;
+    painter.set_layer_id(layer_id);
+    for segment in segments {
+        painter.line_segment(segment, stroke);
+    }
+    // mark the junction of each branch with the trunk
+    for pos in positions {
+        painter.circle_filled(egui::Pos2::new(pos.x, trunk_y), stroke.width * 1.5, stroke.color);
+    }
+
+    response
+}
+
+/// Draw an orthogonal route through the given positions, as a path of straight segments.
+fn draw_route(ctx: &egui::Context, ui: &mut egui::Ui, positions: &[egui::Pos2], stroke: egui::Stroke) -> Response {
+    let segments: Vec<[egui::Pos2; 2]> = positions.windows(2).map(|pair| [pair[0], pair[1]]).collect();
+    let response = interact_with_segments(ctx, ui, &segments);
+    let mut stroke = stroke;
+    if response.hovered() {
+        stroke.color = stroke.color.gamma_multiply(0.5);
+    }
+    let mut painter = ui.painter().clone();
+    let mut layer_id = painter.layer_id();
+    layer_id.order = egui::Order::Middle;
+    painter.set_layer_id(layer_id);
+    painter.add(egui::epaint::PathShape::line(positions.to_vec(), stroke));
+    response
+}
+
+/// Detect hovers and clicks near any of the line segments of a wire.
+fn interact_with_segments(ctx: &egui::Context, ui: &mut egui::Ui, segments: &[[egui::Pos2; 2]]) -> Response {
+    let positions: Vec<egui::Pos2> = segments.iter().flatten().copied().collect();
+    let mut response = ui.allocate_rect(egui::Rect::from_points(&positions), egui::Sense::click());
+    response.hovered = false;
+    response.clicked = false;
+    if let Some(cursor_pos) = ctx.pointer_interact_pos() {
+        const THRESH: f32 = 12.0;
+        if segments.iter().any(|[a, b]| distance_to_segment(cursor_pos, *a, *b) < THRESH) {
+            response.hovered = true;
+            // using any_click allows clicks, context menu, etc to be handled.
+            if ctx.input(|i| i.pointer.any_click()) {
+                response.clicked = true;
+            }
+            response.rect = egui::Rect::from_center_size(cursor_pos, egui::Vec2::new(THRESH, THRESH));
+        }
+    }
+    response
+}
+
+/// The distance from a point to the line segment between a and b.
+fn distance_to_segment(p: egui::Pos2, a: egui::Pos2, b: egui::Pos2) -> f32 {
+    let ab = b - a;
+    let t = match ab.length_sq() {
+        len_sq if len_sq > 0.0 => ((p - a).dot(ab) / len_sq).clamp(0.0, 1.0),
+        _ => 0.0,
+    };
+    p.distance(a + ab * t)
+}
+
+/// Draw a single curve between two positions, and detect hovers and clicks on it.
+fn draw_cable(ctx: &egui::Context, ui: &mut egui::Ui, src_pos: egui::Pos2, dst_pos: egui::Pos2, stroke: egui::Stroke) -> Response {
 
     let mut response = ui.allocate_rect(egui::Rect::from_points(&[src_pos, dst_pos]), egui::Sense::click());
     // these are public fields, but not exposed in egui documentation!
     response.hovered = false;
     response.clicked = false;
 
-    let mut connection_stroke = egui::Stroke { width: 2.0, color };
+    let mut connection_stroke = stroke;
 
     let mid_x = src_pos.x + (dst_pos.x - src_pos.x) / 2.0;
     // let mid_y = src_pos.y + (dst_pos.y - src_pos.y) / 2.0;
@@ -792,3 +2251,65 @@ fn draw_connection(ctx: &egui::Context, ui: &mut egui::Ui, src_pos: egui::Pos2,
     response
 
 }
+
+/// A dropdown of the boards in the system, for a cell of the connection table. Moving the
+/// endpoint to another board keeps its pin if that board has a pin with the same SVG id, and
+/// otherwise takes the first pin of the board's pinout. Returns the board and pin picked.
+fn endpoint_board_cell(ui: &mut egui::Ui, id: impl std::hash::Hash, endpoint: &system::Endpoint, boards: &[board::Board], board_pins: &[Vec<board::pinout::Pin>]) -> Option<(board::Board, board::pinout::Pin)> {
+    let mut picked = None;
+    egui::ComboBox::from_id_source(id).selected_text(endpoint.board.get_designator()).show_ui(ui, |ui| {
+        for (board, pins) in boards.iter().zip(board_pins) {
+            let pin = pins.iter().find(|p| p.svg_id == endpoint.pin.svg_id).or(pins.first()).cloned();
+            let selected = *board == endpoint.board;
+            let label = ui.add_enabled(pin.is_some(), egui::SelectableLabel::new(selected, board.get_designator()));
+            if let Some(pin) = pin.filter(|_| label.clicked() && !selected) {
+                picked = Some((board.clone(), pin));
+            }
+        }
+    });
+    picked
+}
+
+/// A dropdown of the pins in the pinout of an endpoint's board, for a cell of the connection
+/// table. Returns the pin picked.
+fn endpoint_pin_cell(ui: &mut egui::Ui, id: impl std::hash::Hash, endpoint: &system::Endpoint, boards: &[board::Board], board_pins: &[Vec<board::pinout::Pin>]) -> Option<(board::Board, board::pinout::Pin)> {
+    let pins = boards.iter().position(|board| *board == endpoint.board).map_or(&[][..], |idx| &board_pins[idx]);
+    let mut picked = None;
+    egui::ComboBox::from_id_source(id).selected_text(endpoint.pin.name()).show_ui(ui, |ui| {
+        for pin in pins {
+            let selected = pin.svg_id == endpoint.pin.svg_id;
+            if ui.selectable_label(selected, pin.to_string()).clicked() && !selected {
+                picked = Some((endpoint.board.clone(), pin.clone()));
+            }
+        }
+    });
+    picked
+}
+
+/// Open the pin picker, which makes a connection from the keyboard.
+fn open_pin_picker(ctx: &egui::Context) {
+    let id = egui::Id::new("pin_picker");
+    if ctx.data(|data| data.get_temp::<picker::PickerState>(id)).is_none() {
+        ctx.data_mut(|data| data.insert_temp(id, picker::PickerState::default()));
+    }
+}
+
+/// How far the arrow keys pressed this frame nudge the selected boards, in mm. Each press moves
+/// them one grid step, or ten while holding shift. Also returns whether the keys are being held
+/// down, i.e. whether every press is a key repeat.
+fn nudge_from_keys(ctx: &egui::Context) -> Option<(egui::Vec2, bool)> {
+    let arrows = [(Key::ArrowLeft, egui::vec2(-1.0, 0.0)), (Key::ArrowRight, egui::vec2(1.0, 0.0)), (Key::ArrowUp, egui::vec2(0.0, -1.0)), (Key::ArrowDown, egui::vec2(0.0, 1.0))];
+    let held = ctx.input(|i| i.events.iter().all(|event| match event {
+        egui::Event::Key { key, pressed: true, repeat, .. } if arrows.iter().any(|(arrow, _)| arrow == key) => *repeat,
+        _ => true,
+    }));
+    let mut nudge = egui::Vec2::ZERO;
+    for (key, direction) in arrows {
+        if ctx.input_mut(|i| i.consume_key(Modifiers::SHIFT, key)) {
+            nudge += direction * 10.0 * canvas::SNAP_SPACING;
+        } else if ctx.input_mut(|i| i.consume_key(Modifiers::NONE, key)) {
+            nudge += direction * canvas::SNAP_SPACING;
+        }
+    }
+    (nudge != egui::Vec2::ZERO).then_some((nudge, held))
+}
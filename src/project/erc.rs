@@ -0,0 +1,195 @@
+//! Title: Iron Coder Project Module - Electrical Rule Check
+//! Description: This module checks the connections of a System against the electrical
+//! attributes of the pins that they join, i.e. logic levels, output contention, pull-ups, etc.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::board::Board;
+use crate::board::pinout::{InterfaceType, Pin, PinFunction};
+use crate::board::validation::Severity;
+
+use super::system::{Connection, System};
+
+/// A single problem found by the electrical rule check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErcViolation {
+    pub severity: Severity,
+    /// The names of the connections involved in the problem
+    pub connections: Vec<String>,
+    pub message: String,
+}
+
+impl fmt::Display for ErcViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.severity, self.message, self.connections.join(", "))
+    }
+}
+
+/// A net is a set of pins that are electrically the same node, because they are joined
+/// by one or more connections.
+struct Net<'a> {
+    pins: Vec<(&'a Board, &'a Pin)>,
+    connections: Vec<&'a Connection>,
+}
+
+impl Net<'_> {
+    fn connection_names(&self) -> Vec<String> {
+        self.connections.iter().map(|c| c.name.clone()).collect()
+    }
+
+    /// Whether any of the connections on the net are (or are muxed to) the given interface type.
+    fn carries(&self, iface_type: InterfaceType) -> bool {
+        self.connections.iter().any(|c| {
+            c.interface_mapping.interface.iface_type == iface_type
+                || c.endpoints.iter().any(|e| e.function.as_ref().is_some_and(|f| f.iface_type == iface_type))
+        })
+    }
+}
+
+fn describe(board: &Board, pin: &Pin) -> String {
+    format!("{} {}", board.get_designator(), pin.name())
+}
+
+fn find_root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Group the pins of the system's connections into nets.
+fn find_nets(system: &System) -> Vec<Net<'_>> {
+    let mut index: HashMap<(&Board, &str), usize> = HashMap::new();
+    let mut pins: Vec<(&Board, &Pin)> = Vec::new();
+    let mut parent: Vec<usize> = Vec::new();
+    for connection in system.connections.iter() {
+        let indices: Vec<usize> = connection.endpoints.iter().map(|endpoint| {
+            *index.entry((&endpoint.board, endpoint.pin.svg_id.as_str())).or_insert_with(|| {
+                pins.push((&endpoint.board, &endpoint.pin));
+                parent.push(parent.len());
+                parent.len() - 1
+            })
+        }).collect();
+        for pair in indices.windows(2) {
+            let (a, b) = (find_root(&mut parent, pair[0]), find_root(&mut parent, pair[1]));
+            parent[b] = a;
+        }
+    }
+
+    // collect the nets in the order that their first pin appears, to keep the output stable
+    let mut nets: Vec<Net> = Vec::new();
+    let mut net_of_root: HashMap<usize, usize> = HashMap::new();
+    for (i, pin) in pins.iter().enumerate() {
+        let root = find_root(&mut parent, i);
+        let net_idx = *net_of_root.entry(root).or_insert_with(|| {
+            nets.push(Net { pins: Vec::new(), connections: Vec::new() });
+            nets.len() - 1
+        });
+        nets[net_idx].pins.push(*pin);
+    }
+    for connection in system.connections.iter().filter(|c| !c.endpoints.is_empty()) {
+        let first = &connection.endpoints[0];
+        let i = index[&(&first.board, first.pin.svg_id.as_str())];
+        let root = find_root(&mut parent, i);
+        nets[net_of_root[&root]].connections.push(connection);
+    }
+    nets
+}
+
+fn check_net(net: &Net, violations: &mut Vec<ErcViolation>) {
+    let mut violation = |severity: Severity, message: String| {
+        violations.push(ErcViolation { severity, connections: net.connection_names(), message });
+    };
+
+    // more than one pin actively driving the net
+    let drivers: Vec<String> = net.pins.iter()
+        .filter(|(_, pin)| pin.electrical.is_driver())
+        .map(|(board, pin)| describe(board, pin))
+        .collect();
+    if drivers.len() > 1 {
+        violation(Severity::Error, format!("more than one output drives the same net: {}", drivers.join(", ")));
+    }
+
+    // a pin driven above the voltage that it can tolerate
+    for (driver_board, driver) in net.pins.iter().filter(|(_, pin)| pin.electrical.can_drive()) {
+        let Some(voltage) = driver.electrical.voltage else { continue };
+        for (receiver_board, receiver) in net.pins.iter().filter(|(_, pin)| pin.electrical.is_receiver()) {
+            if std::ptr::eq(*driver, *receiver) {
+                continue;
+            }
+            if let Some(max_voltage) = receiver.electrical.max_input_voltage() {
+                if voltage > max_voltage {
+                    violation(Severity::Error, format!(
+                        "{} drives {} into {}, which only tolerates {}",
+                        voltage, describe(driver_board, driver), describe(receiver_board, receiver), max_voltage,
+                    ));
+                }
+            }
+        }
+    }
+
+    // a power supply shorted to ground
+    let is_ground = |pin: &Pin| pin.functions.iter().any(|f| f.iface_type == InterfaceType::GND);
+    if net.pins.iter().any(|(_, pin)| is_ground(pin)) {
+        for (board, pin) in net.pins.iter().filter(|(_, pin)| pin.electrical.power_output) {
+            violation(Severity::Error, format!("power output {} is shorted to ground", describe(board, pin)));
+        }
+    }
+
+    // I2C lines need a pull-up resistor somewhere on the bus
+    if net.carries(InterfaceType::I2C) && !net.pins.iter().any(|(_, pin)| pin.electrical.pull_up) {
+        let pins: Vec<String> = net.pins.iter().map(|(board, pin)| describe(board, pin)).collect();
+        violation(Severity::Warning, format!("I2C net {} has no pull-up resistor", pins.join(", ")));
+    }
+}
+
+/// Figure out whether a pin is the TX or RX end of a UART, either from its chosen function,
+/// or from its name.
+fn uart_role(pin: &Pin, function: Option<&PinFunction>) -> Option<&'static str> {
+    let role = match function {
+        Some(f) if f.iface_type == InterfaceType::UART => {
+            let signal = f.signal.as_deref()?;
+            InterfaceType::UART.pin_roles().iter().find(|role| role.eq_ignore_ascii_case(signal)).copied()
+        },
+        _ => pin.role_in(InterfaceType::UART),
+    };
+    role.filter(|role| matches!(*role, "TX" | "RX"))
+}
+
+fn check_connection(connection: &Connection, violations: &mut Vec<ErcViolation>) {
+    let is_uart = connection.interface_mapping.interface.iface_type == InterfaceType::UART
+        || connection.endpoints.iter().any(|e| e.function.as_ref().is_some_and(|f| f.iface_type == InterfaceType::UART));
+    if !is_uart {
+        return;
+    }
+    let roles: Vec<&'static str> = connection.endpoints.iter()
+        .filter_map(|e| uart_role(&e.pin, e.function.as_ref()))
+        .collect();
+    if let Some(role) = roles.iter().find(|role| roles.iter().filter(|r| r == role).count() > 1) {
+        violations.push(ErcViolation {
+            severity: Severity::Error,
+            connections: vec![connection.name.clone()],
+            message: format!("UART {} is wired to {}, but TX should be wired to RX", role, role),
+        });
+    }
+}
+
+/// Run the electrical rule check over the system. Pin function conflicts are included as errors.
+pub fn check(system: &System) -> Vec<ErcViolation> {
+    let mut violations: Vec<ErcViolation> = system.function_conflicts().into_iter().map(|conflict| {
+        ErcViolation {
+            severity: Severity::Error,
+            connections: conflict.connections,
+            message: conflict.message,
+        }
+    }).collect();
+    for net in find_nets(system).iter() {
+        check_net(net, &mut violations);
+    }
+    for connection in system.connections.iter() {
+        check_connection(connection, &mut violations);
+    }
+    violations
+}
@@ -0,0 +1,104 @@
+//! Title: Iron Coder Project Module - History
+//! Description: This module contains the undo/redo stack of the system editor. Each command
+//! records the system before and after an edit, as a Snapshot in the same reference form that
+//! the project file uses, so commands are cheap to keep around. The history keeps the system as
+//! it was after the last command, which is the "before" of the next one, so a snapshot is only
+//! taken once an edit has been made.
+
+use super::system::Snapshot;
+
+/// The number of commands that can be undone.
+const HISTORY_LIMIT: usize = 100;
+
+/// A single edit of the system, which can be undone and redone.
+#[derive(Clone, Debug)]
+struct Command {
+    /// A short description of the edit, e.g. "remove oled1"
+    description: String,
+    before: Snapshot,
+    after: Snapshot,
+    /// Whether the edit is still going on, e.g. a board that's being dragged. Further changes
+    /// with the same description are folded into an open command.
+    open: bool,
+}
+
+/// The undo/redo stack of the system editor.
+#[derive(Default)]
+pub struct History {
+    undo_stack: Vec<Command>,
+    redo_stack: Vec<Command>,
+    /// The system as it was after the last command, or as it was loaded
+    current: Snapshot,
+}
+
+impl History {
+    /// Record an edit, which made the system into `after`. Everything that changed since the
+    /// last command is part of the edit. If `continuing` is set, the edit is part of an ongoing
+    /// change, like dragging a board or typing a name, and is folded into the previous command
+    /// when that command has the same description and hasn't been closed yet.
+    pub fn record(&mut self, description: &str, after: Snapshot, continuing: bool) {
+        let before = std::mem::replace(&mut self.current, after.clone());
+        if before == after {
+            return;
+        }
+        self.redo_stack.clear();
+        if let Some(last) = self.undo_stack.last_mut() {
+            if last.open && continuing && last.description == description {
+                last.after = after;
+                return;
+            }
+            last.open = false;
+        }
+        self.undo_stack.push(Command {
+            description: description.to_string(),
+            before,
+            after,
+            open: continuing,
+        });
+        if self.undo_stack.len() > HISTORY_LIMIT {
+            self.undo_stack.remove(0);
+        }
+    }
+
+    /// End the ongoing change, so that the next edit becomes a command of its own.
+    pub fn close(&mut self) {
+        if let Some(last) = self.undo_stack.last_mut() {
+            last.open = false;
+        }
+    }
+
+    /// Undo the last command, and return the system as it was before it.
+    pub fn undo(&mut self) -> Option<Snapshot> {
+        let mut command = self.undo_stack.pop()?;
+        command.open = false;
+        self.current = command.before.clone();
+        self.redo_stack.push(command);
+        Some(self.current.clone())
+    }
+
+    /// Redo the last undone command, and return the system as it was after it.
+    pub fn redo(&mut self) -> Option<Snapshot> {
+        let command = self.redo_stack.pop()?;
+        self.current = command.after.clone();
+        self.undo_stack.push(command);
+        Some(self.current.clone())
+    }
+
+    /// The description of the command that would be undone next.
+    pub fn undo_description(&self) -> Option<&str> {
+        self.undo_stack.last().map(|c| c.description.as_str())
+    }
+
+    /// The description of the command that would be redone next.
+    pub fn redo_description(&self) -> Option<&str> {
+        self.redo_stack.last().map(|c| c.description.as_str())
+    }
+
+    /// Forget all of the commands, e.g. when a different project is loaded, and start again
+    /// from the system as it is now.
+    pub fn clear(&mut self, current: Snapshot) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.current = current;
+    }
+}
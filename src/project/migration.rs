@@ -0,0 +1,220 @@
+//! Title: Iron Coder Project Module - Migration
+//! Description: This module upgrades project files written by older versions of Iron Coder.
+//! Each project file records the version of its schema, and each migration function upgrades
+//! a project file from one version to the next, noting what it changed along the way.
+
+use log::info;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::board::Board;
+use super::Project;
+use super::system::{Connection, Placement, System};
+
+/// The version of the project file schema written by this version of Iron Coder.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Project files from before the schema was versioned don't record a version.
+const UNVERSIONED: u32 = 1;
+
+/// A migration upgrades a project file to the next version of the schema, and returns a
+/// description of each change it made.
+type Migration = fn(&mut toml::Table) -> Result<Vec<String>, MigrationError>;
+
+/// The chain of migrations, where `MIGRATIONS[n]` upgrades a project file from version
+/// `n + 1` to version `n + 2`.
+const MIGRATIONS: &[Migration] = &[
+    migrate_v1_to_v2,
+    migrate_v2_to_v3,
+];
+
+#[non_exhaustive]
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The project file was written by a newer version of Iron Coder.
+    NewerSchemaError(u32),
+    /// The project file couldn't be upgraded, with a description of why.
+    InvalidProjectError(String),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MigrationError::NewerSchemaError(version) => write!(
+                f, "the project file is version {}, but this version of Iron Coder only reads up to version {}", version, SCHEMA_VERSION,
+            ),
+            MigrationError::InvalidProjectError(message) => write!(f, "the project file couldn't be upgraded: {}", message),
+        }
+    }
+}
+
+/// What happened when a project file written by an older Iron Coder was loaded.
+#[derive(Clone, Debug)]
+pub struct MigrationReport {
+    /// The schema version that the project file was written with
+    pub from_version: u32,
+    /// Where the original project file was copied to, if it could be
+    pub backup: Option<PathBuf>,
+    /// A description of each change made to the project
+    pub changes: Vec<String>,
+}
+
+/// A project as written to its project file, along with the version of the schema.
+#[derive(Serialize)]
+pub struct ProjectFile<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    project: &'a Project,
+}
+
+impl<'a> ProjectFile<'a> {
+    pub fn new(project: &'a Project) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            project,
+        }
+    }
+}
+
+/// Return the schema version of a project file.
+pub fn schema_version(table: &toml::Table) -> u32 {
+    table.get("schema_version")
+        .and_then(|v| v.as_integer())
+        .and_then(|v| u32::try_from(v).ok())
+        .unwrap_or(UNVERSIONED)
+}
+
+/// Upgrade a project file to the current schema version, one migration at a time. Returns
+/// the changes that were made, which is empty if the project file was already up to date.
+pub fn migrate(table: &mut toml::Table) -> Result<Vec<String>, MigrationError> {
+    let version = schema_version(table);
+    if version > SCHEMA_VERSION {
+        return Err(MigrationError::NewerSchemaError(version));
+    }
+    let mut changes = Vec::new();
+    for (idx, migration) in MIGRATIONS.iter().enumerate().skip((version.max(UNVERSIONED) - UNVERSIONED) as usize) {
+        let from = idx as u32 + UNVERSIONED;
+        info!("migrating project file from schema version {} to {}", from, from + 1);
+        changes.extend(migration(table)?);
+        table.insert("schema_version".to_string(), toml::Value::Integer(from as i64 + 1));
+    }
+    Ok(changes)
+}
+
+/// Copy a project file that's about to be upgraded next to the original, e.g. to
+/// `.ironcoder.toml.v1.bak`, and return the path of the copy.
+pub fn back_up(project_file: &Path, version: u32) -> io::Result<PathBuf> {
+    let mut backup = project_file.as_os_str().to_owned();
+    backup.push(format!(".v{}.bak", version));
+    let backup = PathBuf::from(backup);
+    fs::copy(project_file, &backup)?;
+    Ok(backup)
+}
+
+/// A system as written by earlier versions of Iron Coder, with a full copy of each board.
+#[derive(Deserialize)]
+struct SystemV1 {
+    #[serde(default)]
+    main_board: Option<Board>,
+    #[serde(default)]
+    peripheral_boards: Vec<Board>,
+    #[serde(default)]
+    connections: Vec<Connection>,
+}
+
+/// Version 2 saves references to the library boards rather than copies of them, gives each
+/// board an instance with a designator, and saves connections as nets of any number of pins.
+fn migrate_v1_to_v2(table: &mut toml::Table) -> Result<Vec<String>, MigrationError> {
+    let Some(system) = table.remove("system") else {
+        return Ok(Vec::new());
+    };
+    let num_pin_to_pin = system.get("connections")
+        .and_then(|c| c.as_array())
+        .map_or(0, |c| c.iter().filter(|c| c.get("start_board").is_some()).count());
+    let SystemV1 { main_board, peripheral_boards, connections } = system.try_into().map_err(|e: toml::de::Error| {
+        MigrationError::InvalidProjectError(format!("couldn't read the system: {}", e.message()))
+    })?;
+
+    let mut system = System {
+        main_board,
+        peripheral_boards,
+        connections,
+        ..Default::default()
+    };
+    let before = system.get_all_boards();
+    system.assign_instances();
+
+    let mut changes = vec!["Boards are now saved as references to the board library, rather than as copies.".to_string()];
+    for (old, new) in before.iter().zip(system.get_all_boards().iter()) {
+        if old.get_instance().is_none() {
+            changes.push(format!("The {} board is now called {}.", old.get_name(), new.get_designator()));
+        }
+    }
+    if num_pin_to_pin > 0 {
+        changes.push(format!("{} pin-to-pin connections were converted to nets.", num_pin_to_pin));
+    }
+
+    let mut system = toml::Value::try_from(&system).map_err(|e| {
+        MigrationError::InvalidProjectError(format!("couldn't write the system: {}", e))
+    })?;
+    // version 2 didn't save where boards are drawn, so they're laid out by the next migration
+    if let Some(system) = system.as_table_mut() {
+        let peripheral_boards = system.get_mut("peripheral_boards").and_then(|b| b.as_array_mut()).map(|b| b.iter_mut());
+        for board in peripheral_boards.into_iter().flatten() {
+            if let Some(board) = board.as_table_mut() {
+                board.remove("placement");
+            }
+        }
+        if let Some(board) = system.get_mut("main_board").and_then(|b| b.as_table_mut()) {
+            board.remove("placement");
+        }
+    }
+    table.insert("system".to_string(), system);
+    Ok(changes)
+}
+
+/// Version 3 saves where each board is drawn, the viewport of the system editor, and the
+/// route, color and stacking of each connection. All of these have defaults, except that
+/// boards would all be drawn at the canvas origin, so boards without a placement are cascaded
+/// as if they'd just been added to the project.
+fn migrate_v2_to_v3(table: &mut toml::Table) -> Result<Vec<String>, MigrationError> {
+    let Some(system) = table.get_mut("system").and_then(|s| s.as_table_mut()) else {
+        return Ok(Vec::new());
+    };
+    // the main board comes first, as it's the first board added to a project
+    let mut boards: Vec<&mut toml::Value> = Vec::new();
+    let mut peripheral_boards = None;
+    for (key, value) in system.iter_mut() {
+        match key.as_str() {
+            "main_board" => boards.insert(0, value),
+            "peripheral_boards" => peripheral_boards = value.as_array_mut(),
+            _ => (),
+        }
+    }
+    boards.extend(peripheral_boards.into_iter().flatten());
+    let mut num_boards = 0;
+    for (idx, board) in boards.into_iter().enumerate() {
+        let Some(board) = board.as_table_mut() else {
+            return Err(MigrationError::InvalidProjectError("a board isn't a table".to_string()));
+        };
+        // a board that already has a placement, e.g. in a hand-edited project file, keeps it
+        if board.contains_key("placement") {
+            continue;
+        }
+        num_boards += 1;
+        let offset = 10.0 * idx as f32;
+        let placement = toml::Value::try_from(Placement { x: offset, y: offset, ..Default::default() }).map_err(|e| {
+            MigrationError::InvalidProjectError(format!("couldn't write a placement: {}", e))
+        })?;
+        board.insert("placement".to_string(), placement);
+    }
+    if num_boards == 0 {
+        return Ok(Vec::new());
+    }
+    Ok(vec![format!("{} boards were laid out on the system editor canvas.", num_boards)])
+}
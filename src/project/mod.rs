@@ -13,7 +13,8 @@ use rfd::FileDialog;
 
 use serde::{Serialize, Deserialize};
 
-use crate::board::Board;
+use crate::board::{self, Board, BoardRoot, BoardRootKind};
+use crate::board::loader::{self, BoardLoader};
 use crate::app::code_editor::CodeEditor;
 
 pub mod display;
@@ -22,6 +23,15 @@ use display::ProjectViewType;
 pub mod egui_helpers;
 
 mod system;
+mod breadboard;
+mod canvas;
+mod erc;
+mod history;
+mod migration;
+mod picker;
+mod routing;
+mod selection;
+mod table;
 mod test;
 
 use system::System;
@@ -41,6 +51,16 @@ pub enum ProjectIOError {
     NoProjectDirectory,
     FilesystemError,
     LoadToTomlError,
+    MigrationError(migration::MigrationError),
+}
+
+impl std::fmt::Display for ProjectIOError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ProjectIOError::MigrationError(e) => write!(f, "{}", e),
+            _ => write!(f, "{:?}", self),
+        }
+    }
 }
 
 /// A Project represents the highest level of Iron Coder, which contains
@@ -59,10 +79,30 @@ pub struct Project {
     #[serde(skip)]
     receiver: Option<std::sync::mpsc::Receiver<String>>,
     current_view: ProjectViewType,
+    /// The boards available to this project, i.e. the app-wide library boards with
+    /// the boards from the project-local library layered on top
     #[serde(skip)]
     pub known_boards: Vec<Board>,
+    /// The boards from the app-wide board libraries
+    #[serde(skip)]
+    library_boards: Vec<Board>,
+    /// The directory that the decoded project-local boards are cached in
+    #[serde(skip)]
+    board_cache_dir: Option<PathBuf>,
+    /// Decodes the project-local boards in the background
+    #[serde(skip)]
+    board_loader: Option<BoardLoader>,
     #[serde(skip)]
     repo: Option<Repository>,
+    /// What was changed to upgrade the project file, if it was written by an older Iron Coder
+    #[serde(skip)]
+    migration_report: Option<migration::MigrationReport>,
+    /// The undo/redo history of the system editor
+    #[serde(skip)]
+    history: history::History,
+    /// The boards and connections selected in the system editor
+    #[serde(skip)]
+    selection: selection::Selection,
 }
 
 // backend functionality for Project struct
@@ -96,43 +136,214 @@ impl Project {
     }
 
     pub fn add_board(&mut self, board: Board) {
+        // new boards are cascaded down and to the right of the canvas origin, so they don't
+        // land exactly on top of each other
+        let offset = 10.0 * self.system.get_all_boards().len() as f32;
+        let placement = system::Placement { x: offset, y: offset, ..Default::default() };
         match board.is_main_board() {
             true => {
                 if self.has_main_board() {
                     info!("project already contains a main board! aborting.");
                     return;
                 } else {
-                    self.system.main_board = Some(board.clone());
+                    let board = self.system.place_board(&board);
+                    self.system.set_placement(&board, placement);
+                    let description = format!("add {}", board.get_designator());
+                    self.system.main_board = Some(board);
+                    self.record_edit(&description, false);
                 }
             },
             false => {
-                // don't duplicate a board
-                if self.system.peripheral_boards.contains(&board) {
-                    info!("project <{}> already contains board <{:?}>", self.name, board);
-                    self.terminal_buffer += "project already contains that board\n";
-                    return;
-                } else {
-                    self.system.peripheral_boards.push(board.clone());
+                // a project can contain any number of the same peripheral, each its own instance
+                let board = self.system.place_board(&board);
+                info!("adding board <{}> to project <{}> as {}", board.get_name(), self.name, board.get_designator());
+                self.system.set_placement(&board, placement);
+                let description = format!("add {}", board.get_designator());
+                self.system.peripheral_boards.push(board);
+                self.record_edit(&description, false);
+            }
+        }
+    }
+
+    /// Record an edit of the system in the undo history. Every change made to the system since
+    /// the last recorded edit is part of this one.
+    pub fn record_edit(&mut self, description: &str, continuing: bool) {
+        self.history.record(description, self.system.snapshot(), continuing);
+    }
+
+    /// Undo the last edit of the system, if there is one.
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.history.undo() {
+            self.restore(&snapshot);
+        }
+    }
+
+    /// Redo the last undone edit of the system, if there is one.
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.history.redo() {
+            self.restore(&snapshot);
+        }
+    }
+
+    /// Copy the selected boards, along with the connections between them, as clipboard text.
+    pub fn copy_selection(&self) -> Option<String> {
+        let boards = self.selection.boards(&self.system);
+        if boards.is_empty() {
+            return None;
+        }
+        selection::to_clipboard(&self.system.fragment(&boards))
+    }
+
+    /// Paste boards and connections copied from this or another project, offset from where
+    /// they were copied from so they don't land on top of the originals. The pasted boards
+    /// become the selection. Returns false if the text isn't a system fragment.
+    pub fn paste(&mut self, text: &str) -> bool {
+        let Some(fragment) = selection::from_clipboard(text) else {
+            return false;
+        };
+        self.paste_fragment(&fragment);
+        true
+    }
+
+    /// Make a copy of the selected boards and the connections between them.
+    pub fn duplicate_selection(&mut self) {
+        let boards = self.selection.boards(&self.system);
+        if !boards.is_empty() {
+            let fragment = self.system.fragment(&boards);
+            self.paste_fragment(&fragment);
+        }
+    }
+
+    /// Remove the selected boards and connections from the system.
+    pub fn delete_selection(&mut self) {
+        self.selection.delete(&mut self.system);
+    }
+
+    fn paste_fragment(&mut self, fragment: &System) {
+        const PASTE_OFFSET: f32 = 10.0;
+        let pasted = self.system.paste(fragment, PASTE_OFFSET, PASTE_OFFSET);
+        self.load_board_resources();
+        self.selection.clear();
+        for board in pasted.iter() {
+            self.selection.add_board(board);
+        }
+        for connection in self.system.connections.iter() {
+            if connection.endpoints.iter().all(|e| pasted.contains(&e.board)) {
+                self.selection.add_connection(&connection.name);
+            }
+        }
+    }
+
+    /// Put the system back the way it was when the snapshot was taken, and sync the board
+    /// assets since snapshots only refer to the library boards.
+    fn restore(&mut self, snapshot: &system::Snapshot) {
+        self.system = self.system.restored(snapshot);
+        self.load_board_resources();
+        self.selection.retain(&self.system);
+    }
+
+    /// The project-local board library, i.e. the `boards/` folder in the project directory
+    pub fn local_board_root(&self) -> Option<BoardRoot> {
+        self.location.as_ref().map(|location| {
+            BoardRoot::new(BoardRootKind::Project, location.join("boards"))
+        })
+    }
+
+    /// Set the boards from the app-wide board libraries. The project-local boards will be
+    /// layered on top of these to form the list of known boards, and their images and BSPs are
+    /// cached in `cache_dir` if it's given.
+    pub fn set_library_boards(&mut self, boards: Vec<Board>, cache_dir: Option<PathBuf>) {
+        self.library_boards = boards;
+        self.board_cache_dir = cache_dir;
+        self.refresh_known_boards();
+    }
+
+    /// Update the library boards with the given library ids as they're decoded in the
+    /// background, without reloading the project-local boards. Those boards are replaced by their
+    /// versions in `boards`, or dropped if they couldn't be decoded, and so are the known boards
+    /// and the system's boards that came from them.
+    pub fn update_library_boards(&mut self, boards: &[Board], library_ids: &[String]) {
+        for library_id in library_ids {
+            let board = boards.iter().find(|b| b.library_id() == *library_id);
+            let library = self.library_boards.iter().position(|b| b.library_id() == *library_id);
+            // a project-local board is layered over the library board, so it stays known
+            let known = self.known_boards.iter().position(|k| {
+                k.library_id() == *library_id && k.get_library_root().map(|root| root.kind) != Some(BoardRootKind::Project)
+            });
+            for (list, idx) in [(&mut self.library_boards, library), (&mut self.known_boards, known)] {
+                match (idx, board) {
+                    (Some(idx), Some(board)) => list[idx] = board.clone(),
+                    (Some(idx), None) => {
+                        list.remove(idx);
+                    },
+                    (None, _) => (),
                 }
             }
         }
+        self.load_resources_of(|b| library_ids.contains(&b.library_id()));
+    }
+
+    /// Rebuild the known boards list from the library boards and the project-local boards. The
+    /// project-local boards are listed from their manifests, and decoded in the background (see
+    /// `poll_board_loader`). One that can't be decoded falls back to the library board it overrides.
+    fn refresh_known_boards(&mut self) {
+        self.known_boards = self.library_boards.clone();
+        self.board_loader = None;
+        let Some(root) = self.local_board_root() else {
+            return;
+        };
+        let mut local_boards = board::get_manifests_from_roots(std::slice::from_ref(&root));
+        board::set_overridden(&mut local_boards, &self.library_boards);
+        let cache_dir = self.board_cache_dir.as_deref().map(|dir| loader::local_cache_dir(dir, &root.path));
+        self.board_loader = Some(BoardLoader::start(&local_boards, cache_dir));
+        board::layer_boards(&mut self.known_boards, local_boards);
+    }
+
+    /// Merge the project-local boards that have been decoded since the last poll into the known
+    /// boards, along with the project boards that came from them. Returns whether any of them
+    /// are still being decoded.
+    pub fn poll_board_loader(&mut self) -> bool {
+        let Some(loader) = self.board_loader.as_mut() else {
+            return false;
+        };
+        let changed = loader.poll(&mut self.known_boards);
+        let finished = loader.is_finished();
+        if finished {
+            self.board_loader = None;
+        }
+        if !changed.is_empty() {
+            self.load_resources_of(|b| changed.contains(&b.library_id()));
+        }
+        !finished
     }
 
     /// Populate the project board list via the app-wide 'known boards' list
     fn load_board_resources(&mut self) {
         info!("updating project boards from known boards list.");
-        for b in self.system.get_all_boards_mut().iter_mut() {
-            // returns true if the current, project board is equal to the current known_board
+        self.load_resources_of(|_| true);
+    }
+
+    /// Replace the project boards that match the predicate with their known boards, keeping
+    /// their instances.
+    fn load_resources_of(&mut self, replace: impl Fn(&Board) -> bool) {
+        let mut replaced = false;
+        for b in self.system.get_all_boards_mut().iter_mut().filter(|b| replace(b)) {
+            // returns true if the current, project board is an instance of the current known_board
             let predicate = |known_board: &&Board| {
-                return known_board == b;
+                known_board.is_same_library_board(b)
             };
             if let Some(known_board) = self.known_boards.iter().find(predicate) {
+                let instance = b.get_instance().cloned();
                 **b = known_board.clone();
+                b.set_instance(instance);
+                replaced = true;
             } else {
-                warn!("Could not find the project board in the known boards list. Was the project manifest \
-                       generated with an older version of Iron Coder?")
+                warn!("Could not find board {} in the known boards list. Is its board library missing?", b.library_id())
             }
         }
+        if replaced {
+            self.system.relink_boards();
+        }
     }
 
     /// This method will reload the project based on the current project location
@@ -147,28 +358,53 @@ impl Project {
     /// Load a project from a specified directory, and sync the board assets.
     fn load_from(&mut self, project_directory: &Path) -> Result {
         let project_file = project_directory.join(PROJECT_FILE_NAME);
-        let toml_str = match fs::read_to_string(project_file) {
+        let toml_str = match fs::read_to_string(&project_file) {
             Ok(s) => s,
             Err(e) => {
                 warn!("error reading project file: {:?}", e);
                 return Err(ProjectIOError::FilesystemError);
             },
         };
-        let p: Project = match toml::from_str(&toml_str) {
+        let mut table: toml::Table = match toml::from_str(&toml_str) {
+            Ok(table) => table,
+            Err(e) => {
+                warn!("error parsing project file: {:?}", e);
+                return Err(ProjectIOError::LoadToTomlError)
+            }
+        };
+        // upgrade project files written by older versions of Iron Coder
+        let from_version = migration::schema_version(&table);
+        let changes = migration::migrate(&mut table).map_err(ProjectIOError::MigrationError)?;
+        let p: Project = match table.try_into() {
             Ok(p) => {
                 p
             },
-            Err(_e) => {
+            Err(e) => {
+                warn!("error loading project file: {:?}", e);
                 return Err(ProjectIOError::LoadToTomlError)
             }
         };
+        self.migration_report = None;
+        if from_version < migration::SCHEMA_VERSION {
+            let backup = match migration::back_up(&project_file, from_version) {
+                Ok(backup) => Some(backup),
+                Err(e) => {
+                    warn!("couldn't back up the project file before upgrading it: {:?}", e);
+                    None
+                },
+            };
+            self.migration_report = Some(migration::MigrationReport { from_version, backup, changes });
+        }
         // Now load in certain fields without overwriting others:
         self.code_editor.close_all_tabs();
         self.name = p.name;
         self.location = Some(project_directory.to_path_buf());
         self.system = p.system;
+        self.history.clear(self.system.snapshot());
+        self.selection.clear();
         self.current_view = p.current_view;
-        // sync the assets with the global ones
+        // sync the assets with the global ones, including any in the project's board library
+        self.refresh_known_boards();
         self.load_board_resources();
         self.terminal_buffer.clear();
         // Open the repo in the project directory
@@ -239,7 +475,7 @@ impl Project {
             let project_file = project_folder.join(PROJECT_FILE_NAME);
             info!("saving project file to {}", project_file.display().to_string());
 
-            match toml::to_string(self) {
+            match toml::to_string(&migration::ProjectFile::new(self)) {
                 Ok(contents) => {
                     fs::write(project_file, contents)?;
                 },
@@ -0,0 +1,75 @@
+//! Title: Iron Coder Project Module - Pin Picker
+//! Description: This module finds the pins that match what's typed into the pin picker, which
+//! starts and finishes connections from the keyboard. Pins are named "<designator>:<pin>", and
+//! are matched fuzzily, so that e.g. "f1sda" finds "feather1:SDA / GPIO2".
+
+use crate::board::Board;
+use crate::board::pinout::Pin;
+use super::system::System;
+
+/// What's typed into the pin picker, and which of the matching pins is highlighted.
+#[derive(Clone, Debug, Default)]
+pub struct PickerState {
+    pub query: String,
+    pub highlighted: usize,
+}
+
+/// A pin that can be picked, along with the text it's matched by.
+#[derive(Clone, Debug)]
+pub struct PinChoice {
+    pub board: Board,
+    pub pin: Pin,
+    pub label: String,
+}
+
+/// The pins of all the boards in the system. These are the pins in the board images, which are
+/// the ones that can be clicked, or the pins of the pinout for boards without an image.
+pub fn choices(system: &System) -> Vec<PinChoice> {
+    let mut choices = Vec::new();
+    for board in system.get_all_boards() {
+        let pins: Vec<Pin> = match &board.svg_board_info {
+            Some(svg_board_info) => svg_board_info.pin_rects.iter().map(|(id, _)| board.get_pin_by_svg_id(id)).collect(),
+            None => board.pinout_pins(),
+        };
+        for pin in pins {
+            let label = format!("{}:{}", board.get_designator(), pin);
+            choices.push(PinChoice { board: board.clone(), pin, label });
+        }
+    }
+    choices
+}
+
+/// Score how well the query matches a label, if it does at all. The characters of the query
+/// have to appear in the label in order, ignoring case and spaces. Characters that follow the
+/// previous match, or start a word, score higher, and skipped characters score lower.
+pub fn fuzzy_score(query: &str, label: &str) -> Option<i32> {
+    let label: Vec<char> = label.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut last_match: Option<usize> = None;
+    for q in query.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase) {
+        let idx = next + label[next..].iter().position(|c| *c == q)?;
+        score += 1;
+        if idx == 0 || !label[idx - 1].is_alphanumeric() {
+            score += 8;
+        }
+        match last_match {
+            Some(last) if last + 1 == idx => score += 5,
+            Some(last) => score -= (idx - last - 1) as i32,
+            None => (),
+        }
+        last_match = Some(idx);
+        next = idx + 1;
+    }
+    Some(score)
+}
+
+/// The choices that match the query, best first. Choices that match equally well stay in the
+/// order of the system.
+pub fn matches<'a>(choices: &'a [PinChoice], query: &str) -> Vec<&'a PinChoice> {
+    let mut scored: Vec<(i32, &PinChoice)> = choices.iter()
+        .filter_map(|choice| Some((fuzzy_score(query, &choice.label)?, choice)))
+        .collect();
+    scored.sort_by_key(|(score, _)| -score);
+    scored.into_iter().map(|(_, choice)| choice).collect()
+}
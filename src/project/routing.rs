@@ -0,0 +1,333 @@
+//! Title: Iron Coder Project Module - Routing
+//! Description: This module routes connections on the system editor canvas as orthogonal
+//! ("Manhattan") wires. Each route is found with an A* search over a grid laid over the canvas,
+//! which steers around the boards, keeps the number of bends down, and prefers to run alongside
+//! wires that were routed before it, so that parallel wires are bundled together. The wires of
+//! a bundle are then spread apart so that each one can be told apart.
+
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::{Hash, Hasher};
+
+use egui::{Pos2, Rect};
+
+/// The spacing of the routing grid, in millimeters.
+const GRID_SPACING: f32 = 2.5;
+
+/// The space kept clear between wires and the boards they go around, in millimeters.
+const CLEARANCE: f32 = 2.5;
+
+/// The distance between neighbouring wires of a bundle, in millimeters.
+const BUNDLE_PITCH: f32 = 0.8;
+
+/// Grids with more cells than this are made coarser, to keep routing fast.
+const MAX_CELLS: usize = 200_000;
+
+/// The cost of a step to the next grid cell, a step along a cell that another wire already
+/// runs through in the same direction, and a bend. Sharing is cheaper so wires are bundled.
+const STEP_COST: u32 = 4;
+const SHARED_STEP_COST: u32 = 3;
+const BEND_COST: u32 = 16;
+
+/// A connection to route between two pins, in canvas coordinates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteRequest {
+    pub from: Pos2,
+    /// The board that the first pin is on, which the wire leaves by its nearest edge
+    pub from_board: Option<Rect>,
+    pub to: Pos2,
+    pub to_board: Option<Rect>,
+    /// Points the route must pass through, in order
+    pub via: Vec<Pos2>,
+}
+
+/// Route each of the requests around the obstacles, in order. Each route is a list of points
+/// starting and ending at the pins, where each segment is horizontal or vertical.
+pub fn route_all(obstacles: &[Rect], requests: &[RouteRequest]) -> Vec<Vec<Pos2>> {
+    let mut grid = Grid::new(obstacles, requests);
+    let mut routes: Vec<Vec<Pos2>> = requests.iter().map(|request| grid.route(request)).collect();
+    bundle(&mut routes);
+    routes
+}
+
+/// A hash of everything that goes into routing, so that routes only need to be found again
+/// when a board or waypoint moves.
+pub fn cache_key(obstacles: &[Rect], requests: &[RouteRequest]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let mut hash_pos = |p: Pos2| {
+        p.x.to_bits().hash(&mut hasher);
+        p.y.to_bits().hash(&mut hasher);
+    };
+    for rect in obstacles {
+        hash_pos(rect.min);
+        hash_pos(rect.max);
+    }
+    for request in requests {
+        hash_pos(request.from);
+        hash_pos(request.to);
+        for rect in request.from_board.iter().chain(request.to_board.iter()) {
+            hash_pos(rect.min);
+            hash_pos(rect.max);
+        }
+        request.via.iter().for_each(|p| hash_pos(*p));
+        // separate the requests, so that a via point can't pass for the next request's pin
+        hash_pos(Pos2::new(f32::NAN, f32::NAN));
+    }
+    hasher.finish()
+}
+
+/// The point just outside of a board that a wire leaves a pin by, going straight out through
+/// the nearest edge of the board.
+fn escape(pin: Pos2, board: Option<Rect>) -> Pos2 {
+    let Some(board) = board else { return pin };
+    let out = CLEARANCE + GRID_SPACING;
+    let distances = [pin.x - board.min.x, board.max.x - pin.x, pin.y - board.min.y, board.max.y - pin.y];
+    let nearest = (0..4).min_by(|a, b| distances[*a].total_cmp(&distances[*b])).unwrap();
+    match nearest {
+        0 => Pos2::new(board.min.x - out, pin.y),
+        1 => Pos2::new(board.max.x + out, pin.y),
+        2 => Pos2::new(pin.x, board.min.y - out),
+        _ => Pos2::new(pin.x, board.max.y + out),
+    }
+}
+
+/// The directions a route can step in. Steps along the x axis are 0 and 1, and along the y axis
+/// are 2 and 3.
+const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+/// Search states are a cell along with the direction that the route entered it from, where
+/// `NO_DIRECTION` marks the start of the search.
+const NO_DIRECTION: usize = 4;
+
+struct Grid {
+    origin: Pos2,
+    spacing: f32,
+    cols: usize,
+    rows: usize,
+    /// Cells that are covered by a board, along with its clearance
+    blocked: Vec<bool>,
+    /// The number of routes running through each cell along the x and y axes
+    used: Vec<[u16; 2]>,
+}
+
+impl Grid {
+    /// Lay a grid over the obstacles and the points of each request, with some room to spare
+    /// around them for routes to go around the outside.
+    fn new(obstacles: &[Rect], requests: &[RouteRequest]) -> Self {
+        let points = requests.iter().flat_map(|r| {
+            [r.from, r.to, escape(r.from, r.from_board), escape(r.to, r.to_board)].into_iter().chain(r.via.iter().copied())
+        });
+        let bounds = obstacles.iter().map(|o| o.expand(CLEARANCE))
+            .chain(points.map(|p| Rect::from_min_max(p, p)))
+            .reduce(|a, b| a.union(b))
+            .unwrap_or(Rect::NOTHING)
+            .expand(4.0 * GRID_SPACING);
+        let mut spacing = GRID_SPACING;
+        if bounds.area() / (spacing * spacing) > MAX_CELLS as f32 {
+            spacing = (bounds.area() / MAX_CELLS as f32).sqrt();
+        }
+        let origin = Pos2::new((bounds.min.x / spacing).floor() * spacing, (bounds.min.y / spacing).floor() * spacing);
+        let cols = ((bounds.max.x - origin.x) / spacing).ceil().max(1.0) as usize + 1;
+        let rows = ((bounds.max.y - origin.y) / spacing).ceil().max(1.0) as usize + 1;
+        let mut grid = Self {
+            origin,
+            spacing,
+            cols,
+            rows,
+            blocked: vec![false; cols * rows],
+            used: vec![[0, 0]; cols * rows],
+        };
+        for idx in 0..cols * rows {
+            let center = grid.center(idx);
+            grid.blocked[idx] = obstacles.iter().any(|o| o.expand(CLEARANCE).contains(center));
+        }
+        grid
+    }
+
+    fn cell_of(&self, p: Pos2) -> usize {
+        let col = ((p.x - self.origin.x) / self.spacing).round().clamp(0.0, (self.cols - 1) as f32) as usize;
+        let row = ((p.y - self.origin.y) / self.spacing).round().clamp(0.0, (self.rows - 1) as f32) as usize;
+        row * self.cols + col
+    }
+
+    fn center(&self, idx: usize) -> Pos2 {
+        let (col, row) = (idx % self.cols, idx / self.cols);
+        self.origin + egui::vec2(col as f32, row as f32) * self.spacing
+    }
+
+    fn neighbour(&self, idx: usize, direction: usize) -> Option<usize> {
+        let (dx, dy) = DIRECTIONS[direction];
+        let col = (idx % self.cols).checked_add_signed(dx).filter(|c| *c < self.cols)?;
+        let row = (idx / self.cols).checked_add_signed(dy).filter(|r| *r < self.rows)?;
+        Some(row * self.cols + col)
+    }
+
+    /// Route a request leg by leg, from the first pin through each via point to the second pin,
+    /// and mark the cells it runs through as used.
+    fn route(&mut self, request: &RouteRequest) -> Vec<Pos2> {
+        let mut stops = vec![escape(request.from, request.from_board)];
+        stops.extend(request.via.iter().copied());
+        stops.push(escape(request.to, request.to_board));
+
+        let mut points = vec![request.from, stops[0]];
+        for leg in stops.windows(2) {
+            // waypoints that are already in line with each other are joined directly
+            if !request.via.is_empty() && (leg[0].x == leg[1].x || leg[0].y == leg[1].y) {
+                points.push(leg[1]);
+                continue;
+            }
+            let (start, goal) = (self.cell_of(leg[0]), self.cell_of(leg[1]));
+            if let Some(cells) = self.search(start, goal) {
+                for pair in cells.windows(2) {
+                    let axis = if pair[0] / self.cols == pair[1] / self.cols { 0 } else { 1 };
+                    self.used[pair[1]][axis] = self.used[pair[1]][axis].saturating_add(1);
+                }
+                points.extend(cells.into_iter().map(|idx| self.center(idx)));
+            }
+            points.push(leg[1]);
+        }
+        points.push(request.to);
+        simplify(&orthogonalize(&points))
+    }
+
+    /// Find the cheapest path of cells from the start to the goal, steering around blocked cells
+    /// unless the path starts or ends in one.
+    fn search(&self, start: usize, goal: usize) -> Option<Vec<usize>> {
+        let heuristic = |idx: usize| {
+            let (a, b) = ((idx % self.cols, idx / self.cols), (goal % self.cols, goal / self.cols));
+            (a.0.abs_diff(b.0) + a.1.abs_diff(b.1)) as u32 * SHARED_STEP_COST
+        };
+        let state = |idx: usize, direction: usize| idx * 5 + direction;
+        let mut costs: HashMap<usize, u32> = HashMap::new();
+        let mut came_from: HashMap<usize, usize> = HashMap::new();
+        let mut open = BinaryHeap::new();
+        costs.insert(state(start, NO_DIRECTION), 0);
+        open.push(Reverse((heuristic(start), 0, state(start, NO_DIRECTION))));
+
+        while let Some(Reverse((_, cost, current))) = open.pop() {
+            let (idx, direction) = (current / 5, current % 5);
+            if idx == goal {
+                let mut cells = vec![idx];
+                let mut current = current;
+                while let Some(previous) = came_from.get(&current) {
+                    cells.push(previous / 5);
+                    current = *previous;
+                }
+                cells.reverse();
+                return Some(cells);
+            }
+            if costs.get(&current).is_some_and(|c| *c < cost) {
+                continue;
+            }
+            for next_direction in 0..4 {
+                // routes don't double back on themselves
+                if direction != NO_DIRECTION && next_direction / 2 == direction / 2 && next_direction != direction {
+                    continue;
+                }
+                let Some(next) = self.neighbour(idx, next_direction) else { continue };
+                if self.blocked[next] && next != goal && !self.blocked[start] {
+                    continue;
+                }
+                let mut next_cost = cost + match self.used[next][next_direction / 2] {
+                    0 => STEP_COST,
+                    _ => SHARED_STEP_COST,
+                };
+                if direction != NO_DIRECTION && next_direction != direction {
+                    next_cost += BEND_COST;
+                }
+                let next_state = state(next, next_direction);
+                if costs.get(&next_state).is_none_or(|c| next_cost < *c) {
+                    costs.insert(next_state, next_cost);
+                    came_from.insert(next_state, current);
+                    open.push(Reverse((next_cost + heuristic(next), next_cost, next_state)));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Insert a corner between any two consecutive points that aren't in line with each other.
+fn orthogonalize(points: &[Pos2]) -> Vec<Pos2> {
+    let mut result: Vec<Pos2> = Vec::new();
+    for p in points {
+        if let Some(last) = result.last().copied() {
+            if last.x != p.x && last.y != p.y {
+                result.push(Pos2::new(p.x, last.y));
+            }
+        }
+        result.push(*p);
+    }
+    result
+}
+
+/// Remove repeated points, and points in the middle of a straight run.
+fn simplify(points: &[Pos2]) -> Vec<Pos2> {
+    let mut result: Vec<Pos2> = Vec::new();
+    for p in points {
+        if result.last() == Some(p) {
+            continue;
+        }
+        if let [.., a, b] = result[..] {
+            if (a.x == b.x && b.x == p.x) || (a.y == b.y && b.y == p.y) {
+                result.pop();
+            }
+        }
+        result.push(*p);
+    }
+    result
+}
+
+/// Spread apart the segments of different routes that run along the same line, so that the
+/// wires of a bundle sit side by side rather than on top of each other. The segments that end
+/// at a pin stay where they are.
+fn bundle(routes: &mut [Vec<Pos2>]) {
+    struct Segment {
+        route: usize,
+        /// The segment runs from this point of the route to the next one
+        idx: usize,
+        min: f32,
+        max: f32,
+    }
+    // group the inner segments by their axis and the line they run along
+    let mut lines: HashMap<(bool, i64), Vec<Segment>> = HashMap::new();
+    for (route, points) in routes.iter().enumerate() {
+        for idx in 1..points.len().saturating_sub(2) {
+            let (a, b) = (points[idx], points[idx + 1]);
+            let horizontal = a.y == b.y;
+            let (coord, min, max) = match horizontal {
+                true => (a.y, a.x.min(b.x), a.x.max(b.x)),
+                false => (a.x, a.y.min(b.y), a.y.max(b.y)),
+            };
+            lines.entry((horizontal, (coord * 100.0).round() as i64)).or_default().push(Segment { route, idx, min, max });
+        }
+    }
+
+    let mut offsets: Vec<(usize, usize, bool, f32)> = Vec::new();
+    for ((horizontal, _), segments) in lines.iter() {
+        // the routes with a segment that overlaps another route's segment on this line
+        let mut bundled: Vec<usize> = Vec::new();
+        for a in segments.iter() {
+            let overlaps = segments.iter().any(|b| b.route != a.route && a.min < b.max && b.min < a.max);
+            if overlaps && !bundled.contains(&a.route) {
+                bundled.push(a.route);
+            }
+        }
+        bundled.sort();
+        let center = (bundled.len() as f32 - 1.0) / 2.0;
+        for segment in segments.iter() {
+            if let Some(track) = bundled.iter().position(|r| *r == segment.route) {
+                offsets.push((segment.route, segment.idx, *horizontal, (track as f32 - center) * BUNDLE_PITCH));
+            }
+        }
+    }
+    for (route, idx, horizontal, offset) in offsets {
+        for p in &mut routes[route][idx..=idx + 1] {
+            match horizontal {
+                true => p.y += offset,
+                false => p.x += offset,
+            }
+        }
+    }
+}
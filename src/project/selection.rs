@@ -0,0 +1,159 @@
+//! Title: Iron Coder Project Module - Selection
+//! Description: This module keeps track of the boards and connections that are selected in
+//! the system editor. Selections are copied to the clipboard as a fragment of a System, in
+//! the same form that the project file uses, so they can be pasted into any project.
+
+use log::warn;
+
+use std::collections::HashSet;
+
+use crate::board::Board;
+use super::system::System;
+
+/// The boards and connections that are selected in the system editor.
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    /// The instance ids of the selected boards
+    boards: HashSet<u32>,
+    /// The names of the selected connections
+    connections: HashSet<String>,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.boards.is_empty() && self.connections.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.boards.clear();
+        self.connections.clear();
+    }
+
+    pub fn contains_board(&self, board: &Board) -> bool {
+        board.get_instance_id().is_some_and(|id| self.boards.contains(&id))
+    }
+
+    pub fn contains_connection(&self, name: &str) -> bool {
+        self.connections.contains(name)
+    }
+
+    pub fn add_board(&mut self, board: &Board) {
+        if let Some(id) = board.get_instance_id() {
+            self.boards.insert(id);
+        }
+    }
+
+    pub fn add_connection(&mut self, name: &str) {
+        self.connections.insert(name.to_string());
+    }
+
+    /// Keep a connection selected after it's renamed.
+    pub fn rename_connection(&mut self, name: &str, new_name: &str) {
+        if self.connections.remove(name) {
+            self.connections.insert(new_name.to_string());
+        }
+    }
+
+    /// Select a board that was clicked. A shift-click adds the board to the selection, or
+    /// removes it if it's already selected, and a plain click selects only that board.
+    pub fn click_board(&mut self, board: &Board, shift: bool) {
+        let Some(id) = board.get_instance_id() else { return };
+        if !shift {
+            self.clear();
+        }
+        if !self.boards.insert(id) {
+            self.boards.remove(&id);
+        }
+    }
+
+    /// Select connections that were clicked, in the same way as `click_board`. The wires of a
+    /// bus are selected together.
+    pub fn click_connections(&mut self, names: &[String], shift: bool) {
+        if !shift {
+            self.clear();
+        }
+        if names.iter().all(|name| self.connections.contains(name)) {
+            for name in names {
+                self.connections.remove(name);
+            }
+        } else {
+            self.connections.extend(names.iter().cloned());
+        }
+    }
+
+    /// Select only the board after the first selected one, in the order of the system, or the
+    /// one before it if going backwards. The boards wrap around, and the first board is
+    /// selected if there wasn't a selected board.
+    pub fn select_next_board(&mut self, system: &System, backwards: bool) {
+        let boards = system.get_all_boards();
+        if boards.is_empty() {
+            return;
+        }
+        let current = boards.iter().position(|board| self.contains_board(board));
+        let next = match (current, backwards) {
+            (None, _) => 0,
+            (Some(idx), false) => (idx + 1) % boards.len(),
+            (Some(idx), true) => (idx + boards.len() - 1) % boards.len(),
+        };
+        self.clear();
+        self.add_board(&boards[next]);
+    }
+
+    /// The selected boards, in the order they appear in the system.
+    pub fn boards(&self, system: &System) -> Vec<Board> {
+        system.get_all_boards().into_iter().filter(|board| self.contains_board(board)).collect()
+    }
+
+    /// The boards that move when the selection is moved. Stacked boards move along with the
+    /// board they're stacked on, so that board moves in their place, and only once.
+    pub fn stack_roots(&self, system: &System) -> Vec<Board> {
+        let mut roots: Vec<Board> = Vec::new();
+        for root in self.boards(system).iter().map(|board| system.stack_root(board)) {
+            if !roots.contains(&root) {
+                roots.push(root);
+            }
+        }
+        roots
+    }
+
+    /// The number of selected boards.
+    pub fn num_boards(&self) -> usize {
+        self.boards.len()
+    }
+
+    /// Forget any boards or connections that aren't in the system anymore, e.g. after an undo.
+    pub fn retain(&mut self, system: &System) {
+        let boards: HashSet<u32> = system.get_all_boards().iter().filter_map(|b| b.get_instance_id()).collect();
+        self.boards.retain(|id| boards.contains(id));
+        self.connections.retain(|name| system.connections.iter().any(|c| c.name == *name));
+    }
+
+    /// Remove the selected boards and connections from the system. Connections to the removed
+    /// boards go along with them.
+    pub fn delete(&mut self, system: &mut System) {
+        for board in self.boards(system) {
+            system.remove_board(board).unwrap_or_else(|_| {
+                warn!("error removing board from system.");
+            });
+        }
+        system.connections.retain(|c| !self.connections.contains(&c.name));
+        self.clear();
+    }
+}
+
+/// Write a fragment of a system as clipboard text.
+pub fn to_clipboard(fragment: &System) -> Option<String> {
+    toml::to_string(fragment).map_err(|e| {
+        warn!("couldn't copy the selection to the clipboard: {}", e);
+    }).ok()
+}
+
+/// Read a fragment of a system from clipboard text. Returns None if the text isn't a system
+/// fragment, or has no boards in it.
+pub fn from_clipboard(text: &str) -> Option<System> {
+    let fragment: System = toml::from_str(text).ok()?;
+    if fragment.get_all_boards().is_empty() {
+        return None;
+    }
+    Some(fragment)
+}
@@ -4,16 +4,18 @@
 
 use log::{info, warn};
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::path::Path;
 use serde::{Deserialize, Serialize};
 
 use syn::Ident;
-use quote::quote;
+use quote::{format_ident, quote};
+use proc_macro2::TokenStream;
 
-use crate::board::Board;
-use crate::board::pinout::InterfaceMapping;
+use crate::board::{Board, BoardInstance};
+use crate::board::pinout::{Interface, InterfaceMapping, InterfaceType, Pin, PinFunction};
 
 pub type Result = core::result::Result<(), SystemError>;
 
@@ -21,26 +23,159 @@ pub type Result = core::result::Result<(), SystemError>;
 #[derive(Debug)]
 pub enum SystemError {
     BoardNotInSystemError,
+    IncompatibleInterfacesError,
+    InvalidDesignatorError,
+    NotStackableError,
+    ConnectionNotInSystemError,
+    InvalidConnectionNameError,
+    InvalidEndpointError,
+    BusWireError,
+    BusNotInSystemError,
+    InvalidBusNameError,
 }
 
-/// A Connection is a physical bus connecting two Boards (e.g. I2C, GPIO, SPI, etc).
-/// TODO - determine best way of representing a connection. Can it represent a bus connecting
-/// more than one board? i.e. one I2C main device talking to multiple peripherals, or a CAN
-/// network. 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// One end of a Connection, i.e. a pin on a board, and the function that it is muxed to.
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    pub board: Board,
+    pub pin: Pin,
+    /// The alternate function that the pin is muxed to for this connection, if chosen.
+    pub function: Option<PinFunction>,
+    /// The instance id of the board whose stacking connected this pin, if it was connected by
+    /// stacking a board rather than by the user. Unstacking the board disconnects it again.
+    pub stacked_by: Option<u32>,
+}
+
+impl Endpoint {
+    /// Create an endpoint for the pin. Power and ground rails have their function chosen
+    /// automatically.
+    pub fn new(board: Board, pin: Pin) -> Self {
+        let function = rail_function(&pin);
+        Self { board, pin, function, stacked_by: None }
+    }
+
+    /// Whether this endpoint is the given pin of the given board.
+    pub fn is(&self, board: &Board, svg_id: &str) -> bool {
+        self.board == *board && self.pin.svg_id == svg_id
+    }
+}
+
+/// A Connection is a net joining pins on any number of Boards. This can be a single wire
+/// between two boards, or one line of a multi-drop bus (e.g. the SDA line of an I2C bus that
+/// a main board shares with several peripherals).
+#[derive(Deserialize, Debug, Clone)]
+#[serde(from = "PinToPinConnection")]
 pub struct Connection {
     /// The name of the connection. Iron Coder will search for this name
     /// in your source code to determine which variable is associated with this connection.
     pub name: String,
-    pub start_board: Board,
-    pub start_pin: String,
-    pub end_board: Board,
-    pub end_pin: String,
+    /// The pins joined by this connection. A connection always has at least two endpoints.
+    pub endpoints: Vec<Endpoint>,
     pub interface_mapping: InterfaceMapping,
+    /// The name of the bus that this connection is one wire of, if it was made by connecting
+    /// interfaces rather than pins.
+    pub bus: Option<String>,
+    /// Points on the canvas that an orthogonal route of the connection must pass through, in
+    /// the order they're visited. The router fills in the rest of the route.
+    pub waypoints: Vec<Waypoint>,
+    /// The color that the connection is drawn in, if it's not the color of its interface type.
+    pub color: Option<egui::Color32>,
+}
+
+/// A point on the system editor canvas, in millimeters from the canvas origin.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Waypoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A Connection as written directly in the project files of earlier versions of Iron Coder,
+/// joining exactly two pins. Project files now write connections as nets, with references to
+/// their boards (see `ConnectionFile`).
+#[derive(Deserialize)]
+struct PinToPinConnection {
+    name: String,
+    start_board: Board,
+    start_pin: Pin,
+    end_board: Board,
+    end_pin: Pin,
+    interface_mapping: InterfaceMapping,
+}
+
+impl From<PinToPinConnection> for Connection {
+    fn from(c: PinToPinConnection) -> Self {
+        Connection {
+            name: c.name,
+            endpoints: vec![Endpoint::new(c.start_board, c.start_pin), Endpoint::new(c.end_board, c.end_pin)],
+            interface_mapping: c.interface_mapping,
+            bus: None,
+            waypoints: Vec::new(),
+            color: None,
+        }
+    }
+}
+
+impl Connection {
+    /// Create a new connection between two pins. If either pin is a power or ground rail,
+    /// its function is chosen automatically, and the connection takes on the rail's type.
+    pub fn new(name: String, start_board: Board, start_pin: Pin, end_board: Board, end_pin: Pin) -> Self {
+        let endpoints = vec![Endpoint::new(start_board, start_pin), Endpoint::new(end_board, end_pin)];
+        let mut interface_mapping = InterfaceMapping::default();
+        if let Some(rail) = endpoints.iter().find_map(|e| e.function.as_ref()) {
+            interface_mapping.interface.iface_type = rail.iface_type;
+        }
+        Self {
+            name,
+            endpoints,
+            interface_mapping,
+            bus: None,
+            waypoints: Vec::new(),
+            color: None,
+        }
+    }
+
+    /// Whether the given pin of the given board is one of the connection's endpoints.
+    pub fn contains(&self, board: &Board, svg_id: &str) -> bool {
+        self.endpoints.iter().any(|e| e.is(board, svg_id))
+    }
+
+    /// Return the distinct boards that the connection joins, in endpoint order.
+    pub fn get_boards(&self) -> Vec<&Board> {
+        let mut boards: Vec<&Board> = Vec::new();
+        for endpoint in self.endpoints.iter() {
+            if !boards.contains(&&endpoint.board) {
+                boards.push(&endpoint.board);
+            }
+        }
+        boards
+    }
+
+    /// The role that the connection plays in its interface, e.g. "SCL", if its first pin has one.
+    pub fn role(&self) -> Option<&'static str> {
+        self.endpoints.first()?.pin.role_in(self.interface_mapping.interface.iface_type)
+    }
+}
+
+/// A FunctionConflict describes two or more connections whose chosen pin functions can't
+/// all be satisfied at once, e.g. a pin muxed to both I2C1.SDA and PWM5.A, or the
+/// I2C1.SDA signal claimed on two different pins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionConflict {
+    /// The names of the connections involved in the conflict
+    pub connections: Vec<String>,
+    pub message: String,
 }
 
-/// A system represents the development boards and their interconnections
+impl fmt::Display for FunctionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.connections.join(", "))
+    }
+}
+
+/// A system represents the development boards and their interconnections. In the project
+/// file, boards are written as references to the board library (see `SystemFile`).
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(into = "SystemFile", from = "SystemFile")]
 pub struct System {
     /// The main, programmable board in the system.
     pub main_board: Option<Board>,
@@ -50,8 +185,240 @@ pub struct System {
     /// the system module.
     pub connections: Vec<Connection>,
     /// An optional board + pin for the current in-progress connecion.
-    pub in_progress_connection_start: Option<(Board, String)>,
-    pub in_progress_connection_end: Option<(Board, String)>,
+    pub in_progress_connection_start: Option<(Board, Pin)>,
+    pub in_progress_connection_end: Option<(Board, Pin)>,
+    /// An optional board + interface for the current in-progress bus connection.
+    pub in_progress_bus_start: Option<(Board, InterfaceMapping)>,
+    /// Where each board is drawn in the system editor, keyed by instance id.
+    pub placements: HashMap<u32, Placement>,
+    /// The part of the canvas that's shown in the system editor.
+    pub viewport: Viewport,
+}
+
+/// Where and how a board is drawn in the system editor. The position is the top-left corner
+/// of the board, in millimeters from the canvas origin.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Placement {
+    pub x: f32,
+    pub y: f32,
+    /// Clockwise rotation in degrees, which is always a multiple of 90
+    pub rotation: u16,
+    /// Whether the board is mirrored left-to-right (before it's rotated)
+    pub flipped: bool,
+    /// The instance id of the main board that this board is stacked on, e.g. a FeatherWing on a
+    /// Feather. A stacked board is drawn on top of the main board, lined up by their headers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stacked_on: Option<u32>,
+}
+
+impl Placement {
+    /// Rotate the board a quarter turn clockwise.
+    pub fn rotate(&mut self) {
+        self.rotation = (self.rotation + 90) % 360;
+    }
+
+    /// The size of a board image of the given size once it's been placed, i.e. with its width
+    /// and height swapped if it's rotated a quarter turn.
+    pub fn placed_size(&self, size: egui::Vec2) -> egui::Vec2 {
+        match self.rotation {
+            90 | 270 => egui::vec2(size.y, size.x),
+            _ => size,
+        }
+    }
+
+    /// Move a point on a board image of the given size to where it ends up once the image has
+    /// been flipped and rotated. Both points are relative to the top-left of the image.
+    pub fn place_point(&self, size: egui::Vec2, p: egui::Pos2) -> egui::Pos2 {
+        let x = if self.flipped { size.x - p.x } else { p.x };
+        match self.rotation {
+            90 => egui::pos2(size.y - p.y, x),
+            180 => egui::pos2(size.x - x, size.y - p.y),
+            270 => egui::pos2(p.y, size.x - x),
+            _ => egui::pos2(x, p.y),
+        }
+    }
+}
+
+/// The part of the canvas that's shown in the system editor.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct Viewport {
+    /// The offset of the canvas origin from the top-left of the editor, in screen points
+    pub pan_x: f32,
+    pub pan_y: f32,
+    /// The number of screen points per millimeter
+    pub zoom: f32,
+    /// Whether boards snap to the grid when they're moved
+    pub snap_to_grid: bool,
+    /// Whether connections are drawn as orthogonal routes around the boards, rather than curves
+    pub orthogonal_routing: bool,
+    /// Whether each connection's name is shown on its wire
+    pub show_wire_labels: bool,
+    /// Whether the legend of interface colors is shown
+    pub show_legend: bool,
+    /// Whether the table of connections is shown below the diagram
+    pub show_connection_table: bool,
+    /// Whether the breadboard view of the system is shown beside the diagram
+    pub show_breadboard: bool,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            pan_x: 0.0,
+            pan_y: 0.0,
+            zoom: 5.0,
+            snap_to_grid: true,
+            orthogonal_routing: false,
+            show_wire_labels: true,
+            show_legend: false,
+            show_connection_table: false,
+            show_breadboard: false,
+        }
+    }
+}
+
+/// A reference to a library board placed in a project, along with its per-instance data.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct BoardRef {
+    library_id: String,
+    #[serde(flatten)]
+    instance: BoardInstance,
+    #[serde(default)]
+    placement: Placement,
+}
+
+impl BoardRef {
+    fn new(board: &Board, placement: Placement) -> Self {
+        Self {
+            library_id: board.library_id(),
+            instance: board.get_instance().cloned().unwrap_or_else(|| BoardInstance {
+                id: 0,
+                designator: board.get_designator().to_string(),
+            }),
+            placement,
+        }
+    }
+}
+
+/// An endpoint as written in a project file, with the board named by its designator and the
+/// pin by its SVG id.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct EndpointRef {
+    board: String,
+    pin: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    function: Option<PinFunction>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stacked_by: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct ConnectionFile {
+    name: String,
+    endpoints: Vec<EndpointRef>,
+    interface_mapping: InterfaceMapping,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bus: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    waypoints: Vec<Waypoint>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<egui::Color32>,
+}
+
+/// The way a System is written in a project file. Rather than copies of each Board, this
+/// holds references to the board library, which are replaced by the library boards when
+/// the project is loaded (see `System::relink_boards`). Project files with copies of each
+/// board are upgraded by the migration module.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+struct SystemFile {
+    #[serde(default)]
+    main_board: Option<BoardRef>,
+    #[serde(default)]
+    peripheral_boards: Vec<BoardRef>,
+    #[serde(default)]
+    connections: Vec<ConnectionFile>,
+    #[serde(default)]
+    viewport: Viewport,
+}
+
+/// The boards and connections of a system at one point in time, without the board resources.
+/// These are kept by the undo/redo history. The default is a snapshot of an empty system.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Snapshot(SystemFile);
+
+impl From<System> for SystemFile {
+    fn from(system: System) -> Self {
+        SystemFile::from(&system)
+    }
+}
+
+impl From<&System> for SystemFile {
+    fn from(system: &System) -> Self {
+        let connections = system.connections.iter().map(|c| ConnectionFile {
+            name: c.name.clone(),
+            endpoints: c.endpoints.iter().map(|e| EndpointRef {
+                board: e.board.get_designator().to_string(),
+                pin: e.pin.svg_id.clone(),
+                function: e.function.clone(),
+                stacked_by: e.stacked_by,
+            }).collect(),
+            interface_mapping: c.interface_mapping.clone(),
+            bus: c.bus.clone(),
+            waypoints: c.waypoints.clone(),
+            color: c.color,
+        }).collect();
+        let board_ref = |board: &Board| BoardRef::new(board, system.get_placement(board));
+        Self {
+            main_board: system.main_board.as_ref().map(board_ref),
+            peripheral_boards: system.peripheral_boards.iter().map(board_ref).collect(),
+            connections,
+            viewport: system.viewport,
+        }
+    }
+}
+
+impl From<SystemFile> for System {
+    fn from(file: SystemFile) -> Self {
+        let mut placements = HashMap::new();
+        let mut to_board = |r: BoardRef| {
+            placements.insert(r.instance.id, r.placement);
+            Board::from_reference(&r.library_id, r.instance)
+        };
+        let main_board = file.main_board.map(&mut to_board);
+        let peripheral_boards = file.peripheral_boards.into_iter().map(&mut to_board).collect();
+        let mut system = System {
+            main_board,
+            peripheral_boards,
+            placements,
+            viewport: file.viewport,
+            ..Default::default()
+        };
+        let boards = system.get_all_boards();
+        for c in file.connections {
+            let endpoints: Vec<Endpoint> = c.endpoints.into_iter().filter_map(|e| {
+                let Some(board) = boards.iter().find(|b| b.get_designator() == e.board) else {
+                    warn!("connection {} refers to board {}, which isn't in the system", c.name, e.board);
+                    return None;
+                };
+                Some(Endpoint { board: board.clone(), pin: Pin::from_svg_id(&e.pin), function: e.function, stacked_by: e.stacked_by })
+            }).collect();
+            if endpoints.len() < 2 {
+                warn!("connection {} joins fewer than two pins of the system, so it was left out", c.name);
+                continue;
+            }
+            system.connections.push(Connection {
+                name: c.name,
+                endpoints,
+                interface_mapping: c.interface_mapping,
+                bus: c.bus,
+                waypoints: c.waypoints,
+                color: c.color,
+            });
+        }
+        system
+    }
 }
 
 /// A datastructure that will hold all of the information we need to populate the System module.
@@ -59,6 +426,8 @@ pub struct System {
 struct TokenStreamAccumulator {
     /// A set of crate Idents that need to be included in the system module.
     required_bsp_crates: HashSet<Ident>,
+    /// A list of type aliases naming the HAL type of each connection's pins.
+    pin_type_aliases: Vec<TokenStream>,
     // /// A vector of <field>: <type> to include in the System struct declaration.
     // struct_field_and_type_list: Vec<TokenStream>,
     // /// A vector of <field>: <constructor> to include in the System struct constructor.
@@ -91,9 +460,261 @@ impl System {
         return boards;
     }
 
+    /// Return a copy of a library board, ready to be added to the system as a new instance. The
+    /// instance gets the next unused id, and a designator like "oled1".
+    pub fn place_board(&self, board: &Board) -> Board {
+        let id = self.next_instance_id();
+        let designator = self.unique_designator(&board.designator_prefix());
+        let mut placed = board.clone();
+        placed.set_instance(Some(BoardInstance { id, designator }));
+        placed
+    }
+
+    /// The lowest instance id that's greater than every one in use.
+    fn next_instance_id(&self) -> u32 {
+        self.get_all_boards().iter()
+            .filter_map(|board| board.get_instance_id())
+            .max()
+            .map_or(0, |id| id + 1)
+    }
+
+    /// The first designator of the form "<prefix>N" that no board in the system has.
+    fn unique_designator(&self, prefix: &str) -> String {
+        let boards = self.get_all_boards();
+        (1..).map(|n| format!("{}{}", prefix, n))
+            .find(|designator| !boards.iter().any(|b| b.get_designator() == designator))
+            .unwrap()
+    }
+
+    /// Change the designator of a board in the system, including the copies of it held by
+    /// connections. Designators must be unique and non-empty, so this fails if another board
+    /// has it already.
+    pub fn set_designator(&mut self, board: &Board, designator: &str) -> Result {
+        let Some(mut instance) = board.get_instance().cloned() else {
+            return Err(SystemError::BoardNotInSystemError);
+        };
+        if designator.is_empty() || self.get_all_boards().iter().any(|b| b != board && b.get_designator() == designator) {
+            return Err(SystemError::InvalidDesignatorError);
+        }
+        instance.designator = designator.to_string();
+        let mut found = false;
+        for b in self.get_all_boards_mut().into_iter().filter(|b| **b == *board) {
+            b.set_instance(Some(instance.clone()));
+            found = true;
+        }
+        if !found {
+            return Err(SystemError::BoardNotInSystemError);
+        }
+        for endpoint in self.connections.iter_mut().flat_map(|c| c.endpoints.iter_mut()) {
+            if endpoint.board == *board {
+                endpoint.board.set_instance(Some(instance.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Give an instance to each board that doesn't have one, i.e. the boards of projects saved
+    /// before a project could contain more than one of the same board. The boards held by
+    /// connections are matched up with the system boards by their library id.
+    pub fn assign_instances(&mut self) {
+        for idx in 0..self.get_all_boards().len() {
+            let board = self.get_all_boards()[idx].clone();
+            if board.get_instance().is_none() {
+                let placed = self.place_board(&board);
+                info!("assigning designator {} to board {}", placed.get_designator(), board.get_name());
+                *self.get_all_boards_mut()[idx] = placed;
+            }
+        }
+        let boards = self.get_all_boards();
+        for endpoint in self.connections.iter_mut().flat_map(|c| c.endpoints.iter_mut()) {
+            if endpoint.board.get_instance().is_none() {
+                if let Some(board) = boards.iter().find(|b| b.is_same_library_board(&endpoint.board)) {
+                    endpoint.board = board.clone();
+                }
+            }
+        }
+    }
+
+    /// Replace the boards held by connections with the system's own copies, so that they pick up
+    /// the resources of the library boards. Each endpoint's pin is looked up again as well.
+    pub fn relink_boards(&mut self) {
+        let boards = self.get_all_boards();
+        for endpoint in self.connections.iter_mut().flat_map(|c| c.endpoints.iter_mut()) {
+            if let Some(board) = boards.iter().find(|b| **b == endpoint.board) {
+                endpoint.pin = board.get_pin_by_svg_id(&endpoint.pin.svg_id);
+                endpoint.board = board.clone();
+            }
+        }
+    }
+
+    /// Take a snapshot of the boards, placements and connections of the system.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut file = SystemFile::from(self);
+        file.viewport = Viewport::default();
+        Snapshot(file)
+    }
+
+    /// Return the system as it was when the snapshot was taken, viewed through this system's
+    /// viewport. Like a system loaded from a project file, its boards are only references until
+    /// they're replaced by the library boards.
+    pub fn restored(&self, snapshot: &Snapshot) -> System {
+        let mut system = System::from(snapshot.0.clone());
+        system.viewport = self.viewport;
+        system
+    }
+
+    /// Return where the board is drawn in the system editor. A stacked board is wherever the
+    /// board it's stacked on puts it.
+    pub fn get_placement(&self, board: &Board) -> Placement {
+        let placement = board.get_instance_id()
+            .and_then(|id| self.placements.get(&id).copied())
+            .unwrap_or_default();
+        match self.stack_base(board) {
+            Some(base) => self.stacked_placement(board, &base, placement),
+            None => placement,
+        }
+    }
+
+    /// Set where the board is drawn in the system editor. Moving, rotating or flipping a
+    /// stacked board does the same to the board it's stacked on, so the stack stays together.
+    pub fn set_placement(&mut self, board: &Board, placement: Placement) {
+        let Some(id) = board.get_instance_id() else { return };
+        if let Some(base) = self.stack_base(board) {
+            let current = self.get_placement(board);
+            let mut base_placement = self.get_placement(&base);
+            base_placement.x += placement.x - current.x;
+            base_placement.y += placement.y - current.y;
+            base_placement.rotation = placement.rotation;
+            base_placement.flipped = placement.flipped;
+            self.set_placement(&base, base_placement);
+            return;
+        }
+        self.placements.insert(id, placement);
+    }
+
+    /// The main board that the given board is stacked on, if it's stacked.
+    pub fn stack_base(&self, board: &Board) -> Option<Board> {
+        let base_id = self.placements.get(&board.get_instance_id()?)?.stacked_on?;
+        self.main_board.clone().filter(|main_board| main_board.get_instance_id() == Some(base_id) && main_board != board)
+    }
+
+    /// The board that moves when the given board is moved, i.e. the base of its stack if it's
+    /// stacked, and otherwise the board itself.
+    pub fn stack_root(&self, board: &Board) -> Board {
+        self.stack_base(board).unwrap_or_else(|| board.clone())
+    }
+
+    /// Where a board stacked on the base is drawn, with the first header pins of the two boards
+    /// on top of each other. If either board's header pins aren't known, the boards are lined
+    /// up by their top-left corners instead.
+    fn stacked_placement(&self, board: &Board, base: &Board, placement: Placement) -> Placement {
+        let base_placement = self.get_placement(base);
+        let mut placement = Placement {
+            x: base_placement.x,
+            y: base_placement.y,
+            rotation: base_placement.rotation,
+            flipped: base_placement.flipped,
+            ..placement
+        };
+        let size = |b: &Board| b.svg_board_info.as_ref().map(|info| info.physical_size);
+        if let (Some(anchor), Some(size), Some(base_anchor), Some(base_size)) = (board.header_anchor(), size(board), base.header_anchor(), size(base)) {
+            let offset = base_placement.place_point(base_size, base_anchor) - placement.place_point(size, anchor);
+            placement.x += offset.x;
+            placement.y += offset.y;
+        }
+        placement
+    }
+
+    /// The main board that the given board can be stacked on, which is one of the same standard
+    /// form factor, if the board library describes the standard's headers.
+    pub fn stackable_on(&self, board: &Board) -> Option<Board> {
+        let main_board = self.main_board.clone()?;
+        let standard = board.get_standard_geometry()?.standard;
+        if main_board == *board || main_board.get_standard() != Some(standard) || self.stack_base(board).is_some() {
+            return None;
+        }
+        Some(main_board)
+    }
+
+    /// Stack a board on the main board, e.g. a FeatherWing on a Feather. The pins of the two
+    /// boards that are at the same header position are connected, joining any connection that
+    /// either pin is already part of. The pins that stacking connects are marked with the
+    /// board's instance id, so that unstacking it can disconnect them again.
+    pub fn stack(&mut self, board: &Board) -> Result {
+        let base = self.stackable_on(board).ok_or(SystemError::NotStackableError)?;
+        let id = board.get_instance_id().ok_or(SystemError::BoardNotInSystemError)?;
+        let mut placement = self.get_placement(board);
+        placement.stacked_on = base.get_instance_id();
+        self.placements.insert(id, placement);
+        let stacked = |board: &Board, pin: &Pin| Endpoint { stacked_by: Some(id), ..Endpoint::new(board.clone(), pin.clone()) };
+        for (pin, base_pin) in header_pin_pairs(board, &base) {
+            match (self.find_connection(&base, &base_pin.svg_id), self.find_connection(board, &pin.svg_id)) {
+                (None, None) => {
+                    let taken: Vec<String> = self.connections.iter().map(|c| c.name.clone()).collect();
+                    let mut name = format!("{}_{}", board.get_designator(), pin.name().to_lowercase());
+                    if taken.contains(&name) {
+                        name = unique_name(&name, &taken);
+                    }
+                    let mut connection = Connection::new(name, base.clone(), base_pin.clone(), board.clone(), pin.clone());
+                    connection.endpoints = vec![stacked(&base, &base_pin), stacked(board, &pin)];
+                    if let Some(mapping) = board.get_pinout().into_iter().find(|m| m.pins.iter().any(|name| pin.is_called(name))) {
+                        connection.interface_mapping.interface = mapping.interface;
+                    }
+                    self.connections.push(connection);
+                },
+                (Some(idx), None) => self.connections[idx].endpoints.push(stacked(board, &pin)),
+                (None, Some(idx)) => self.connections[idx].endpoints.push(stacked(&base, &base_pin)),
+                // two connections that stacking joins stay joined once the board is unstacked
+                (Some(_), Some(_)) => {
+                    if let Err(e) = self.connect_pins(&base, &base_pin, board, &pin) {
+                        warn!("couldn't connect {} to {} when stacking: {:?}", base_pin.name(), pin.name(), e);
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Take a board off the stack it's on, disconnecting the pins that stacking it connected.
+    /// Connections that the user made to its header pins are kept. The board is left beside
+    /// the stack.
+    pub fn unstack(&mut self, board: &Board) -> Result {
+        let base = self.stack_base(board).ok_or(SystemError::NotStackableError)?;
+        let id = board.get_instance_id().ok_or(SystemError::BoardNotInSystemError)?;
+        let mut placement = self.get_placement(board);
+        placement.stacked_on = None;
+        placement.x += base.svg_board_info.as_ref().map_or(0.0, |info| placement.placed_size(info.physical_size).x) + 10.0;
+        self.placements.insert(id, placement);
+        self.remove_stacked_endpoints(id);
+        Ok(())
+    }
+
+    /// Disconnect the pins that stacking the board with the given instance id connected.
+    /// Connections that are left joining fewer than two pins are removed entirely.
+    fn remove_stacked_endpoints(&mut self, id: u32) {
+        for connection in self.connections.iter_mut() {
+            connection.endpoints.retain(|endpoint| endpoint.stacked_by != Some(id));
+        }
+        self.connections.retain(|connection| connection.endpoints.len() >= 2);
+    }
+
     /// Try to remove the provided Board from the system, along with all of it's connections.
     /// If everything is good, return Ok(()), otherwise return an error indicating what went wrong.
     pub fn remove_board(&mut self, board: Board) -> Result {
+        // boards stacked on this one stay where they are, but aren't connected to it any more
+        let stacked: Vec<Board> = self.get_all_boards().into_iter().filter(|b| self.stack_base(b).as_ref() == Some(&board)).collect();
+        for b in stacked {
+            let mut placement = self.get_placement(&b);
+            placement.stacked_on = None;
+            if let Some(id) = b.get_instance_id() {
+                self.placements.insert(id, placement);
+                self.remove_stacked_endpoints(id);
+            }
+        }
+        if let Some(id) = board.get_instance_id() {
+            self.placements.remove(&id);
+            self.remove_stacked_endpoints(id);
+        }
         if let Some(ref mb) = self.main_board {
             if *mb == board {
                 self.main_board = None;
@@ -109,11 +730,446 @@ impl System {
         return Err(SystemError::BoardNotInSystemError);
     }
 
-    /// Iterate through the connection list, and remove connections that involve the provided board
+    /// Iterate through the connection list, and remove the provided board's endpoints. Connections
+    /// that are left joining fewer than two pins are removed entirely.
     fn remove_connections_involving_board(&mut self, board: Board) {
-        self.connections = self.connections.iter().filter(|elem| {
-            !((**elem).start_board == board || (**elem).end_board == board)
-        }).cloned().collect();
+        for connection in self.connections.iter_mut() {
+            connection.endpoints.retain(|endpoint| endpoint.board != board);
+        }
+        self.connections.retain(|connection| connection.endpoints.len() >= 2);
+    }
+
+    /// Return the part of the system made up of the given boards and the connections between
+    /// them, e.g. to copy to the clipboard. Connections that also reach boards outside of the
+    /// fragment only keep their pins on the fragment's boards, and are left out if that leaves
+    /// fewer than two.
+    pub fn fragment(&self, boards: &[Board]) -> System {
+        let mut fragment = System::default();
+        for board in self.get_all_boards().into_iter().filter(|b| boards.contains(b)) {
+            fragment.set_placement(&board, self.get_placement(&board));
+            if self.main_board.as_ref() == Some(&board) {
+                fragment.main_board = Some(board);
+            } else {
+                fragment.peripheral_boards.push(board);
+            }
+        }
+        let fragment_boards = fragment.get_all_boards();
+        for connection in self.connections.iter() {
+            let mut connection = connection.clone();
+            connection.endpoints.retain(|endpoint| fragment_boards.contains(&endpoint.board));
+            if connection.endpoints.len() >= 2 {
+                fragment.connections.push(connection);
+            }
+        }
+        fragment
+    }
+
+    /// Add a copy of a fragment of a system, e.g. one pasted from the clipboard, with each of its
+    /// boards moved by the given offset in millimeters. Every board becomes a new instance with a
+    /// designator of its own, and buses and connections are renamed if their names are taken. A
+    /// main board is only added if the system doesn't have one yet. Returns the new boards.
+    pub fn paste(&mut self, fragment: &System, dx: f32, dy: f32) -> Vec<Board> {
+        let mut fragment = fragment.clone();
+        for bus in fragment.get_buses() {
+            let mut taken = self.get_buses();
+            if taken.contains(&bus) {
+                taken.extend(fragment.get_buses());
+                let new_name = unique_name(&bus, &taken);
+                fragment.rename_bus(&bus, &new_name).unwrap_or_else(|e| {
+                    warn!("couldn't rename pasted bus {} to {}: {:?}", bus, new_name, e);
+                });
+            }
+        }
+        let mut taken: Vec<String> = self.connections.iter().chain(fragment.connections.iter())
+            .map(|c| c.name.clone())
+            .collect();
+        for connection in fragment.connections.iter_mut() {
+            if self.connections.iter().any(|c| c.name == connection.name) {
+                connection.name = unique_name(&connection.name, &taken);
+                taken.push(connection.name.clone());
+            }
+        }
+
+        // pasted boards keep the prefix of their designator, e.g. a copy of "oled1" is "oled2"
+        let mut pasted: Vec<(Board, Board)> = Vec::new();
+        for board in fragment.get_all_boards() {
+            let is_main_board = fragment.main_board.as_ref() == Some(&board);
+            if is_main_board && self.main_board.is_some() {
+                warn!("the system already has a main board, so {} wasn't pasted", board.get_designator());
+                continue;
+            }
+            let prefix = match board.get_designator().trim_end_matches(|c: char| c.is_ascii_digit()) {
+                "" => board.designator_prefix(),
+                prefix => prefix.to_string(),
+            };
+            let mut placed = board.clone();
+            placed.set_instance(Some(BoardInstance {
+                id: self.next_instance_id(),
+                designator: self.unique_designator(&prefix),
+            }));
+            let mut placement = fragment.get_placement(&board);
+            placement.x += dx;
+            placement.y += dy;
+            self.set_placement(&placed, placement);
+            if is_main_board {
+                self.main_board = Some(placed.clone());
+            } else {
+                self.peripheral_boards.push(placed.clone());
+            }
+            pasted.push((board, placed));
+        }
+        // pasted boards stay stacked if the board they're stacked on was pasted along with them
+        for (board, placed) in pasted.iter() {
+            let stacked_on = fragment.get_placement(board).stacked_on.and_then(|base_id| {
+                let (_, base) = pasted.iter().find(|(b, _)| b.get_instance_id() == Some(base_id))?;
+                base.get_instance_id()
+            });
+            if let Some(placement) = placed.get_instance_id().and_then(|id| self.placements.get_mut(&id)) {
+                placement.stacked_on = stacked_on;
+            }
+        }
+        for mut connection in fragment.connections {
+            connection.endpoints.retain_mut(|endpoint| {
+                match pasted.iter().find(|(board, _)| *board == endpoint.board) {
+                    Some((_, placed)) => {
+                        endpoint.board = placed.clone();
+                        // a pin connected by stacking stays that way if the stacked board was pasted
+                        endpoint.stacked_by = endpoint.stacked_by.and_then(|id| {
+                            let (_, stacked) = pasted.iter().find(|(b, _)| b.get_instance_id() == Some(id))?;
+                            stacked.get_instance_id()
+                        });
+                        true
+                    },
+                    None => false,
+                }
+            });
+            for waypoint in connection.waypoints.iter_mut() {
+                waypoint.x += dx;
+                waypoint.y += dy;
+            }
+            if connection.endpoints.len() >= 2 {
+                self.connections.push(connection);
+            }
+        }
+        pasted.into_iter().map(|(_, placed)| placed).collect()
+    }
+
+    /// Find the connection that the given pin is part of, if any.
+    pub fn find_connection(&self, board: &Board, svg_id: &str) -> Option<usize> {
+        self.connections.iter().position(|c| c.contains(board, svg_id))
+    }
+
+    /// Connect two pins. If neither pin is connected yet, this makes a new connection between
+    /// them. If one of them is, the other pin joins that connection, and if both are, their
+    /// connections are merged into one. A pin can't be connected to itself, and pins can't be
+    /// connected if their interface types differ, e.g. a ground rail and a power rail, or the
+    /// pins of an I2C connection and a UART connection. The wires of a bus only change along
+    /// with the rest of their bus, so they aren't merged with other connections.
+    pub fn connect_pins(&mut self, start_board: &Board, start_pin: &Pin, end_board: &Board, end_pin: &Pin) -> Result {
+        if start_board == end_board && start_pin.svg_id == end_pin.svg_id {
+            return Err(SystemError::InvalidEndpointError);
+        }
+        let start = self.find_connection(start_board, &start_pin.svg_id);
+        let end = self.find_connection(end_board, &end_pin.svg_id);
+        if let (Some(start), Some(end)) = (start, end) {
+            if start != end && (self.connections[start].bus.is_some() || self.connections[end].bus.is_some()) {
+                return Err(SystemError::BusWireError);
+            }
+        }
+        // a pin that isn't connected yet only has a type if it's a rail
+        let iface_type = |connection: Option<usize>, pin: &Pin| match connection {
+            Some(idx) => self.connections[idx].interface_mapping.interface.iface_type,
+            None => rail_function(pin).map_or(InterfaceType::NONE, |f| f.iface_type),
+        };
+        let iface_type = match (iface_type(start, start_pin), iface_type(end, end_pin)) {
+            (InterfaceType::NONE, t) | (t, InterfaceType::NONE) => t,
+            (s, e) if s == e => s,
+            _ => return Err(SystemError::IncompatibleInterfacesError),
+        };
+        match (start, end) {
+            (None, None) => {
+                let mut idx = self.connections.len();
+                let name = loop {
+                    let name = format!("connection_{}", idx);
+                    if !self.connections.iter().any(|c| c.name == name) {
+                        break name;
+                    }
+                    idx += 1;
+                };
+                let c = Connection::new(name, start_board.clone(), start_pin.clone(), end_board.clone(), end_pin.clone());
+                self.connections.push(c);
+            },
+            (Some(idx), None) => {
+                self.connections[idx].endpoints.push(Endpoint::new(end_board.clone(), end_pin.clone()));
+            },
+            (None, Some(idx)) => {
+                self.connections[idx].endpoints.push(Endpoint::new(start_board.clone(), start_pin.clone()));
+            },
+            (Some(start), Some(end)) if start != end => {
+                let merged = self.connections.remove(end);
+                let start = if end < start { start - 1 } else { start };
+                info!("merging connection {} into {}", merged.name, self.connections[start].name);
+                self.connections[start].endpoints.extend(merged.endpoints);
+            },
+            _ => (),
+        }
+        // the connection takes on the type of whichever side had one
+        if let Some(idx) = self.find_connection(start_board, &start_pin.svg_id) {
+            self.connections[idx].interface_mapping.interface.iface_type = iface_type;
+        }
+        Ok(())
+    }
+
+    /// Rename a connection. Names must be unique and non-empty, since a connection is found in
+    /// the source code, and in the system editor, by its name.
+    pub fn rename_connection(&mut self, name: &str, new_name: &str) -> Result {
+        if new_name.is_empty() || self.connections.iter().any(|c| c.name == new_name && c.name != name) {
+            return Err(SystemError::InvalidConnectionNameError);
+        }
+        let Some(connection) = self.connections.iter_mut().find(|c| c.name == name) else {
+            return Err(SystemError::ConnectionNotInSystemError);
+        };
+        connection.name = new_name.to_string();
+        Ok(())
+    }
+
+    /// Set the type and direction of a connection. The wires of a bus take theirs from the
+    /// interface that the bus connects, so they can't be changed.
+    pub fn set_interface(&mut self, name: &str, interface: Interface) -> Result {
+        let Some(connection) = self.connections.iter_mut().find(|c| c.name == name) else {
+            return Err(SystemError::ConnectionNotInSystemError);
+        };
+        if connection.bus.is_some() {
+            return Err(SystemError::BusWireError);
+        }
+        connection.interface_mapping.interface = interface;
+        Ok(())
+    }
+
+    /// Move one end of a connection to another pin. A pin can't be connected to itself, and the
+    /// wires of a bus only change along with the rest of their bus, so a connection can't be
+    /// moved onto the pin of a bus wire either. If the pin is already part of another
+    /// connection, the two are merged into one, as connecting the pins would.
+    pub fn set_endpoint(&mut self, name: &str, idx: usize, board: &Board, pin: &Pin) -> Result {
+        let Some(c_idx) = self.connections.iter().position(|c| c.name == name) else {
+            return Err(SystemError::ConnectionNotInSystemError);
+        };
+        let connection = &self.connections[c_idx];
+        if connection.bus.is_some() {
+            return Err(SystemError::BusWireError);
+        }
+        if idx >= connection.endpoints.len() {
+            return Err(SystemError::InvalidEndpointError);
+        }
+        if connection.endpoints[idx].is(board, &pin.svg_id) {
+            return Ok(());
+        }
+        if connection.contains(board, &pin.svg_id) {
+            return Err(SystemError::InvalidEndpointError);
+        }
+        let other = self.find_connection(board, &pin.svg_id);
+        if other.is_some_and(|other| self.connections[other].bus.is_some()) {
+            return Err(SystemError::BusWireError);
+        }
+        self.connections[c_idx].endpoints[idx] = Endpoint::new(board.clone(), pin.clone());
+        if let Some(other) = other {
+            let merged = self.connections.remove(other);
+            let keep = if other < c_idx { c_idx - 1 } else { c_idx };
+            info!("merging connection {} into {}", merged.name, self.connections[keep].name);
+            for endpoint in merged.endpoints {
+                if !self.connections[keep].contains(&endpoint.board, &endpoint.pin.svg_id) {
+                    self.connections[keep].endpoints.push(endpoint);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Connect an interface on one board to a compatible interface on another, as a single bus.
+    /// The pins of the two interfaces are paired up by their role (e.g. SCL to SCL, or TX to RX),
+    /// and each pair becomes one wire of the bus, with the interface type already set.
+    pub fn add_bus_connection(&mut self, start_board: &Board, start_mapping: &InterfaceMapping, end_board: &Board, end_mapping: &InterfaceMapping) -> Result {
+        let iface_type = start_mapping.interface.iface_type;
+        if end_mapping.interface.iface_type != iface_type {
+            return Err(SystemError::IncompatibleInterfacesError);
+        }
+        let start_pins = pins_with_roles(start_board, start_mapping);
+        let end_pins = pins_with_roles(end_board, end_mapping);
+
+        let mut bus_idx = 0;
+        let bus_name = loop {
+            let name = format!("{}_{}", iface_type.to_string().to_lowercase(), bus_idx);
+            if !self.get_buses().contains(&name) {
+                break name;
+            }
+            bus_idx += 1;
+        };
+
+        let wires: Vec<Connection> = start_pins.iter().filter_map(|(start_pin, role)| {
+            let paired_role = iface_type.paired_role(role);
+            let (end_pin, _) = end_pins.iter().find(|(_, end_role)| *end_role == paired_role)?;
+            let mut wire = Connection::new(
+                format!("{}_{}", bus_name, role.to_lowercase()),
+                start_board.clone(),
+                start_pin.clone(),
+                end_board.clone(),
+                end_pin.clone(),
+            );
+            wire.interface_mapping.interface = start_mapping.interface.clone();
+            wire.endpoints[0].function = role_function(start_pin, iface_type, role);
+            wire.endpoints[1].function = role_function(end_pin, iface_type, paired_role);
+            wire.bus = Some(bus_name.clone());
+            Some(wire)
+        }).collect();
+
+        if wires.is_empty() {
+            return Err(SystemError::IncompatibleInterfacesError);
+        }
+        info!("connected {} to {} as bus {} with {} wires", start_mapping, end_mapping, bus_name, wires.len());
+        self.connections.extend(wires);
+        Ok(())
+    }
+
+    /// Add another board to an existing bus, such as a second peripheral on an I2C bus. Each pin
+    /// of the interface joins the wire with the same role. Pins whose role belongs to a single
+    /// device, like an SPI chip select, are left for the user to connect separately.
+    pub fn join_bus(&mut self, bus: &str, board: &Board, mapping: &InterfaceMapping) -> Result {
+        let iface_type = mapping.interface.iface_type;
+        let bus_iface_type = self.get_bus_wires(bus).first().map(|wire| wire.interface_mapping.interface.iface_type);
+        if bus_iface_type != Some(iface_type) || !iface_type.is_multi_drop() {
+            return Err(SystemError::IncompatibleInterfacesError);
+        }
+        let mut joined = 0;
+        for (pin, role) in pins_with_roles(board, mapping) {
+            if !iface_type.is_shared_role(role) {
+                continue;
+            }
+            let wire = self.connections.iter_mut().find(|c| {
+                c.bus.as_deref() == Some(bus) && c.role() == Some(role)
+            });
+            if let Some(wire) = wire {
+                if !wire.contains(board, &pin.svg_id) {
+                    let function = role_function(&pin, iface_type, role);
+                    wire.endpoints.push(Endpoint { board: board.clone(), pin, function, stacked_by: None });
+                    joined += 1;
+                }
+            }
+        }
+        if joined == 0 {
+            return Err(SystemError::IncompatibleInterfacesError);
+        }
+        info!("joined {} on {} to bus {}", mapping, board.get_designator(), bus);
+        Ok(())
+    }
+
+    /// Return the names of all of the buses in the system, in the order they were made.
+    pub fn get_buses(&self) -> Vec<String> {
+        let mut buses: Vec<String> = Vec::new();
+        for bus in self.connections.iter().filter_map(|c| c.bus.as_ref()) {
+            if !buses.contains(bus) {
+                buses.push(bus.clone());
+            }
+        }
+        buses
+    }
+
+    /// Return the wires that make up the named bus.
+    pub fn get_bus_wires(&self, bus: &str) -> Vec<&Connection> {
+        self.connections.iter().filter(|c| c.bus.as_deref() == Some(bus)).collect()
+    }
+
+    /// Rename a bus, along with each of its wires. Like connection names, bus names must be
+    /// unique and non-empty, and so must the names that the bus's wires are given.
+    pub fn rename_bus(&mut self, bus: &str, new_name: &str) -> Result {
+        let buses = self.get_buses();
+        if !buses.iter().any(|b| b == bus) {
+            return Err(SystemError::BusNotInSystemError);
+        }
+        if new_name.is_empty() || buses.iter().any(|b| b == new_name && b != bus) {
+            return Err(SystemError::InvalidBusNameError);
+        }
+        let wire_name = |wire: &Connection| match wire.name.strip_prefix(bus) {
+            Some(role) => format!("{}{}", new_name, role),
+            None => wire.name.clone(),
+        };
+        let taken = self.connections.iter().any(|c| {
+            c.bus.as_deref() != Some(bus) && self.get_bus_wires(bus).iter().any(|wire| wire_name(wire) == c.name)
+        });
+        if taken {
+            return Err(SystemError::InvalidBusNameError);
+        }
+        for wire in self.connections.iter_mut().filter(|c| c.bus.as_deref() == Some(bus)) {
+            wire.name = wire_name(wire);
+            wire.bus = Some(new_name.to_string());
+        }
+        Ok(())
+    }
+
+    /// Set the color of each wire of a bus, or go back to the color of its interface type.
+    pub fn set_bus_color(&mut self, bus: &str, color: Option<egui::Color32>) {
+        for wire in self.connections.iter_mut().filter(|c| c.bus.as_deref() == Some(bus)) {
+            wire.color = color;
+        }
+    }
+
+    /// Remove a bus, along with each of its wires.
+    pub fn remove_bus(&mut self, bus: &str) {
+        self.connections.retain(|c| c.bus.as_deref() != Some(bus));
+    }
+
+    /// Check the chosen pin functions of every connection for conflicts. A pin can only be
+    /// muxed to a single function at a time, and a given peripheral signal (e.g. I2C1.SDA)
+    /// can only be routed to a single pin of a board.
+    pub fn function_conflicts(&self) -> Vec<FunctionConflict> {
+        let mut conflicts = Vec::new();
+        // (board, svg_id) -> [(function, connection name)]
+        let mut functions_by_pin: HashMap<(&Board, &str), Vec<(&PinFunction, &str)>> = HashMap::new();
+        // (board, function) -> [(pin, connection name)]
+        let mut pins_by_signal: HashMap<(&Board, &PinFunction), Vec<(&Pin, &str)>> = HashMap::new();
+        for connection in self.connections.iter() {
+            for Endpoint { board, pin, function, .. } in connection.endpoints.iter() {
+                let Some(function) = function else { continue };
+                functions_by_pin.entry((board, pin.svg_id.as_str()))
+                    .or_default()
+                    .push((function, connection.name.as_str()));
+                // plain GPIO and power rails aren't signals that belong to a single pin
+                if !function.iface_type.is_rail() && (function.instance.is_some() || function.signal.is_some()) {
+                    pins_by_signal.entry((board, function))
+                        .or_default()
+                        .push((pin, connection.name.as_str()));
+                }
+            }
+        }
+
+        for ((board, svg_id), uses) in functions_by_pin.iter() {
+            let distinct: HashSet<&PinFunction> = uses.iter().map(|(function, _)| *function).collect();
+            if distinct.len() > 1 {
+                let pin = board.get_pin_by_svg_id(svg_id);
+                let mut functions: Vec<String> = distinct.iter().map(|f| f.to_string()).collect();
+                functions.sort();
+                conflicts.push(FunctionConflict {
+                    connections: uses.iter().map(|(_, name)| name.to_string()).collect(),
+                    message: format!("{} pin {} is muxed to more than one function: {}", board.get_designator(), pin, functions.join(", ")),
+                });
+            }
+        }
+
+        for ((board, function), uses) in pins_by_signal.iter() {
+            let distinct: HashSet<&str> = uses.iter().map(|(pin, _)| pin.svg_id.as_str()).collect();
+            if distinct.len() > 1 {
+                let mut pins: Vec<String> = uses.iter().map(|(pin, _)| pin.to_string()).collect();
+                pins.sort();
+                pins.dedup();
+                conflicts.push(FunctionConflict {
+                    connections: uses.iter().map(|(_, name)| name.to_string()).collect(),
+                    message: format!("{} signal {} is routed to more than one pin: {}", board.get_designator(), function, pins.join(", ")),
+                });
+            }
+        }
+
+        // keep the output stable, since HashMap iteration order isn't
+        conflicts.sort_by(|a, b| a.message.cmp(&b.message));
+        conflicts
     }
 
     /// Generate a module based on the system. Lots to improve here. For now, this just saves
@@ -123,34 +1179,64 @@ impl System {
         // Fold through the list of connections, and capture the required information
         let TokenStreamAccumulator {
             required_bsp_crates,
-            ..
+            pin_type_aliases,
         } = self.connections.iter().fold(TokenStreamAccumulator::default(), |mut acc, elem| {
 
-            let Connection {
-                start_board,
-                end_board,
-                ..
-            } = elem;
-
-            // get starting board info
-            if let Some(start_board_bsp_info) = &start_board.bsp_parse_info {
-                info!("  found some bsp info");
-                if let Some(start_board_crate_ident) = &start_board_bsp_info.bsp_crate_identifier {
-                    info!("    found a crate ident");
-                    acc.required_bsp_crates.insert(start_board_crate_ident.clone());
+            // name the HAL type of each pin of the connection, if the board manifest provides it.
+            // The wires of a bus are named in the bus's module instead.
+            if elem.bus.is_none() {
+                let mut alias_name = to_camel_case(&elem.name);
+                if !alias_name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                    alias_name.insert_str(0, "Connection");
                 }
+                acc.pin_type_aliases.extend(pin_type_aliases_of(elem, &alias_name));
             }
 
-            // get ending board info
-            if let Some(end_board_bsp_info) = &end_board.bsp_parse_info {
-                if let Some(end_board_crate_ident) = &end_board_bsp_info.bsp_crate_identifier {
-                    acc.required_bsp_crates.insert(end_board_crate_ident.clone());
+            // get the info of each board on the connection
+            for board in elem.get_boards() {
+                if let Some(bsp_info) = &board.bsp_parse_info {
+                    info!("  found some bsp info");
+                    if let Some(crate_ident) = &bsp_info.bsp_crate_identifier {
+                        info!("    found a crate ident");
+                        acc.required_bsp_crates.insert(crate_ident.clone());
+                    }
                 }
             }
 
             return acc;
         });
 
+        // each bus gets a module, naming the HAL types of its pins and the devices that share it
+        let bus_modules: Vec<TokenStream> = self.get_buses().iter().map(|bus| {
+            let wires = self.get_bus_wires(bus);
+            let mut aliases: Vec<TokenStream> = Vec::new();
+            let mut devices: Vec<String> = Vec::new();
+            for wire in wires.iter() {
+                let alias_name = match wire.role() {
+                    Some(role) => to_camel_case(&role.to_lowercase()),
+                    None => to_camel_case(&wire.name),
+                };
+                aliases.extend(pin_type_aliases_of(wire, &alias_name));
+                for board in wire.get_boards() {
+                    if Some(board) != self.main_board.as_ref() && !devices.contains(&board.get_designator().to_string()) {
+                        devices.push(board.get_designator().to_string());
+                    }
+                }
+            }
+            let iface_type = wires.first().map(|wire| wire.interface_mapping.interface.iface_type).unwrap_or(InterfaceType::NONE);
+            let doc = format!(" {}: {} bus shared by {}", bus, iface_type, devices.join(", "));
+            let module = format_ident!("{}", to_snake_case(bus));
+            let num_devices = devices.len();
+            quote! {
+                #[doc = #doc]
+                pub mod #module {
+                    #(#aliases)*
+                    /// The boards that share this bus with the main board.
+                    pub const DEVICES: [&str; #num_devices] = [#(#devices),*];
+                }
+            }
+        }).collect();
+
         info!("after folding, num required crates is {}", required_bsp_crates.len());
 
         let r = required_bsp_crates.iter();
@@ -162,6 +1248,10 @@ impl System {
 
             // todo - include needed imports
 
+            #(#pin_type_aliases)*
+
+            #(#bus_modules)*
+
             pub struct System {
                 // #(#struct_field_and_type_list),*
             }
@@ -201,3 +1291,104 @@ impl System {
 
 
 }
+
+/// Pair up the pins of a stacked board with the pins of the board it's stacked on that are at
+/// the same header position, as (stacked pin, base pin).
+fn header_pin_pairs(board: &Board, base: &Board) -> Vec<(Pin, Pin)> {
+    let Some(geometry) = board.get_standard_geometry() else { return Vec::new() };
+    board.get_pins().iter().filter_map(|pin| {
+        let position = pin.header_position.as_deref()?;
+        geometry.header_offset(position)?;
+        let base_pin = base.get_pins().iter().find(|p| p.header_position.as_deref() == Some(position))?;
+        Some((pin.clone(), base_pin.clone()))
+    }).collect()
+}
+
+/// The first name of the form "<name>_N" that isn't taken.
+fn unique_name(name: &str, taken: &[String]) -> String {
+    (1..).map(|n| format!("{}_{}", name, n))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap()
+}
+
+/// Resolve the pins of an interface mapping, along with the role each one plays in the interface.
+/// Pins whose role can't be determined are left out.
+fn pins_with_roles(board: &Board, mapping: &InterfaceMapping) -> Vec<(Pin, &'static str)> {
+    mapping.pins.iter().filter_map(|name| {
+        let pin = board.find_pin(name).unwrap_or_else(|| Pin::from_svg_id(name));
+        let role = pin.role_in(mapping.interface.iface_type)?;
+        Some((pin, role))
+    }).collect()
+}
+
+/// Find the function of the pin that plays the given role in the given type of interface.
+fn role_function(pin: &Pin, iface_type: InterfaceType, role: &str) -> Option<PinFunction> {
+    pin.functions.iter().find(|f| {
+        f.iface_type == iface_type && f.signal.as_deref().is_some_and(|s| s.eq_ignore_ascii_case(role))
+    }).cloned()
+}
+
+/// If the pin is a power or ground rail, return its rail function.
+fn rail_function(pin: &Pin) -> Option<PinFunction> {
+    pin.functions.iter().find(|f| f.iface_type.is_rail()).cloned()
+}
+
+/// Convert a connection name such as "connection_0" into a type-friendly "Connection0".
+fn to_camel_case(s: &str) -> String {
+    s.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Convert a bus name into a valid module name, e.g. "my bus" into "my_bus".
+fn to_snake_case(s: &str) -> String {
+    let mut snake: String = s.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    if !snake.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        snake.insert_str(0, "bus_");
+    }
+    snake
+}
+
+/// Generate a type alias for the HAL type of each pin of a connection that has one, named
+/// "<alias_name>Pin". If more than one pin has a HAL type, each alias is also numbered.
+fn pin_type_aliases_of(connection: &Connection, alias_name: &str) -> Vec<TokenStream> {
+    let typed: Vec<&Endpoint> = connection.endpoints.iter().filter(|e| e.pin.hal_type.is_some()).collect();
+    let shared_with: Vec<&str> = connection.get_boards().iter().map(|b| b.get_designator()).collect();
+    typed.iter().enumerate().filter_map(|(idx, endpoint)| {
+        let alias = match typed.len() {
+            1 => format_ident!("{}Pin", alias_name),
+            _ => format_ident!("{}Pin{}", alias_name, idx),
+        };
+        let mut doc = format!(" {}: {} on {}", connection.name, endpoint.pin, endpoint.board.get_designator());
+        if shared_with.len() > 2 {
+            doc += &format!(", shared with {}", shared_with.join(", "));
+        }
+        pin_type_alias(&alias, &doc, &endpoint.board, &endpoint.pin)
+    }).collect()
+}
+
+/// Generate a type alias for the HAL type of a pin, with the provided doc comment.
+/// Returns None if the pin's HAL type isn't known.
+fn pin_type_alias(alias: &Ident, doc: &str, board: &Board, pin: &Pin) -> Option<TokenStream> {
+    let hal_type = pin.hal_type.as_ref()?;
+    let hal_path: syn::Path = match syn::parse_str(hal_type) {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("couldn't parse HAL type {} of pin {} on board {}: {:?}", hal_type, pin, board.get_name(), e);
+            return None;
+        },
+    };
+    Some(quote! {
+        #[doc = #doc]
+        pub type #alias = #hal_path;
+    })
+}
@@ -0,0 +1,99 @@
+//! Title: Iron Coder Project Module - Connection Table
+//! Description: This module sorts and filters the rows of the connection table, which lists
+//! the connections of the system alongside the system editor diagram.
+
+use enum_iterator::Sequence;
+
+use std::cmp;
+
+use super::system::Connection;
+
+/// The columns of the connection table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Sequence)]
+pub enum Column {
+    Name,
+    StartBoard,
+    StartPin,
+    EndBoard,
+    EndPin,
+    Type,
+    Direction,
+}
+
+impl Column {
+    pub fn title(&self) -> &'static str {
+        match self {
+            Column::Name => "name",
+            Column::StartBoard => "start board",
+            Column::StartPin => "start pin",
+            Column::EndBoard => "end board",
+            Column::EndPin => "end pin",
+            Column::Type => "type",
+            Column::Direction => "direction",
+        }
+    }
+
+    /// The text of this column for a connection, which rows are sorted and filtered by.
+    pub fn text(&self, connection: &Connection) -> String {
+        let endpoint = |idx: usize| connection.endpoints.get(idx);
+        let interface = &connection.interface_mapping.interface;
+        match self {
+            Column::Name => connection.name.clone(),
+            Column::StartBoard => endpoint(0).map(|e| e.board.get_designator().to_string()).unwrap_or_default(),
+            Column::StartPin => endpoint(0).map(|e| e.pin.name().to_string()).unwrap_or_default(),
+            Column::EndBoard => endpoint(1).map(|e| e.board.get_designator().to_string()).unwrap_or_default(),
+            Column::EndPin => endpoint(1).map(|e| e.pin.name().to_string()).unwrap_or_default(),
+            Column::Type => interface.iface_type.to_string(),
+            Column::Direction => interface.direction.to_string(),
+        }
+    }
+}
+
+/// How the connection table is sorted and filtered.
+#[derive(Clone, Debug)]
+pub struct TableState {
+    pub sort: Column,
+    pub ascending: bool,
+    /// Only connections with this text in one of their columns are shown
+    pub filter: String,
+}
+
+impl Default for TableState {
+    fn default() -> Self {
+        Self {
+            sort: Column::Name,
+            ascending: true,
+            filter: String::new(),
+        }
+    }
+}
+
+impl TableState {
+    /// Sort the table by a column, or reverse the order if it's already sorted by that column.
+    pub fn sort_by(&mut self, column: Column) {
+        if self.sort == column {
+            self.ascending = !self.ascending;
+        } else {
+            self.sort = column;
+            self.ascending = true;
+        }
+    }
+
+    /// The indices of the connections to show, in the order they're shown. Both the filter and
+    /// the sort ignore case, and connections that sort the same keep their order in the system.
+    pub fn rows(&self, connections: &[Connection]) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+        let mut rows: Vec<usize> = (0..connections.len()).filter(|idx| {
+            filter.is_empty() || enum_iterator::all::<Column>().any(|column| {
+                column.text(&connections[*idx]).to_lowercase().contains(&filter)
+            })
+        }).collect();
+        let key = |idx: &usize| self.sort.text(&connections[*idx]).to_lowercase();
+        if self.ascending {
+            rows.sort_by_cached_key(key);
+        } else {
+            rows.sort_by_cached_key(|idx| cmp::Reverse(key(idx)));
+        }
+        rows
+    }
+}
@@ -2,9 +2,1099 @@
 mod project_tests {
     use crate::project::*;
 
+    /// The boards of the library that these tests are run against.
+    fn library_boards() -> Vec<crate::board::Board> {
+        crate::board::get_boards(std::path::Path::new("./iron-coder-boards"), None)
+    }
+
+    /// Find a board by name, e.g. among the library boards.
+    fn find_board(boards: &[crate::board::Board], name: &str) -> crate::board::Board {
+        boards.iter().find(|b| b.get_name() == name).unwrap().clone()
+    }
+
+    /// A project that knows the library boards, with a Feather RP2040 as its main board and an
+    /// OLED FeatherWing as a peripheral, which most of these tests start from. The library
+    /// boards are returned along with it.
+    fn feather_project() -> (Project, Vec<crate::board::Board>) {
+        let boards = library_boards();
+        let mut project = Project::default();
+        project.set_library_boards(boards.clone(), None);
+        project.add_board(find_board(&boards, "Feather RP2040"));
+        project.add_board(find_board(&boards, "OLED Featherwing (128x64)"));
+        (project, boards)
+    }
+
     #[test]
     pub fn test_save_as() {
-        let mut project: Project = Project{name : "test_project".to_string(), location: None, system: Default::default(), code_editor: Default::default(), terminal_buffer: "".to_string(), receiver: None, current_view: Default::default(), known_boards: vec![], repo: Repository };
+        let mut project: Project = Project{name : "test_project".to_string(), location: None, system: Default::default(), code_editor: Default::default(), terminal_buffer: "".to_string(), receiver: None, current_view: Default::default(), known_boards: vec![], library_boards: vec![], board_cache_dir: None, board_loader: None, repo: None, migration_report: None, history: Default::default(), selection: Default::default() };
         project.save_as(true).expect("Project Failed to Save!");
     }
-}
\ No newline at end of file
+
+    #[test]
+    pub fn test_function_conflicts() {
+        use crate::project::system::{Connection, System};
+        let boards = library_boards();
+        let feather = find_board(&boards, "Feather RP2040");
+        let connection = |name: &str, pin: &str, function: &str| {
+            let mut c = Connection::new(name.to_string(), feather.clone(), feather.find_pin(pin).unwrap(), feather.clone(), feather.find_pin("GND").unwrap());
+            c.endpoints[0].function = Some(function.parse().unwrap());
+            c
+        };
+        let mut system = System::default();
+        system.connections.push(connection("sda", "SDA", "I2C1.SDA"));
+        system.connections.push(connection("scl", "SCL", "I2C1.SCL"));
+        assert!(system.function_conflicts().is_empty());
+        // the same pin muxed to two different functions
+        system.connections.push(connection("pwm", "SDA", "PWM1.A"));
+        assert_eq!(system.function_conflicts().len(), 1);
+        assert_eq!(system.function_conflicts()[0].connections, vec!["sda", "pwm"]);
+        system.connections.pop();
+        // the same signal routed to two different pins (GPIO6 can also be I2C1.SDA)
+        system.connections.push(connection("sda2", "GPIO6", "I2C1.SDA"));
+        let conflicts = system.function_conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].message.contains("I2C1.SDA"), "{}", conflicts[0]);
+    }
+
+    #[test]
+    pub fn test_rail_connections() {
+        use crate::board::pinout::InterfaceType;
+        use crate::project::system::{Connection, System};
+        let boards = library_boards();
+        let feather = find_board(&boards, "Feather RP2040");
+        let mut system = System::default();
+        // power and ground rails are connectable, and pick up their rail type
+        for (name, rail) in [("gnd_0", "GND"), ("gnd_1", "GND"), ("power_0", "3V"), ("power_1", "3V")] {
+            let pin = feather.find_pin(rail).unwrap();
+            let other = feather.find_pin("D5").unwrap();
+            system.connections.push(Connection::new(name.to_string(), feather.clone(), pin, feather.clone(), other));
+        }
+        assert_eq!(system.connections[0].interface_mapping.interface.iface_type, InterfaceType::GND);
+        assert_eq!(system.connections[2].interface_mapping.interface.iface_type, InterfaceType::POWER);
+        assert_eq!(system.connections[2].endpoints[0].function.as_ref().unwrap().to_string(), "POWER.3V3");
+        // a rail can be shared by any number of connections
+        assert!(system.function_conflicts().is_empty());
+    }
+
+    #[test]
+    pub fn test_electrical_rule_check() {
+        use crate::board::{self, pinout::{InterfaceType, Pin}, validation::Severity};
+        use crate::project::erc;
+        use crate::project::system::{Connection, System};
+        let boards = library_boards();
+        let feather = find_board(&boards, "Feather RP2040");
+        let oled = find_board(&boards, "OLED Featherwing (128x64)");
+        let connect = |name: &str, start: (&board::Board, Pin), end: (&board::Board, Pin), iface_type: InterfaceType| {
+            let mut c = Connection::new(name.to_string(), start.0.clone(), start.1, end.0.clone(), end.1);
+            c.interface_mapping.interface.iface_type = iface_type;
+            c
+        };
+        let pin = |board: &board::Board, name: &str| board.find_pin(name).unwrap();
+
+        // the OLED's pull-ups take care of the I2C bus
+        let mut system = System::default();
+        system.connections.push(connect("sda", (&feather, pin(&feather, "SDA")), (&oled, pin(&oled, "SDA")), InterfaceType::I2C));
+        assert!(erc::check(&system).is_empty(), "{:?}", erc::check(&system));
+        // ...but an I2C bus between two Feather pins has none
+        system.connections.push(connect("i2c", (&feather, pin(&feather, "SCL")), (&feather, pin(&feather, "D5")), InterfaceType::I2C));
+        let violations = erc::check(&system);
+        assert_eq!(violations.len(), 1);
+        assert_eq!((violations[0].severity, violations[0].connections.clone()), (Severity::Warning, vec!["i2c".to_string()]));
+
+        // 5 V into a 3.3 V-only GPIO
+        let mut system = System::default();
+        system.connections.push(connect("usb", (&feather, pin(&feather, "USB")), (&feather, pin(&feather, "A0")), InterfaceType::NONE));
+        let violations = erc::check(&system);
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0].message.contains("only tolerates 3.3 V"), "{}", violations[0]);
+
+        // two power outputs on one net, and a power output shorted to ground
+        let mut system = System::default();
+        system.connections.push(connect("rails", (&feather, pin(&feather, "3V")), (&feather, pin(&feather, "USB")), InterfaceType::NONE));
+        system.connections.push(connect("short", (&feather, pin(&feather, "3V")), (&feather, pin(&feather, "GND")), InterfaceType::NONE));
+        let messages: Vec<String> = erc::check(&system).into_iter().map(|v| v.message).collect();
+        assert!(messages.iter().any(|m| m.starts_with("more than one output drives the same net")), "{:?}", messages);
+        assert!(messages.iter().any(|m| m.ends_with("is shorted to ground")), "{:?}", messages);
+
+        // UART TX wired to TX
+        let mut system = System::default();
+        system.connections.push(connect("uart", (&feather, pin(&feather, "TX")), (&oled, oled.get_pin_by_svg_id("tx")), InterfaceType::UART));
+        let violations = erc::check(&system);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].message, "UART TX is wired to TX, but TX should be wired to RX");
+    }
+
+    #[test]
+    pub fn test_bus_connections() {
+        use crate::board::{self, pinout::InterfaceType};
+        use crate::project::system::System;
+        let boards = library_boards();
+        let feather = find_board(&boards, "Feather RP2040");
+        let oled = find_board(&boards, "OLED Featherwing (128x64)");
+        let mapping = |board: &board::Board, iface_type: InterfaceType| {
+            board.get_pinout().into_iter().find(|m| m.interface.iface_type == iface_type).unwrap()
+        };
+
+        // the I2C pins are paired up by role, and take on the interface type and pin functions
+        let mut system = System::default();
+        system.add_bus_connection(&feather, &mapping(&feather, InterfaceType::I2C), &oled, &mapping(&oled, InterfaceType::I2C)).unwrap();
+        assert_eq!(system.get_buses(), vec!["i2c_0"]);
+        let wires = system.get_bus_wires("i2c_0");
+        assert_eq!(wires.len(), 2);
+        for wire in wires {
+            assert_eq!(wire.interface_mapping.interface.iface_type, InterfaceType::I2C);
+            assert_eq!(wire.endpoints[0].pin.svg_id, wire.endpoints[1].pin.svg_id);
+            let function = wire.endpoints[0].function.as_ref().unwrap().to_string();
+            assert!(function == "I2C1.SCL" || function == "I2C1.SDA", "{}", function);
+        }
+        // a UART crosses TX over to RX
+        system.add_bus_connection(&feather, &mapping(&feather, InterfaceType::UART), &feather, &mapping(&feather, InterfaceType::UART)).unwrap();
+        for wire in system.get_bus_wires("uart_0") {
+            assert_ne!(wire.endpoints[0].pin.svg_id, wire.endpoints[1].pin.svg_id);
+        }
+        // interfaces of different types can't be connected
+        assert!(system.add_bus_connection(&feather, &mapping(&feather, InterfaceType::SPI), &oled, &mapping(&oled, InterfaceType::I2C)).is_err());
+
+        // bus names are unique and non-empty
+        assert!(system.rename_bus("i2c_0", "uart_0").is_err());
+        assert!(system.rename_bus("i2c_0", "").is_err());
+        assert!(system.rename_bus("spi_0", "display").is_err());
+        system.rename_bus("i2c_0", "display").unwrap();
+        assert_eq!(system.get_buses(), vec!["display", "uart_0"]);
+        assert!(system.get_bus_wires("display").iter().all(|wire| wire.name.starts_with("display_")));
+        system.remove_bus("display");
+        assert_eq!(system.get_buses(), vec!["uart_0"]);
+        assert_eq!(system.connections.len(), 2);
+    }
+
+    #[test]
+    pub fn test_multi_drop_nets() {
+        use crate::board::{self, pinout::{InterfaceMapping, InterfaceType}};
+        use crate::project::system::{Connection, System};
+        let boards = library_boards();
+        let feather = find_board(&boards, "Feather RP2040");
+        let oled = find_board(&boards, "OLED Featherwing (128x64)");
+        let pin = |board: &board::Board, name: &str| board.find_pin(name).unwrap();
+
+        // connecting a pin that's already connected joins the existing connection
+        let mut system = System::default();
+        system.connect_pins(&feather, &pin(&feather, "D5"), &feather, &pin(&feather, "D6")).unwrap();
+        system.connect_pins(&feather, &pin(&feather, "D9"), &feather, &pin(&feather, "D6")).unwrap();
+        assert_eq!(system.connections.len(), 1);
+        assert_eq!(system.connections[0].endpoints.len(), 3);
+        // ...and connecting two connections merges them
+        system.connect_pins(&feather, &pin(&feather, "D10"), &feather, &pin(&feather, "D11")).unwrap();
+        system.connect_pins(&feather, &pin(&feather, "D11"), &feather, &pin(&feather, "D5")).unwrap();
+        assert_eq!(system.connections.len(), 1);
+        assert_eq!(system.connections[0].endpoints.len(), 5);
+        // a pin can't be connected to itself, nor to a pin or connection of another type
+        assert!(system.connect_pins(&feather, &pin(&feather, "D5"), &feather, &pin(&feather, "D5")).is_err());
+        assert!(system.connect_pins(&feather, &pin(&feather, "GND"), &feather, &pin(&feather, "3V")).is_err());
+        system.connections[0].interface_mapping.interface.iface_type = InterfaceType::GPIO;
+        assert!(system.connect_pins(&feather, &pin(&feather, "GND"), &feather, &pin(&feather, "D5")).is_err());
+        assert_eq!(system.connections.len(), 1);
+        assert_eq!(system.connections[0].endpoints.len(), 5);
+        // ...but a pin without a type can join a connection that has one
+        system.connect_pins(&feather, &pin(&feather, "D12"), &feather, &pin(&feather, "D5")).unwrap();
+        assert_eq!(system.connections[0].endpoints.len(), 6);
+        assert_eq!(system.connections[0].interface_mapping.interface.iface_type, InterfaceType::GPIO);
+
+        // a second peripheral joins each shared wire of a bus
+        let mut system = System { main_board: Some(feather.clone()), ..Default::default() };
+        let i2c = |board: &board::Board| board.get_pinout().into_iter().find(|m| m.interface.iface_type == InterfaceType::I2C).unwrap();
+        system.add_bus_connection(&feather, &i2c(&feather), &oled, &i2c(&oled)).unwrap();
+        let sensor: board::Board = toml::from_str(
+            &toml::to_string(&oled).unwrap().replacen(oled.get_name(), "Sensor", 1)
+        ).unwrap();
+        system.join_bus("i2c_0", &sensor, &i2c(&sensor)).unwrap();
+        assert!(system.join_bus("i2c_0", &sensor, &i2c(&sensor)).is_err());
+        for wire in system.get_bus_wires("i2c_0") {
+            assert_eq!(wire.get_boards().len(), 3);
+        }
+
+        // the bus gets a module in the system module
+        let dir = std::env::temp_dir().join("iron_coder_test_multi_drop_nets");
+        std::fs::create_dir_all(&dir).unwrap();
+        let module_path = dir.join("system.rs");
+        system.generate_system_module(&module_path).unwrap();
+        let module = std::fs::read_to_string(&module_path).unwrap();
+        assert!(module.contains("pub mod i2c_0"), "{}", module);
+        assert!(module.contains("pub type SclPin = adafruit_feather_rp2040::hal::gpio::bank0::Gpio3;"), "{}", module);
+        assert!(module.contains(r#"["OLED Featherwing (128x64)", "Sensor"]"#), "{}", module);
+
+        // removing a board removes its pins from each connection
+        system.peripheral_boards = vec![oled.clone(), sensor.clone()];
+        system.remove_board(sensor).unwrap();
+        assert!(system.connections.iter().all(|c| c.endpoints.len() == 2));
+
+        // connections saved as pin-to-pin by earlier versions still load
+        let mut legacy = toml::Table::new();
+        legacy.insert("name".to_string(), "connection_0".into());
+        legacy.insert("start_board".to_string(), toml::Value::try_from(&feather).unwrap());
+        legacy.insert("start_pin".to_string(), "d5".into());
+        legacy.insert("end_board".to_string(), toml::Value::try_from(&feather).unwrap());
+        legacy.insert("end_pin".to_string(), "d6".into());
+        legacy.insert("interface_mapping".to_string(), toml::Value::try_from(InterfaceMapping::default()).unwrap());
+        let connection: Connection = legacy.try_into().unwrap();
+        assert_eq!(connection.endpoints.len(), 2);
+        assert_eq!(connection.endpoints[1].pin.svg_id, "d6");
+    }
+
+    #[test]
+    pub fn test_board_instances() {
+        let boards = library_boards();
+        let feather = find_board(&boards, "Feather RP2040");
+        let oled = find_board(&boards, "OLED Featherwing (128x64)");
+        assert_eq!(oled.library_id(), "Adafruit/OLED Featherwing (128x64)");
+        let mut project = Project::default();
+        project.add_board(feather.clone());
+        project.add_board(feather.clone());
+        assert_eq!(project.system.get_all_boards().len(), 1);
+        // two of the same peripheral are two different boards, each with its own designator
+        project.add_board(oled.clone());
+        project.add_board(oled.clone());
+        let oleds = project.system.peripheral_boards.clone();
+        assert_eq!(oleds.len(), 2);
+        assert_ne!(oleds[0], oleds[1]);
+        assert!(oleds[0].is_same_library_board(&oleds[1]));
+        assert_eq!(oleds[0].get_designator(), "oled1");
+        assert_eq!(oleds[1].get_designator(), "oled2");
+        assert_eq!(project.system.main_board.as_ref().unwrap().get_designator(), "feather1");
+
+        let main_board = project.system.main_board.clone().unwrap();
+        let pin = oleds[1].find_pin("SDA").unwrap();
+        project.system.connect_pins(&main_board, &main_board.find_pin("SDA").unwrap(), &oleds[1], &pin).unwrap();
+        assert!(project.system.find_connection(&oleds[0], &pin.svg_id).is_none());
+        // renaming a board keeps its identity, and the connections follow it
+        assert!(project.system.set_designator(&oleds[1], "oled1").is_err());
+        assert!(project.system.set_designator(&oleds[1], "display").is_ok());
+        assert_eq!(project.system.peripheral_boards[1].get_designator(), "display");
+        assert_eq!(project.system.connections[0].endpoints[1].board.get_designator(), "display");
+        assert!(project.system.find_connection(&oleds[1], &pin.svg_id).is_some());
+
+        // boards from projects saved without instances get them when loaded
+        for board in project.system.get_all_boards_mut() {
+            board.set_instance(None);
+        }
+        project.system.peripheral_boards.pop();
+        project.system.connections[0].endpoints[1].board.set_instance(None);
+        project.system.assign_instances();
+        let oled = project.system.peripheral_boards[0].clone();
+        assert_eq!(oled.get_designator(), "oled1");
+        assert_eq!(project.system.connections[0].endpoints[1].board, oled);
+    }
+
+    #[test]
+    pub fn test_board_references() {
+        let boards = library_boards();
+        let feather = find_board(&boards, "Feather RP2040");
+        let oled = find_board(&boards, "OLED Featherwing (128x64)");
+        let dir = std::env::temp_dir().join("iron_coder_test_board_references");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut project = Project { location: Some(dir.clone()), ..Default::default() };
+        project.add_board(feather.clone());
+        project.add_board(oled.clone());
+        project.add_board(oled.clone());
+        let main_board = project.system.main_board.clone().unwrap();
+        let display = project.system.peripheral_boards[1].clone();
+        project.system.connect_pins(&main_board, &main_board.find_pin("SDA").unwrap(), &display, &display.find_pin("SDA").unwrap()).unwrap();
+        project.save().unwrap();
+
+        // the project file refers to the library boards, rather than copying them
+        let toml_str = std::fs::read_to_string(dir.join(PROJECT_FILE_NAME)).unwrap();
+        assert!(toml_str.contains(r#"library_id = "Adafruit/Feather RP2040""#), "{}", toml_str);
+        assert!(toml_str.contains(r#"board = "oled2""#), "{}", toml_str);
+        assert!(!toml_str.contains("manufacturer"), "{}", toml_str);
+        assert!(toml_str.starts_with("schema_version = 3"), "{}", toml_str);
+
+        // loading the project resolves the references against the known boards
+        let mut loaded = Project::default();
+        loaded.set_library_boards(boards.clone(), None);
+        loaded.load_from(&dir).unwrap();
+        assert_eq!(loaded.system.get_all_boards(), project.system.get_all_boards());
+        assert_eq!(loaded.system.peripheral_boards[1].get_designator(), "oled2");
+        assert!(loaded.system.peripheral_boards[1].svg_board_info.is_some());
+        let endpoint = &loaded.system.connections[0].endpoints[1];
+        assert_eq!(endpoint.board, display);
+        assert_eq!(endpoint.pin, display.find_pin("SDA").unwrap());
+        assert!(loaded.migration_report.is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_project_migration() {
+        use crate::board;
+        use crate::project::migration::{self, MigrationError};
+        let boards = library_boards();
+        let feather = find_board(&boards, "Feather RP2040");
+        let oled = find_board(&boards, "OLED Featherwing (128x64)");
+        let dir = std::env::temp_dir().join("iron_coder_test_project_migration");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // an unversioned project file, with copies of each board and a pin-to-pin connection
+        let mut connection = toml::Table::new();
+        connection.insert("name".to_string(), "connection_0".into());
+        connection.insert("start_board".to_string(), toml::Value::try_from(&feather).unwrap());
+        connection.insert("start_pin".to_string(), "scl".into());
+        connection.insert("end_board".to_string(), toml::Value::try_from(&oled).unwrap());
+        connection.insert("end_pin".to_string(), "scl".into());
+        connection.insert("interface_mapping".to_string(), toml::Value::try_from(board::pinout::InterfaceMapping::default()).unwrap());
+        let mut system = toml::Table::new();
+        system.insert("main_board".to_string(), toml::Value::try_from(&feather).unwrap());
+        system.insert("peripheral_boards".to_string(), toml::Value::try_from(vec![&oled]).unwrap());
+        system.insert("connections".to_string(), vec![toml::Value::from(connection)].into());
+        let mut legacy = toml::Table::new();
+        legacy.insert("name".to_string(), "legacy".into());
+        legacy.insert("system".to_string(), system.into());
+        let legacy = toml::to_string(&legacy).unwrap();
+        std::fs::write(dir.join(PROJECT_FILE_NAME), &legacy).unwrap();
+
+        let mut project = Project::default();
+        project.set_library_boards(boards.clone(), None);
+        project.load_from(&dir).unwrap();
+        assert_eq!(project.system.main_board.as_ref().unwrap().get_designator(), "feather1");
+        assert_eq!(project.system.peripheral_boards[0].get_designator(), "oled1");
+        let endpoints = &project.system.connections[0].endpoints;
+        assert_eq!(endpoints[1].board, project.system.peripheral_boards[0]);
+        assert_eq!(endpoints[1].pin, oled.find_pin("SCL").unwrap());
+        // the boards of an unversioned project file are laid out too
+        let placement = project.system.get_placement(&project.system.peripheral_boards[0]);
+        assert_eq!((placement.x, placement.y), (10.0, 10.0));
+
+        // the original is backed up, and the changes are reported
+        let report = project.migration_report.clone().unwrap();
+        assert_eq!(report.from_version, 1);
+        assert_eq!(std::fs::read_to_string(report.backup.unwrap()).unwrap(), legacy);
+        assert!(report.changes.contains(&"The OLED Featherwing (128x64) board is now called oled1.".to_string()), "{:?}", report.changes);
+        assert!(report.changes.contains(&"1 pin-to-pin connections were converted to nets.".to_string()), "{:?}", report.changes);
+
+        // project files from a newer Iron Coder aren't loaded
+        let mut table: toml::Table = toml::from_str(&legacy).unwrap();
+        table.insert("schema_version".to_string(), toml::Value::Integer(migration::SCHEMA_VERSION as i64 + 1));
+        assert!(matches!(migration::migrate(&mut table), Err(MigrationError::NewerSchemaError(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_project_migration_v2() {
+        use crate::project::system::{Placement, Viewport, Waypoint};
+        let dir = std::env::temp_dir().join("iron_coder_test_project_migration_v2");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // a project that uses each of the fields added in version 3
+        let (mut project, boards) = feather_project();
+        project.location = Some(dir.clone());
+        project.add_board(find_board(&boards, "OLED Featherwing (128x64)"));
+        let main_board = project.system.main_board.clone().unwrap();
+        let (display, spare) = (project.system.peripheral_boards[0].clone(), project.system.peripheral_boards[1].clone());
+        project.system.set_placement(&spare, Placement { x: 120.0, y: 40.0, rotation: 90, flipped: true, stacked_on: None });
+        project.system.stack(&display).unwrap();
+        project.system.connect_pins(&main_board, &main_board.find_pin("A0").unwrap(), &spare, &spare.find_pin("SDA").unwrap()).unwrap();
+        let wire = project.system.connections.iter_mut().find(|c| c.contains(&spare, &spare.find_pin("SDA").unwrap().svg_id)).unwrap();
+        wire.waypoints = vec![Waypoint { x: 60.0, y: 20.0 }];
+        wire.color = Some(egui::Color32::from_rgb(200, 40, 40));
+        project.system.viewport = Viewport { zoom: 8.0, orthogonal_routing: true, show_legend: true, show_connection_table: true, ..Default::default() };
+        project.save().unwrap();
+
+        // they're all read back from a version 3 project file, which isn't migrated
+        let mut loaded = Project::default();
+        loaded.set_library_boards(boards.clone(), None);
+        loaded.load_from(&dir).unwrap();
+        assert!(loaded.migration_report.is_none());
+        for board in project.system.get_all_boards().iter() {
+            assert_eq!(loaded.system.get_placement(board), project.system.get_placement(board));
+        }
+        assert_eq!(loaded.system.get_placement(&display).stacked_on, main_board.get_instance_id());
+        assert_eq!(loaded.system.viewport, project.system.viewport);
+        assert!(loaded.system.snapshot() == project.system.snapshot());
+        assert!(loaded.system.connections.iter().flat_map(|c| c.endpoints.iter()).any(|e| e.stacked_by == display.get_instance_id()));
+
+        // a version 2 project file has none of them
+        let mut table: toml::Table = toml::from_str(&std::fs::read_to_string(dir.join(PROJECT_FILE_NAME)).unwrap()).unwrap();
+        table.insert("schema_version".to_string(), toml::Value::Integer(2));
+        let system = table.get_mut("system").unwrap().as_table_mut().unwrap();
+        system.remove("viewport");
+        system.get_mut("main_board").unwrap().as_table_mut().unwrap().remove("placement");
+        // ...except that the spare board was given a placement by hand
+        let peripheral_boards = system.get_mut("peripheral_boards").unwrap().as_array_mut().unwrap();
+        let spare_index = peripheral_boards.len() - 1;
+        for board in peripheral_boards[..spare_index].iter_mut() {
+            board.as_table_mut().unwrap().remove("placement");
+        }
+        for connection in system.get_mut("connections").unwrap().as_array_mut().unwrap() {
+            let connection = connection.as_table_mut().unwrap();
+            connection.remove("waypoints");
+            connection.remove("color");
+            for endpoint in connection.get_mut("endpoints").unwrap().as_array_mut().unwrap() {
+                endpoint.as_table_mut().unwrap().remove("stacked_by");
+            }
+        }
+        let v2 = toml::to_string(&table).unwrap();
+        assert_eq!(v2.matches("[system.peripheral_boards.placement]").count(), 1, "{}", v2);
+        assert!(!v2.contains("stacked") && !v2.contains("waypoints"), "{}", v2);
+        std::fs::write(dir.join(PROJECT_FILE_NAME), &v2).unwrap();
+
+        // the new fields take their defaults, except that the boards are cascaded rather than
+        // all being drawn at the origin
+        let mut loaded = Project::default();
+        loaded.set_library_boards(boards.clone(), None);
+        loaded.load_from(&dir).unwrap();
+        let report = loaded.migration_report.clone().unwrap();
+        assert_eq!(report.from_version, 2);
+        assert_eq!(std::fs::read_to_string(report.backup.unwrap()).unwrap(), v2);
+        assert_eq!(report.changes, vec!["2 boards were laid out on the system editor canvas.".to_string()]);
+        for (idx, board) in loaded.system.get_all_boards().iter().enumerate() {
+            let offset = 10.0 * idx as f32;
+            let placement = match *board == spare {
+                true => project.system.get_placement(&spare),
+                false => Placement { x: offset, y: offset, ..Default::default() },
+            };
+            assert_eq!(loaded.system.get_placement(board), placement);
+        }
+        assert_eq!(loaded.system.viewport, Viewport::default());
+        assert_eq!(loaded.system.connections.len(), project.system.connections.len());
+        for connection in loaded.system.connections.iter() {
+            assert!(connection.waypoints.is_empty());
+            assert!(connection.color.is_none());
+            assert!(connection.endpoints.iter().all(|e| e.stacked_by.is_none()));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_board_placement() {
+        use crate::project::system::Placement;
+        let boards = library_boards();
+        let feather = find_board(&boards, "Feather RP2040");
+        let oled = find_board(&boards, "OLED Featherwing (128x64)");
+        let dir = std::env::temp_dir().join("iron_coder_test_board_placement");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut project = Project { location: Some(dir.clone()), ..Default::default() };
+        project.add_board(feather.clone());
+        project.add_board(oled.clone());
+        // new boards don't land on top of each other
+        let main_board = project.system.main_board.clone().unwrap();
+        let display = project.system.peripheral_boards[0].clone();
+        assert_ne!(project.system.get_placement(&main_board), project.system.get_placement(&display));
+
+        let mut placement = Placement { x: 42.5, y: -3.0, ..Default::default() };
+        placement.rotate();
+        placement.rotate();
+        placement.rotate();
+        placement.rotate();
+        placement.rotate();
+        placement.flipped = true;
+        assert_eq!(placement.rotation, 90);
+        project.system.set_placement(&display, placement);
+        project.system.viewport.pan_x = 120.0;
+        project.system.viewport.zoom = 3.4;
+        project.save().unwrap();
+
+        // the placements and viewport are restored exactly when the project is loaded
+        let mut loaded = Project::default();
+        loaded.set_library_boards(boards.clone(), None);
+        loaded.load_from(&dir).unwrap();
+        assert_eq!(loaded.system.get_placement(&display), placement);
+        assert_eq!(loaded.system.get_placement(&main_board), project.system.get_placement(&main_board));
+        assert_eq!(loaded.system.viewport, project.system.viewport);
+
+        // removing a board forgets its placement
+        loaded.system.remove_board(display.clone()).unwrap();
+        assert!(!loaded.system.placements.contains_key(&display.get_instance_id().unwrap()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_canvas_transform() {
+        use crate::project::canvas::{self, Canvas};
+        use crate::project::system::{Placement, Viewport};
+        let rect = egui::Rect::from_min_size(egui::pos2(100.0, 50.0), egui::vec2(800.0, 600.0));
+        let mut viewport = Viewport::default();
+        let canvas = Canvas::new(rect, &viewport);
+        let p = egui::pos2(12.5, -4.0);
+        assert_eq!(canvas.to_canvas(canvas.to_screen(p)), p);
+
+        // zooming keeps the point under the cursor in place
+        let cursor = egui::pos2(400.0, 300.0);
+        let anchor = canvas.to_canvas(cursor);
+        canvas::zoom_around(&mut viewport, rect, cursor, 2.0);
+        assert_eq!(viewport.zoom, 10.0);
+        let zoomed = Canvas::new(rect, &viewport);
+        assert!((zoomed.to_screen(anchor) - cursor).length() < 1e-3);
+        canvas::zoom_around(&mut viewport, rect, cursor, 1000.0);
+        assert_eq!(viewport.zoom, canvas::MAX_ZOOM);
+
+        // fitting an area shows all of it, centered
+        let area = egui::Rect::from_min_size(egui::pos2(-20.0, 30.0), egui::vec2(160.0, 40.0));
+        canvas::fit(&mut viewport, rect, area);
+        let fitted = Canvas::new(rect, &viewport);
+        assert!(fitted.visible_area().contains_rect(area));
+        assert!((fitted.to_screen(area.center()) - rect.center()).length() < 1e-3);
+
+        assert_eq!(canvas::snap(12.4), 12.0);
+        // rotating a board a quarter turn clockwise moves its top-left corner to the top-right
+        let size = egui::vec2(50.0, 20.0);
+        let mut placement = Placement::default();
+        placement.rotate();
+        assert_eq!(placement.placed_size(size), egui::vec2(20.0, 50.0));
+        assert_eq!(placement.place_point(size, egui::pos2(0.0, 0.0)), egui::pos2(20.0, 0.0));
+        placement.flipped = true;
+        assert_eq!(placement.place_point(size, egui::pos2(0.0, 0.0)), egui::pos2(20.0, 50.0));
+    }
+
+    #[test]
+    pub fn test_undo_redo() {
+        use crate::project::history::History;
+        use crate::project::system::Placement;
+        let (mut project, _) = feather_project();
+        let main_board = project.system.main_board.clone().unwrap();
+        let display = project.system.peripheral_boards[0].clone();
+
+        // continuing edits with the same description are merged until the history is closed
+        let mut history = History::default();
+        let start = project.system.snapshot();
+        history.clear(start.clone());
+        for x in [1.0, 2.0, 3.0] {
+            project.system.set_placement(&display, Placement { x, ..Default::default() });
+            history.record("move oled1", project.system.snapshot(), true);
+        }
+        history.close();
+        project.system.set_placement(&display, Placement { x: 4.0, ..Default::default() });
+        history.record("move oled1", project.system.snapshot(), true);
+        let moved = project.system.snapshot();
+        assert_eq!(history.undo_description(), Some("move oled1"));
+        history.undo();
+        assert_eq!(history.undo(), Some(start.clone()));
+        assert!(history.undo().is_none());
+        history.redo();
+        assert_eq!(history.redo(), Some(moved));
+        assert_eq!(history.redo_description(), None);
+
+        // removing a board and undoing it brings back the board along with its connections
+        let sda = main_board.find_pin("SDA").unwrap();
+        let oled_sda = display.find_pin("SDA").unwrap();
+        project.system.connect_pins(&main_board, &sda, &display, &oled_sda).unwrap();
+        project.record_edit("connect pins", false);
+        let connected = project.system.snapshot();
+        project.system.remove_board(display.clone()).unwrap();
+        project.record_edit("remove oled1", false);
+        assert!(project.system.connections.is_empty());
+        project.undo();
+        assert_eq!(project.system.snapshot(), connected);
+        assert_eq!(project.system.peripheral_boards[0], display);
+        assert!(project.system.peripheral_boards[0].svg_board_info.is_some());
+        project.redo();
+        assert!(project.system.peripheral_boards.is_empty());
+        // an edit made after an undo or redo starts from the restored system
+        let removed = project.system.snapshot();
+        project.system.set_placement(&main_board, Placement { x: 50.0, ..Default::default() });
+        project.record_edit("move feather1", false);
+        project.undo();
+        assert_eq!(project.system.snapshot(), removed);
+        // adding boards can be undone too
+        project.undo();
+        project.undo();
+        project.undo();
+        assert!(project.system.peripheral_boards.is_empty());
+        assert!(project.system.main_board.is_some());
+    }
+
+    #[test]
+    pub fn test_toolbar_undo() {
+        let (mut project, _) = feather_project();
+
+        // show the system editor for a frame, with some pointer events
+        let ctx = egui::Context::default();
+        let mut run_frame = |project: &mut Project, events: Vec<egui::Event>| {
+            let input = egui::RawInput {
+                screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(1280.0, 800.0))),
+                events,
+                ..Default::default()
+            };
+            let _ = ctx.run(input, |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| project.display_system_editor_boards(ctx, ui));
+            });
+        };
+        run_frame(&mut project, vec![]);
+        run_frame(&mut project, vec![]);
+
+        // click the undo button, which is the first one in the toolbar
+        let toolbar = ctx.memory(|m| m.area_rect(egui::Id::new("system_editor_canvas_toolbar"))).unwrap();
+        let pos = toolbar.left_center() + egui::vec2(20.0, 0.0);
+        let click = |pressed| egui::Event::PointerButton { pos, button: egui::PointerButton::Primary, pressed, modifiers: Default::default() };
+        run_frame(&mut project, vec![egui::Event::PointerMoved(pos)]);
+        run_frame(&mut project, vec![click(true)]);
+        run_frame(&mut project, vec![click(false)]);
+        run_frame(&mut project, vec![]);
+
+        // undoing isn't recorded as an edit of its own, so it can still be redone
+        assert!(project.system.peripheral_boards.is_empty());
+        assert_eq!(project.history.redo_description(), Some("add oled1"));
+        assert_eq!(project.history.undo_description(), Some("add feather1"));
+        project.redo();
+        assert_eq!(project.system.peripheral_boards.len(), 1);
+    }
+
+    #[test]
+    pub fn test_copy_paste() {
+        let (mut project, boards) = feather_project();
+        let main_board = project.system.main_board.clone().unwrap();
+        let display = project.system.peripheral_boards[0].clone();
+        let sda = main_board.find_pin("SDA").unwrap();
+        project.system.connect_pins(&main_board, &sda, &display, &display.find_pin("SDA").unwrap()).unwrap();
+
+        // nothing is copied until something is selected
+        assert!(project.copy_selection().is_none());
+        project.selection.click_board(&main_board, false);
+        project.selection.click_board(&display, true);
+        let text = project.copy_selection().unwrap();
+
+        // pasting into another project brings along the connection between the boards
+        let mut other = Project::default();
+        other.set_library_boards(boards.clone(), None);
+        assert!(!other.paste("name = \"not a system\""));
+        assert!(other.paste(&text));
+        assert_eq!(other.system.main_board.as_ref().unwrap().get_designator(), "feather1");
+        assert_eq!(other.system.peripheral_boards[0].get_designator(), "oled1");
+        assert!(other.system.peripheral_boards[0].svg_board_info.is_some());
+        assert_eq!(other.system.connections.len(), 1);
+        assert_eq!(other.system.connections[0].endpoints[1].board, other.system.peripheral_boards[0]);
+        assert_eq!(other.selection.num_boards(), 2);
+
+        // a connection to a board that isn't in the clipboard is left out, rather than being
+        // pasted with fewer than two pins
+        let dangling = text.replace("board = \"oled1\"", "board = \"oled9\"");
+        assert_ne!(dangling, text);
+        let fragment = crate::project::selection::from_clipboard(&dangling).unwrap();
+        assert!(fragment.connections.is_empty());
+
+        // pasting into the same project makes new instances, but there can only be one main board
+        assert!(project.paste(&text));
+        assert_eq!(project.system.peripheral_boards.len(), 2);
+        let copy = project.system.peripheral_boards[1].clone();
+        assert_eq!(copy.get_designator(), "oled2");
+        let placement = project.system.get_placement(&copy);
+        assert_eq!(placement.x, project.system.get_placement(&display).x + 10.0);
+        // the pasted connection lost its pin on the main board, so it was left out
+        assert_eq!(project.system.connections.len(), 1);
+
+        // duplicating a peripheral on its own gets a new designator, and deleting removes it
+        project.selection.click_board(&display, false);
+        project.duplicate_selection();
+        assert_eq!(project.system.peripheral_boards[2].get_designator(), "oled3");
+        assert!(project.selection.contains_board(&project.system.peripheral_boards[2]));
+        project.delete_selection();
+        assert_eq!(project.system.peripheral_boards.len(), 2);
+        assert!(project.selection.is_empty());
+    }
+
+    #[test]
+    pub fn test_orthogonal_routing() {
+        use egui::{pos2, Rect};
+        use crate::project::routing::{self, RouteRequest};
+        use crate::project::system::{System, Waypoint};
+        let left = Rect::from_min_max(pos2(0.0, 0.0), pos2(20.0, 40.0));
+        let right = Rect::from_min_max(pos2(80.0, 0.0), pos2(100.0, 40.0));
+        let middle = Rect::from_min_max(pos2(40.0, -20.0), pos2(60.0, 30.0));
+        let obstacles = [left, right, middle];
+        let request = |y: f32| RouteRequest {
+            from: pos2(18.0, y),
+            from_board: Some(left),
+            to: pos2(82.0, y),
+            to_board: Some(right),
+            via: vec![],
+        };
+        let routes = routing::route_all(&obstacles, &[request(10.0), request(12.5)]);
+        let crosses = |a: egui::Pos2, b: egui::Pos2, rect: Rect| {
+            let segment = Rect::from_two_pos(a, b);
+            segment.max.x > rect.min.x && segment.min.x < rect.max.x && segment.max.y > rect.min.y && segment.min.y < rect.max.y
+        };
+        for route in routes.iter() {
+            assert_eq!(route.first(), Some(&pos2(18.0, route[0].y)));
+            assert_eq!(route.last().unwrap().x, 82.0);
+            for pair in route.windows(2) {
+                // every segment is horizontal or vertical, and none of them cross the middle board
+                assert!(pair[0].x == pair[1].x || pair[0].y == pair[1].y, "{:?} isn't orthogonal", pair);
+                assert!(!crosses(pair[0], pair[1], middle), "{:?} crosses the middle board", pair);
+            }
+        }
+        // the two wires run together around the middle board, side by side rather than on top
+        // of each other
+        let below = |route: &Vec<egui::Pos2>| route.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+        assert!(below(&routes[0]) > 30.0 && below(&routes[1]) > 30.0);
+        assert!((below(&routes[0]) - below(&routes[1])).abs() > 0.1);
+
+        // a route passes through its waypoints, which are saved along with the connection
+        let mut via = request(10.0);
+        via.via = vec![pos2(30.0, -30.0), pos2(70.0, -30.0)];
+        let route = routing::route_all(&obstacles, &[via]).remove(0);
+        let passes_through = |p: egui::Pos2| route.windows(2).any(|pair| Rect::from_two_pos(pair[0], pair[1]).contains(p));
+        assert!(passes_through(pos2(30.0, -30.0)) && passes_through(pos2(70.0, -30.0)));
+        let mut system: System = toml::from_str(
+            "[[peripheral_boards]]\nlibrary_id = \"a\"\nid = 0\ndesignator = \"a1\"\n\
+             [[peripheral_boards]]\nlibrary_id = \"b\"\nid = 1\ndesignator = \"b1\"\n\
+             [[connections]]\nname = \"c\"\nendpoints = [{ board = \"a1\", pin = \"x\" }, { board = \"b1\", pin = \"y\" }]\n\
+             interface_mapping = { pins = [], interface = { iface_type = \"GPIO\", direction = \"Unknown\" } }\n"
+        ).unwrap();
+        system.connections[0].waypoints = vec![Waypoint { x: 30.0, y: -20.0 }];
+        let loaded: System = toml::from_str(&toml::to_string(&system).unwrap()).unwrap();
+        assert_eq!(loaded.connections[0].waypoints, system.connections[0].waypoints);
+    }
+
+    #[test]
+    pub fn test_wire_colors() {
+        use egui::Color32;
+        use crate::app::colorscheme::{self, ColorScheme};
+        use crate::board::pinout::InterfaceType;
+        use crate::project::system::System;
+        // each color scheme has its own interface colors
+        let industrial = colorscheme::INDUSTRIAL_DARK;
+        assert_eq!(industrial.interface_color(InterfaceType::I2C), Color32::RED);
+        assert_ne!(colorscheme::SOLARIZED_DARK.interface_color(InterfaceType::I2C), Color32::RED);
+        // settings saved before color schemes had interface colors get the default ones
+        let mut saved = toml::Value::try_from(&industrial).unwrap();
+        saved.as_table_mut().unwrap().remove("interface_colors");
+        let loaded: ColorScheme = saved.try_into().unwrap();
+        assert_eq!(loaded.interface_color(InterfaceType::UART), ColorScheme::default().interface_color(InterfaceType::UART));
+
+        // a connection's own color is saved with the project
+        let mut system: System = toml::from_str(
+            "[[peripheral_boards]]\nlibrary_id = \"a\"\nid = 0\ndesignator = \"a1\"\n\
+             [[connections]]\nname = \"c\"\nendpoints = [{ board = \"a1\", pin = \"x\" }, { board = \"a1\", pin = \"y\" }]\n\
+             interface_mapping = { pins = [], interface = { iface_type = \"GPIO\", direction = \"Unknown\" } }\n"
+        ).unwrap();
+        assert_eq!(system.connections[0].color, None);
+        system.connections[0].color = Some(Color32::from_rgb(1, 2, 3));
+        let loaded: System = toml::from_str(&toml::to_string(&system).unwrap()).unwrap();
+        assert_eq!(loaded.connections[0].color, Some(Color32::from_rgb(1, 2, 3)));
+    }
+
+    #[test]
+    pub fn test_connection_table() {
+        use crate::board::pinout::InterfaceType;
+        use crate::project::system::{Connection, System};
+        use crate::project::table::{Column, TableState};
+        let boards = library_boards();
+        let feather = find_board(&boards, "Feather RP2040");
+        // the pins offered for a board are the ones in its pinout, without duplicates
+        let pins = feather.pinout_pins();
+        assert!(!pins.is_empty());
+        assert!(pins.iter().enumerate().all(|(i, p)| pins[..i].iter().all(|q| q.svg_id != p.svg_id)));
+
+        let mut system = System::default();
+        for (name, iface_type) in [("b_led", InterfaceType::GPIO), ("a_sda", InterfaceType::I2C), ("C_tx", InterfaceType::UART)] {
+            let mut c = Connection::new(name.to_string(), feather.clone(), pins[0].clone(), feather.clone(), pins[1].clone());
+            c.interface_mapping.interface.iface_type = iface_type;
+            system.connections.push(c);
+        }
+        // sorted by name ignoring case, and reversed by clicking the header again
+        let mut state = TableState::default();
+        assert_eq!(state.rows(&system.connections), vec![1, 0, 2]);
+        state.sort_by(Column::Name);
+        assert_eq!(state.rows(&system.connections), vec![2, 0, 1]);
+        state.sort_by(Column::Type);
+        assert!(state.ascending);
+        assert_eq!(state.rows(&system.connections), vec![0, 1, 2]);
+        // the filter matches the text of any column
+        state.filter = "i2c".to_string();
+        assert_eq!(state.rows(&system.connections), vec![1]);
+        state.filter = "C_T".to_string();
+        assert_eq!(state.rows(&system.connections), vec![2]);
+    }
+
+    #[test]
+    pub fn test_connection_table_edits() {
+        use crate::board;
+        let (mut project, _) = feather_project();
+        let main_board = project.system.main_board.clone().unwrap();
+        let display = project.system.peripheral_boards[0].clone();
+        let pin = |board: &board::Board, name: &str| board.find_pin(name).unwrap();
+        let system = &mut project.system;
+        system.connect_pins(&main_board, &pin(&main_board, "SDA"), &display, &pin(&display, "SDA")).unwrap();
+        system.connect_pins(&main_board, &pin(&main_board, "SCL"), &display, &pin(&display, "SCL")).unwrap();
+        let (sda, scl) = (system.connections[0].name.clone(), system.connections[1].name.clone());
+
+        // names stay unique and non-empty
+        assert!(system.rename_connection(&sda, &scl).is_err());
+        assert!(system.rename_connection(&sda, "").is_err());
+        system.rename_connection(&sda, "i2c_sda").unwrap();
+        let sda = "i2c_sda".to_string();
+
+        // a pin can't be connected to itself
+        assert!(system.set_endpoint(&sda, 1, &main_board, &pin(&main_board, "SDA")).is_err());
+        assert_eq!(system.connections[0].endpoints[1].board, display);
+
+        // moving an end to a pin of another net merges the nets, rather than duplicating the pin
+        system.set_endpoint(&sda, 1, &display, &pin(&display, "SCL")).unwrap();
+        assert_eq!(system.connections.len(), 1);
+        assert_eq!(system.connections[0].name, sda);
+        assert_eq!(system.connections[0].endpoints.len(), 3);
+        assert!(system.connections[0].contains(&main_board, &pin(&main_board, "SCL").svg_id));
+        assert!(system.find_connection(&display, &pin(&display, "SDA").svg_id).is_none());
+
+        // the wires of a bus only change along with their bus
+        system.connections.clear();
+        let feather_i2c = main_board.pinout.iter().find(|m| m.interface.iface_type == board::pinout::InterfaceType::I2C).unwrap().clone();
+        let oled_i2c = display.pinout.iter().find(|m| m.interface.iface_type == board::pinout::InterfaceType::I2C).unwrap().clone();
+        system.add_bus_connection(&main_board, &feather_i2c, &display, &oled_i2c).unwrap();
+        let wire = system.connections[0].name.clone();
+        assert!(system.set_endpoint(&wire, 0, &main_board, &pin(&main_board, "A0")).is_err());
+        let interface = system.connections[0].interface_mapping.interface.clone();
+        let mut changed = interface.clone();
+        changed.direction = match interface.direction {
+            board::pinout::InterfaceDirection::Output => board::pinout::InterfaceDirection::Input,
+            _ => board::pinout::InterfaceDirection::Output,
+        };
+        assert!(system.set_interface(&wire, changed.clone()).is_err());
+        assert_eq!(system.connections[0].interface_mapping.interface, interface);
+
+        // and other connections aren't merged into them
+        system.connect_pins(&main_board, &pin(&main_board, "A0"), &main_board, &pin(&main_board, "A1")).unwrap();
+        let net = system.connections.last().unwrap().name.clone();
+        let wire_end = system.connections[0].endpoints[1].clone();
+        let count = system.connections.len();
+        assert!(system.set_endpoint(&net, 1, &wire_end.board, &wire_end.pin).is_err());
+        assert!(system.connect_pins(&main_board, &pin(&main_board, "A1"), &wire_end.board, &wire_end.pin).is_err());
+        let other_wire = system.connections[1].endpoints[0].clone();
+        assert!(system.connect_pins(&wire_end.board, &wire_end.pin, &other_wire.board, &other_wire.pin).is_err());
+        assert_eq!(system.connections.len(), count);
+        assert_eq!(system.connections[0].interface_mapping.interface, interface);
+        assert!(system.connections.last().unwrap().contains(&main_board, &pin(&main_board, "A1").svg_id));
+        system.set_interface(&net, changed.clone()).unwrap();
+        assert_eq!(system.connections.last().unwrap().interface_mapping.interface, changed);
+    }
+
+    #[test]
+    pub fn test_update_library_boards() {
+        use std::path::Path;
+        use crate::board;
+        let manifests = board::get_manifests(Path::new("./iron-coder-boards"));
+        let mut project = Project::default();
+        project.set_library_boards(manifests.clone(), None);
+        project.add_board(find_board(&manifests, "Feather RP2040"));
+        project.add_board(find_board(&manifests, "OLED Featherwing (128x64)"));
+        let main_board = project.system.main_board.clone().unwrap();
+        let display = project.system.peripheral_boards[0].clone();
+        let pin = |board: &board::Board, name: &str| board.find_pin(name).unwrap();
+        project.system.connect_pins(&main_board, &pin(&main_board, "SDA"), &display, &pin(&display, "SDA")).unwrap();
+
+        // only the boards in the batch are replaced, in the system and in its connections
+        let decoded = library_boards();
+        project.update_library_boards(&decoded, &[main_board.library_id()]);
+        let main_board = project.system.main_board.clone().unwrap();
+        assert!(main_board.svg_board_info.is_some());
+        assert!(main_board.get_instance().is_some());
+        assert!(project.system.peripheral_boards[0].svg_board_info.is_none());
+        let endpoint = &project.system.connections[0].endpoints[0];
+        assert_eq!(endpoint.board, main_board);
+        assert!(endpoint.board.svg_board_info.is_some());
+        let names = |boards: &[board::Board]| boards.iter().map(|b| b.library_id()).collect::<Vec<_>>();
+        assert_eq!(names(&project.library_boards), names(&manifests));
+        assert!(find_board(&project.known_boards, "Feather RP2040").svg_board_info.is_some());
+
+        // a board that couldn't be decoded is dropped
+        project.update_library_boards(&[], &[display.library_id()]);
+        assert!(project.known_boards.iter().all(|b| !b.is_same_library_board(&display)));
+        assert_eq!(project.library_boards.len(), manifests.len() - 1);
+    }
+
+    #[test]
+    pub fn test_local_board_loader() {
+        use std::path::Path;
+        use crate::board::{self, BoardRootKind};
+        // a project whose library overrides the Feather RP2040, and has an OLED that can't be decoded
+        let dir = std::env::temp_dir().join("iron_coder_test_local_board_loader");
+        let _ = std::fs::remove_dir_all(&dir);
+        let src_dir = Path::new("./iron-coder-boards/Adafruit");
+        let feather_dir = dir.join("boards/Acme/Feather_RP2040");
+        std::fs::create_dir_all(&feather_dir).unwrap();
+        std::fs::copy(src_dir.join("Feather_RP2040/feather_rp2040.toml"), feather_dir.join("feather_rp2040.toml")).unwrap();
+        std::fs::copy(src_dir.join("Feather_RP2040/feather_rp2040.svg"), feather_dir.join("feather_rp2040.svg")).unwrap();
+        let oled_dir = dir.join("boards/Acme/FeatherWing_OLED_128x64");
+        std::fs::create_dir_all(&oled_dir).unwrap();
+        std::fs::copy(src_dir.join("FeatherWing_OLED_128x64/featherwing_oled_128x64.toml"), oled_dir.join("featherwing_oled_128x64.toml")).unwrap();
+        std::fs::write(oled_dir.join("featherwing_oled_128x64.svg"), "not an svg").unwrap();
+
+        // the project-local boards are listed right away, and decoded in the background
+        let boards = library_boards();
+        let cache_dir = dir.join("cache");
+        let mut project = Project { location: Some(dir.clone()), ..Default::default() };
+        project.set_library_boards(boards.clone(), Some(cache_dir.clone()));
+        let feather = find_board(&project.known_boards, "Feather RP2040");
+        assert_eq!(feather.get_library_root().map(|root| root.kind), Some(BoardRootKind::Project));
+        assert!(feather.svg_board_info.is_none());
+        project.add_board(feather);
+        while project.poll_board_loader() {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let main_board = project.system.main_board.clone().unwrap();
+        assert_eq!(main_board.get_library_root().map(|root| root.kind), Some(BoardRootKind::Project));
+        assert!(main_board.svg_board_info.is_some());
+        // the OLED falls back to the library board it overrides
+        let oled = find_board(&project.known_boards, "OLED Featherwing (128x64)");
+        assert_ne!(oled.get_library_root().map(|root| root.kind), Some(BoardRootKind::Project));
+        assert!(oled.svg_board_info.is_some());
+        // and the project-local boards are cached apart from the library boards
+        let local_cache_dir = board::loader::local_cache_dir(&cache_dir, &dir.join("boards"));
+        assert!(std::fs::read_dir(&local_cache_dir).unwrap().count() > 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_keyboard_connections() {
+        use crate::project::picker;
+        // the characters of the query appear in order, and whole words beat scattered letters
+        assert!(picker::fuzzy_score("f1sda", "feather1:SDA / GPIO2").is_some());
+        assert!(picker::fuzzy_score("adsf", "feather1:SDA").is_none());
+        assert!(picker::fuzzy_score("sda", "oled1:SDA") > picker::fuzzy_score("sda", "oled1:SCL / D4 / A"));
+
+        let (mut project, _) = feather_project();
+        let main_board = project.system.main_board.clone().unwrap();
+        let display = project.system.peripheral_boards[0].clone();
+
+        // every pin of every board can be picked, and the best match comes first
+        let choices = picker::choices(&project.system);
+        assert!(choices.iter().any(|c| c.board == main_board) && choices.iter().any(|c| c.board == display));
+        let best = picker::matches(&choices, "oled1:sda")[0];
+        assert_eq!(best.board, display);
+        assert_eq!(best.pin, display.find_pin("SDA").unwrap());
+        assert!(picker::matches(&choices, "").len() == choices.len());
+
+        // tab cycles through the boards, wrapping around in both directions
+        project.selection.select_next_board(&project.system, false);
+        assert!(project.selection.contains_board(&main_board));
+        project.selection.select_next_board(&project.system, false);
+        assert!(project.selection.contains_board(&display) && project.selection.num_boards() == 1);
+        project.selection.select_next_board(&project.system, false);
+        assert!(project.selection.contains_board(&main_board));
+        project.selection.select_next_board(&project.system, true);
+        assert!(project.selection.contains_board(&display));
+
+        // each press of an arrow key nudges the selection as an edit of its own, while the
+        // repeats of a key that's held down are a single edit. egui marks each press of a key
+        // that hasn't been released as a repeat.
+        let ctx = egui::Context::default();
+        let mut run_frame = |project: &mut Project, presses: &[bool]| {
+            let events = presses.iter().map(|pressed| {
+                egui::Event::Key { key: egui::Key::ArrowRight, physical_key: None, pressed: *pressed, repeat: false, modifiers: Default::default() }
+            }).collect();
+            let input = egui::RawInput { events, ..Default::default() };
+            let _ = ctx.run(input, |ctx| {
+                egui::CentralPanel::default().show(ctx, |ui| project.display_system_editor_boards(ctx, ui));
+            });
+        };
+        let x = project.system.get_placement(&display).x;
+        run_frame(&mut project, &[true, false]);
+        run_frame(&mut project, &[true, false]);
+        run_frame(&mut project, &[true]);
+        run_frame(&mut project, &[true]);
+        run_frame(&mut project, &[true, false]);
+        assert_eq!(project.system.get_placement(&display).x, x + 5.0);
+        project.undo();
+        assert_eq!(project.system.get_placement(&display).x, x + 2.0);
+        project.undo();
+        assert_eq!(project.system.get_placement(&display).x, x + 1.0);
+    }
+
+    #[test]
+    pub fn test_feather_stacking() {
+        use crate::board;
+        use crate::project::canvas;
+        use crate::project::system::Placement;
+        let (mut project, _) = feather_project();
+        let main_board = project.system.main_board.clone().unwrap();
+        // the library describes where the Feather's header pins are
+        let geometry = main_board.get_standard_geometry().unwrap();
+        assert_eq!(geometry.header_offset("J1-1"), Some(egui::vec2(0.0, 0.0)));
+        assert_eq!(geometry.header_offset("J2-1"), Some(egui::vec2(20.32, 10.16)));
+        assert_eq!(geometry.header_offset("J2-13"), None);
+
+        let display = project.system.peripheral_boards[0].clone();
+        project.system.set_placement(&main_board, Placement { x: 10.0, y: 20.0, ..Default::default() });
+        assert!(project.system.stackable_on(&main_board).is_none());
+        assert_eq!(project.system.stackable_on(&display), Some(main_board.clone()));
+
+        // stacking connects the pins at the same header positions, and lines them up
+        project.system.stack(&display).unwrap();
+        let names: Vec<&str> = project.system.connections.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["oled1_scl", "oled1_sda"]);
+        assert_eq!(project.system.connections[0].interface_mapping.interface.iface_type, board::pinout::InterfaceType::I2C);
+        let pin = |system: &crate::project::system::System, board: &board::Board, svg_id: &str| canvas::pin_location(system, board, svg_id).unwrap();
+        assert!((pin(&project.system, &main_board, "sda") - pin(&project.system, &display, "sda")).length() < 1.0);
+        assert!(project.system.stackable_on(&display).is_none());
+
+        // moving the stacked board moves the whole stack
+        let mut placement = project.system.get_placement(&display);
+        placement.x += 5.0;
+        project.system.set_placement(&display, placement);
+        assert_eq!(project.system.get_placement(&main_board).x, 15.0);
+        assert!((pin(&project.system, &main_board, "scl") - pin(&project.system, &display, "scl")).length() < 1.0);
+
+        // the stack survives saving, and unstacking takes its connections away
+        let saved: crate::project::system::System = toml::from_str(&toml::to_string(&project.system).unwrap()).unwrap();
+        assert_eq!(saved.get_placement(&display).stacked_on, main_board.get_instance_id());
+        assert!(saved.connections.iter().flat_map(|c| c.endpoints.iter()).all(|e| e.stacked_by == display.get_instance_id()));
+        project.system.unstack(&display).unwrap();
+        assert!(project.system.connections.is_empty());
+        assert!(project.system.stack_base(&display).is_none());
+
+        // ...but not the connections that the user made to the header pins
+        let pin = |board: &board::Board, name: &str| board.find_pin(name).unwrap();
+        project.system.connect_pins(&display, &pin(&display, "SDA"), &main_board, &pin(&main_board, "A0")).unwrap();
+        project.system.stack(&display).unwrap();
+        assert_eq!(project.system.connections[0].endpoints.len(), 3);
+        project.system.unstack(&display).unwrap();
+        assert_eq!(project.system.connections.len(), 1);
+        assert!(project.system.connections[0].contains(&display, "sda"));
+        assert!(!project.system.connections[0].contains(&main_board, "sda"));
+    }
+
+    #[test]
+    pub fn test_breadboard_layout() {
+        use crate::board;
+        use crate::project::breadboard;
+        let (mut project, boards) = feather_project();
+        project.add_board(find_board(&boards, "PiTFT 3.2 with Capacitive Touch Screen"));
+        let main_board = project.system.main_board.clone().unwrap();
+        let display = project.system.peripheral_boards[0].clone();
+        let breakout = project.system.peripheral_boards[1].clone();
+        project.system.stack(&display).unwrap();
+
+        // the Feather straddles the center channel, with its headers in columns "c" and "i"
+        let layout = breadboard::layout(&project.system);
+        assert_eq!(layout.footprints.len(), 2);
+        let footprint = &layout.footprints[0];
+        assert_eq!(footprint.board, main_board);
+        assert_eq!(footprint.stacked, vec![display.clone()]);
+        assert_eq!(layout.hole_of(&main_board, "rst").unwrap().name(), "c3");
+        assert_eq!(layout.hole_of(&main_board, "sda").unwrap().column, 8);
+        // the PiTFT has no header positions, so it's plugged in like a breakout in column "e"
+        let footprint = &layout.footprints[1];
+        assert_eq!(footprint.board, breakout);
+        assert_eq!((footprint.min.column, footprint.max.column), (4, 4));
+        assert!(footprint.min.row > layout.footprints[0].max.row);
+
+        // the stacked display's pins are already in the rows of the Feather's pins, so the
+        // connections made by stacking it need no jumpers
+        assert!(project.system.connections.iter().any(|c| c.name == "oled1_sda"));
+        assert!(layout.jumpers.iter().all(|j| project.system.connections[j.connection].name != "oled1_sda"));
+        assert!(layout.jumpers.iter().all(|j| !breadboard::same_strip(j.from.hole, j.to.hole)));
+
+        // each connection is a chain of jumpers, colored by what it carries
+        let gnd = main_board.get_pin_by_svg_id("gnd");
+        let breakout_pin = breakout.get_pin_by_svg_id("gnd");
+        project.system.connect_pins(&main_board, &gnd, &breakout, &breakout_pin).unwrap();
+        let last = project.system.connections.len() - 1;
+        project.system.connections[last].interface_mapping.interface.iface_type = board::pinout::InterfaceType::GND;
+        let layout = breadboard::layout(&project.system);
+        assert!(layout.jumpers.iter().all(|j| !breadboard::same_strip(j.from.hole, j.to.hole)));
+        assert_eq!(layout.jumpers.iter().filter(|j| j.connection == last).count(), 1);
+        let jumper = layout.jumpers.iter().find(|j| j.connection == last).unwrap();
+        assert_eq!(jumper.color, egui::Color32::from_rgb(0x20, 0x20, 0x20));
+        assert_eq!(jumper.from.hole.row, layout.hole_of(&main_board, "gnd").unwrap().row);
+        project.system.connections[last].color = Some(egui::Color32::GREEN);
+        assert_eq!(breadboard::jumper_color(&project.system.connections[last], last), egui::Color32::GREEN);
+    }
+}